@@ -0,0 +1,154 @@
+//! Benchmarks of the core steps on synthetic genomes.
+//!
+//! The input size is configurable with `BENCH_RECORDS` (default 50) and `BENCH_LENGTH`
+//! (default 5000), the generator is seeded so the inputs are the same on every run.
+use criterion::{Criterion, criterion_group, criterion_main};
+use od_msspe::config::{DuplicateNames, PrimerConfig, ProgramConfig, Strategy, TmMethod};
+use od_msspe::{
+    Direction, PartitioningOption, SequenceRecord, find_candidates_kmers, find_kmers,
+    get_segment_manager,
+};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::hint::black_box;
+
+const SEED: u64 = 42;
+const BASES: [char; 4] = ['A', 'C', 'G', 'T'];
+
+fn env_or(name: &str, default: usize) -> usize {
+    std::env::var(name)
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(default)
+}
+
+/// Synthetic genomes sharing a common ancestor, each with random substitutions at 2% of sites.
+fn get_synthetic_records(n: usize, length: usize) -> Vec<SequenceRecord> {
+    let mut rng = StdRng::seed_from_u64(SEED);
+    let ancestor: Vec<char> = (0..length).map(|_| BASES[rng.gen_range(0..4)]).collect();
+    (0..n)
+        .map(|i| {
+            let mut sequence = ancestor.clone();
+            for _ in 0..length / 50 {
+                let pos = rng.gen_range(0..length);
+                sequence[pos] = BASES[rng.gen_range(0..4)];
+            }
+            SequenceRecord::new(format!("seq{}", i), sequence.into_iter().collect())
+        })
+        .collect()
+}
+
+fn get_program_config() -> ProgramConfig {
+    ProgramConfig {
+        ntthal_path: "".to_string(),
+        primer3_path: "".to_string(),
+        blastn_path: "".to_string(),
+        specificity_db: None,
+        max_off_target_mismatch: 2,
+        max_iterations: 1000,
+        max_mismatch_segments: 1,
+        target_coverage: 1.0,
+        max_mismatch: 0,
+        min_frequency: 0.0,
+        max_direction_ratio: 0.0,
+        strategy: Strategy::MaxFrequency,
+        keep_all: false,
+        check_cross_dimers: true,
+        check_self_dimers: true,
+        check_hairpin: false,
+        tm_method: TmMethod::Nn,
+        tm_stddev: 2.0,
+        tm_fallback_margin: 5.0,
+        disable_tm_stddev: false,
+        do_align: false,
+        soft_mask: false,
+        duplicate_names: DuplicateNames::Rename,
+        threads: 0,
+        align_timeout: 0,
+        align_retry: false,
+        progress: false,
+        dedupe_rev_comp: true,
+        seed: None,
+        deterministic: false,
+        complement_table: None,
+        primer_config: PrimerConfig {
+            kmer_size: 13,
+            min_tm: 30.0,
+            max_tm: 60.0,
+            max_self_dimer_any_tm: 20.0,
+            max_self_dimer_end_tm: 20.0,
+            max_hairpin_tm: 20.0,
+            gc_clamp_length: 3,
+            min_gc_clamp: 1,
+            min_gc: 0.0,
+            max_gc: 100.0,
+            min_complexity: 0.75,
+            max_mono_repeats: 5,
+            max_di_repeats: 4,
+            max_tri_repeats: 0,
+            max_three_prime_run: 2,
+            min_three_prime_dg: -8.0,
+            min_self_fold_dg: f32::NEG_INFINITY,
+            annealing_temp: 25.0,
+            annealing_tm_margin: 5.0,
+            mv_conc: 50.0,
+            dv_conc: 3.0,
+            dntp_conc: 0.0,
+            dna_conc: 250.0,
+        },
+    }
+}
+
+fn bench_core(c: &mut Criterion) {
+    let records = get_synthetic_records(env_or("BENCH_RECORDS", 50), env_or("BENCH_LENGTH", 5000));
+
+    c.bench_function("get_segment_manager", |b| {
+        b.iter(|| {
+            get_segment_manager(black_box(&records), PartitioningOption::default())
+                .map(|manager| manager.segments.len())
+        })
+    });
+
+    c.bench_function("find_kmers", |b| {
+        b.iter(|| {
+            records
+                .iter()
+                .map(|record| find_kmers(black_box(&record.sequence), 13).len())
+                .sum::<usize>()
+        })
+    });
+
+    // the search windows of the default 50 bases, the hot path of the segmentation
+    let windows: Vec<&str> = records
+        .iter()
+        .flat_map(|record| {
+            (0..record.sequence.len().saturating_sub(50))
+                .step_by(50)
+                .map(|start| &record.sequence[start..start + 50])
+        })
+        .collect();
+    c.bench_function("find_kmers_windows", |b| {
+        b.iter(|| {
+            windows
+                .iter()
+                .map(|window| find_kmers(black_box(window), 13).len())
+                .sum::<usize>()
+        })
+    });
+
+    let segment_manager = get_segment_manager(&records, PartitioningOption::default()).unwrap();
+    let config = get_program_config();
+    c.bench_function("find_candidates_kmers", |b| {
+        b.iter(|| {
+            find_candidates_kmers(
+                black_box(&segment_manager),
+                Direction::Forward,
+                config.clone(),
+            )
+            .map(|candidates| candidates.len())
+        })
+    });
+}
+
+criterion_group!(benches, bench_core);
+criterion_main!(benches);
@@ -0,0 +1,26 @@
+"""Tests of the Python bindings, run with `maturin develop && pytest python/tests`."""
+import od_msspe
+
+
+def test_find_kmers():
+    assert od_msspe.find_kmers("ACGTAC", 3) == ["ACG", "CGT", "GTA", "TAC"]
+
+
+def test_reverse_complement():
+    assert od_msspe.reverse_complement("ACGR") == "YCGT"
+
+
+def test_get_tm():
+    assert od_msspe.get_tm("ACGTACGTACGTA") < od_msspe.get_tm("ACGTACGTACGTA", mv_conc=100.0)
+
+
+def test_find_candidate_primers():
+    genome = "ACTGAGGTATTAACGTACGTTT" * 10
+    primers = od_msspe.find_candidate_primers(
+        [genome, genome],
+        {"window_size": 100, "overlap_size": 50, "count_occurrences": False},
+    )
+    assert {p["direction"] for p in primers} == {"F", "R"}
+    for primer in primers:
+        assert len(primer["sequence"]) == 13
+        assert primer["frequency"] > 1
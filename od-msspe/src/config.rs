@@ -1,32 +1,495 @@
 pub(crate) use crate::constants::{
-    ANNEALING_TEMP, DEFAULT_NTTHAL_PATH, DEFAULT_PRIMER3_PATH, DELTA_G_THRESHOLD, DNA_CONC,
-    DNTP_CONC, DV_CONC, KMER_SIZE, MAX_ITERATIONS, MAX_MISMATCH_SEGMENTS, MV_CONC, OVERLAP_SIZE,
-    PRIMER_MAX_HAIRPIN_TH, PRIMER_MAX_SELF_ANY_TH, PRIMER_MAX_SELF_END_TH, PRIMER_MAX_TM,
-    PRIMER_MIN_TM, SEARCH_WINDOWS_SIZE, WINDOW_SIZE,
+    ALIGN_TIMEOUT, ANNEALING_TEMP, ANNEALING_TM_MARGIN, COLLAPSE_DEGENERACY, DEFAULT_BLASTN_PATH,
+    DEFAULT_NTTHAL_PATH, DEFAULT_PRIMER3_PATH, DELTA_G_THRESHOLD, DNA_CONC, DNTP_CONC, DV_CONC,
+    GC_CLAMP_LENGTH, KMER_SIZE, MASK_PROXIMITY, MAX_AMPLICON_LENGTH, MAX_DEGENERACY, MAX_DELTA_TM,
+    MAX_DI_REPEATS, MAX_DIRECTION_RATIO, MAX_GC, MAX_ITERATIONS, MAX_MISMATCH,
+    MAX_MISMATCH_SEGMENTS, MAX_MONO_REPEATS, MAX_N, MAX_N_LIMIT, MAX_OFF_TARGET_MISMATCH,
+    MAX_POOL_TM_STD, MAX_THREE_PRIME_RUN, MAX_TRI_REPEATS, MIN_AMPLICON_LENGTH, MIN_COMPLEXITY,
+    MIN_FREQUENCY, MIN_GC, MIN_GC_CLAMP, MIN_PRIMER_SPACING, MIN_SELF_FOLD_DG, MIN_THREE_PRIME_DG,
+    MV_CONC, OVERLAP_SIZE, PRIMER_MAX_HAIRPIN_TH, PRIMER_MAX_SELF_ANY_TH, PRIMER_MAX_SELF_END_TH,
+    PRIMER_MAX_TM, PRIMER_MIN_TM, SEARCH_WINDOWS_SIZE, STDIO_PATH, TARGET_COVERAGE, THREADS,
+    TM_FALLBACK_MARGIN, WINDOW_SIZE,
 };
+use std::collections::HashMap;
 use std::path::Path;
 
-use clap::Parser;
+use clap::{Parser, ValueEnum};
+use serde::Serialize;
 
-#[derive(Parser, Debug)]
+#[derive(ValueEnum, Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    Csv,
+    /// Tab-separated values, with the same columns as the CSV
+    Tsv,
+    Json,
+    /// JSON Lines, one primer object per line
+    Jsonl,
+    /// Primer3 boulder-IO records
+    Primer3,
+}
+
+/// Columns of the CSV and TSV outputs, named as in their header
+#[derive(ValueEnum, Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[value(rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum Column {
+    Direction,
+    Name,
+    /// The primer sequence
+    #[value(alias = "sequence")]
+    Primers,
+    #[value(alias = "gc_percent")]
+    Gc,
+    GcMin,
+    GcMax,
+    /// Mean Tm of the primers of the direction
+    Avg,
+    Std,
+    Tm,
+    TmMax,
+    TmOk,
+    Frequency,
+    Degeneracy,
+    Sequences,
+    SelfBindingCount,
+    Constituents,
+    SelfAnyTh,
+    SelfEndTh,
+    HairpinTh,
+    ThreePrimeDg,
+    SelfFoldDg,
+    Runs,
+    ThreePrimeRun,
+    GcClamp,
+    ComplexityOk,
+    SpecificityOk,
+    NearMasked,
+    Species,
+    TaxId,
+}
+
+/// Every column in the order of the CSV header
+pub const DEFAULT_COLUMNS: [Column; 29] = [
+    Column::Direction,
+    Column::Name,
+    Column::Primers,
+    Column::Gc,
+    Column::GcMin,
+    Column::GcMax,
+    Column::Avg,
+    Column::Std,
+    Column::Tm,
+    Column::TmMax,
+    Column::TmOk,
+    Column::Frequency,
+    Column::Degeneracy,
+    Column::Sequences,
+    Column::SelfBindingCount,
+    Column::Constituents,
+    Column::SelfAnyTh,
+    Column::SelfEndTh,
+    Column::HairpinTh,
+    Column::ThreePrimeDg,
+    Column::SelfFoldDg,
+    Column::Runs,
+    Column::ThreePrimeRun,
+    Column::GcClamp,
+    Column::ComplexityOk,
+    Column::SpecificityOk,
+    Column::NearMasked,
+    Column::Species,
+    Column::TaxId,
+];
+
+#[derive(ValueEnum, Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    Text,
+    /// One JSON object per log record
+    Json,
+}
+
+#[derive(ValueEnum, Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Alphabet {
+    /// Primers with T
+    Dna,
+    /// Primers with U instead of T
+    Rna,
+}
+
+#[derive(ValueEnum, Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SortBy {
+    /// Most conserved primers first
+    Frequency,
+    /// Highest Tm first
+    Tm,
+    /// Highest GC content first
+    Gc,
+    /// By the start of the first partition the primer binds
+    Position,
+}
+
+#[derive(ValueEnum, Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum TmMethod {
+    /// SantaLucia (1998) nearest-neighbor model, using the configured salt concentrations
+    Nn,
+    /// Tm as reported by primer3_core
+    Primer3,
+    /// GC content formula with Schildkraut-Lippincott salt correction
+    Basic,
+    /// Wallace rule `2 * (A + T) + 4 * (G + C)`
+    Wallace,
+    /// Wallace rule below 14 nt, the GC content formula otherwise
+    Auto,
+}
+
+#[derive(ValueEnum, Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Strategy {
+    /// K-mer with the highest frequency in the uncovered segments
+    MaxFrequency,
+    /// K-mer newly covering the most segments (greedy set cover), ties by frequency
+    MaxCoverage,
+}
+
+#[derive(ValueEnum, Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum DuplicateNames {
+    /// Rename the later records with a `_<n>` suffix
+    Rename,
+    /// Fail on the first duplicate
+    Error,
+}
+
+#[derive(Parser, Serialize, Debug)]
 #[command(version, about, long_about=None)]
 pub struct Args {
-    #[arg(short, long)]
-    pub input: String,
+    #[arg(
+        short,
+        long,
+        num_args = 1..,
+        required_unless_present_any = ["load_index", "update"],
+        help = "Input FASTA files, records of all files are aligned and designed together. Use - to read from stdin."
+    )]
+    pub input: Vec<String>,
 
-    #[arg(short, long)]
+    #[arg(
+        short,
+        long,
+        default_value = STDIO_PATH,
+        help = "Output file of the designed primers, - or empty writes to stdout."
+    )]
     pub output: String,
 
+    #[arg(
+        long,
+        env = "FORMAT",
+        value_enum,
+        default_value_t = OutputFormat::Csv,
+        help = "Output format of the designed primers."
+    )]
+    pub format: OutputFormat,
+
+    // not COLUMNS, which shells set to the terminal width
+    #[arg(
+        long,
+        env = "OUTPUT_COLUMNS",
+        value_enum,
+        value_delimiter = ',',
+        default_values_t = DEFAULT_COLUMNS,
+        help = "Comma-separated columns of the CSV and TSV outputs, in this order."
+    )]
+    pub columns: Vec<Column>,
+
+    #[arg(
+        long,
+        env = "ALPHABET",
+        value_enum,
+        default_value_t = Alphabet::Dna,
+        help = "Alphabet of the reported primers, only the output is converted."
+    )]
+    pub alphabet: Alphabet,
+
+    #[arg(
+        long,
+        env = "SORT_BY",
+        value_enum,
+        default_value_t = SortBy::Frequency,
+        help = "Order of the primers of each direction in the outputs."
+    )]
+    pub sort_by: SortBy,
+
+    #[arg(
+        group = "flag",
+        long,
+        env = "NAME_BY_POSITION",
+        default_value = "false",
+        value_parser = ["true", "false"],
+        help = "Names the primers by the sequence and start they first bind, like SEQ_1024_F, instead of their index."
+    )]
+    pub name_by_position: String,
+
+    #[arg(
+        long,
+        env = "TOP_N",
+        help = "Write only the first N primers in the --sort-by order, after all the filters."
+    )]
+    pub top_n: Option<usize>,
+
+    #[arg(
+        long,
+        env = "FASTA",
+        help = "Also write the primers as FASTA to this path, alongside the main output."
+    )]
+    pub fasta: Option<String>,
+
+    #[arg(
+        long,
+        env = "COVERAGE",
+        help = "Write a per-segment coverage summary of the primers as TSV to this path."
+    )]
+    pub coverage: Option<String>,
+
+    #[arg(
+        long,
+        env = "TILING",
+        help = "Write the ungapped interval of every primer on each segment it binds as TSV to this path."
+    )]
+    pub tiling: Option<String>,
+
+    #[arg(
+        long,
+        env = "GFF",
+        help = "Write the binding sites of the primers as GFF3 primer_binding_site features to this path."
+    )]
+    pub gff: Option<String>,
+
+    #[arg(
+        long,
+        env = "DIMER_MATRIX",
+        help = "Write the dimer ΔG of every pair of primers as a TSV matrix to this path."
+    )]
+    pub dimer_matrix: Option<String>,
+
+    #[arg(
+        long,
+        env = "PAIRS",
+        help = "Write forward/reverse primer pairs of every segment as CSV to this path."
+    )]
+    pub pairs: Option<String>,
+
+    #[arg(
+        long,
+        env = "TRACE",
+        help = "Write every iteration of the greedy primer selection as TSV to this path."
+    )]
+    pub trace: Option<String>,
+
+    #[arg(
+        long,
+        env = "REPORT",
+        help = "Write the filter summary (Tm threshold, primers removed per filter) as TSV to this path."
+    )]
+    pub report: Option<String>,
+
+    #[arg(
+        long,
+        env = "EXPLAIN",
+        help = "Write every candidate primer removed by the filters or the dimer check, with all the criteria it fails, as TSV to this path."
+    )]
+    pub explain: Option<String>,
+
+    #[arg(
+        long,
+        env = "MANIFEST",
+        help = "Write a JSON manifest of all parameters, input checksums and the tool version to this path."
+    )]
+    pub manifest: Option<String>,
+
+    #[arg(
+        long,
+        env = "SAVE_INDEX",
+        help = "Save the aligned sequences and their segments and k-mers to this path, to reuse with --load-index."
+    )]
+    pub save_index: Option<String>,
+
+    #[arg(
+        long,
+        env = "LOAD_INDEX",
+        help = "Load the sequences, segments and k-mers saved with --save-index, skipping the alignment and segmentation."
+    )]
+    pub load_index: Option<String>,
+
+    #[arg(
+        long,
+        env = "UPDATE",
+        requires = "add",
+        conflicts_with = "load_index",
+        help = "Add the sequences of --add to the index saved with --save-index and write it back to this path, then design from the updated index."
+    )]
+    pub update: Option<String>,
+
+    #[arg(
+        long,
+        num_args = 1..,
+        requires = "update",
+        help = "FASTA files of the sequences to add to the index of --update, aligned to the indexed sequences with MAFFT --add --keeplength."
+    )]
+    pub add: Vec<String>,
+
+    #[arg(
+        long,
+        env = "MIN_AMPLICON_LENGTH",
+        default_value_t = MIN_AMPLICON_LENGTH,
+        help = "Minimum amplicon length of a primer pair."
+    )]
+    pub min_amplicon_length: usize,
+
+    #[arg(
+        long,
+        env = "MAX_AMPLICON_LENGTH",
+        default_value_t = MAX_AMPLICON_LENGTH,
+        help = "Maximum amplicon length of a primer pair."
+    )]
+    pub max_amplicon_length: usize,
+
+    #[arg(
+        long,
+        env = "MAX_DELTA_TM",
+        default_value_t = MAX_DELTA_TM,
+        help = "Maximum Tm difference between the forward and reverse primer of a pair."
+    )]
+    pub max_delta_tm: f32,
+
+    #[arg(
+        long,
+        env = "SPECIES_NAME",
+        default_value = "",
+        help = "Species name reported when the FASTA headers do not contain one."
+    )]
+    pub species_name: String,
+
+    #[arg(
+        long,
+        env = "TAX_ID",
+        default_value = "",
+        help = "Taxonomy id reported when the FASTA headers do not contain one."
+    )]
+    pub tax_id: String,
+
     #[arg(long, env = "KMER_SIZE", default_value_t = KMER_SIZE)]
     pub kmer_size: usize,
 
+    #[arg(
+        long,
+        env = "KMER_SIZES",
+        value_delimiter = ',',
+        help = "Comma separated k-mer sizes to sweep, the size with the best coverage is used instead of --kmer-size."
+    )]
+    pub kmer_sizes: Vec<usize>,
+
     #[arg(long, env = "WINDOW_SIZE", default_value_t = WINDOW_SIZE)]
     pub window_size: usize,
 
     #[arg(long, env = "OVERLAP_SIZE", default_value_t = OVERLAP_SIZE)]
     pub overlap_size: usize,
-    #[arg(long, env = "MAX_MISMATCH_SEGMENTS", default_value_t = MAX_MISMATCH_SEGMENTS)]
+    #[arg(
+        long,
+        env = "AMPLICON_SIZE",
+        conflicts_with_all = ["window_size", "overlap_size"],
+        help = "Target amplicon length, sets --window-size and --overlap-size from it and --search-windows-size."
+    )]
+    pub amplicon_size: Option<usize>,
+    #[arg(
+        long,
+        env = "MAX_MISMATCH_SEGMENTS",
+        default_value_t = MAX_MISMATCH_SEGMENTS,
+        help = "Stop selecting primers once at most this many segments remain uncovered."
+    )]
     pub max_mismatch_segments: usize,
+    #[arg(
+        long,
+        env = "TARGET_COVERAGE",
+        default_value_t = TARGET_COVERAGE,
+        help = "Stop selecting primers once this fraction of the segments is covered, e.g. 0.95 for a smaller pool."
+    )]
+    pub target_coverage: f32,
+    #[arg(
+        long,
+        env = "MAX_MISMATCH",
+        default_value_t = MAX_MISMATCH,
+        help = "Mismatches allowed for a primer to count a segment as covered, 0 only counts exact matches."
+    )]
+    pub max_mismatch: usize,
+    #[arg(
+        long,
+        env = "MAX_DEGENERACY",
+        default_value_t = MAX_DEGENERACY,
+        help = "Collapse k-mers varying across the sequences into IUPAC degenerate primers of at most this many expansions, 1 disables degenerate primers."
+    )]
+    pub max_degeneracy: usize,
+    #[arg(
+        long,
+        env = "COLLAPSE_DEGENERACY",
+        default_value_t = COLLAPSE_DEGENERACY,
+        help = "Collapse selected primers binding the same partitions into an IUPAC consensus of at most this many expansions, 1 keeps every primer."
+    )]
+    pub collapse_degeneracy: usize,
+    #[arg(
+        long,
+        env = "MIN_PRIMER_SPACING",
+        default_value_t = MIN_PRIMER_SPACING,
+        help = "Drop selected primers binding within this many bases of a better primer of the same direction on any sequence, 0 disables the spacing."
+    )]
+    pub min_primer_spacing: usize,
+    #[arg(
+        long,
+        env = "MAX_N",
+        default_value_t = MAX_N,
+        value_parser = parse_max_n,
+        help = "Keep k-mers with up to this many ambiguous bases (N), matching any base, 0 drops every k-mer with an N. At most 3."
+    )]
+    pub max_n: usize,
+    #[arg(
+        long,
+        env = "REGION",
+        value_parser = parse_region,
+        help = "Design primers only within start:end of the aligned sequences, 0-based with the end exclusive."
+    )]
+    pub region: Option<(usize, usize)>,
+
+    #[arg(
+        long,
+        env = "MASK",
+        help = "BED of ranges of the aligned sequences (0-based, end exclusive) no primer may overlap, e.g. recombination hotspots."
+    )]
+    pub mask: Option<String>,
+    #[arg(
+        long,
+        env = "MIN_FREQUENCY",
+        default_value_t = MIN_FREQUENCY,
+        help = "Minimum frequency of a primer, a count or below 1 a fraction of the input sequences."
+    )]
+    pub min_frequency: f32,
+    #[arg(
+        long,
+        env = "MAX_DIRECTION_RATIO",
+        default_value_t = MAX_DIRECTION_RATIO,
+        help = "Cap the primers of one direction at this many times the primers of the other direction, below 1 disables the cap."
+    )]
+    pub max_direction_ratio: f32,
+    #[arg(
+        long,
+        env = "STRATEGY",
+        value_enum,
+        default_value_t = Strategy::MaxFrequency,
+        help = "Score of the k-mers in the greedy primer selection."
+    )]
+    pub strategy: Strategy,
     #[arg(long, env = "MAX_ITERATIONS", default_value_t = MAX_ITERATIONS)]
     pub max_iterations: usize,
     #[arg(long, env = "SEARCH_WINDOWS_SIZE", default_value_t = SEARCH_WINDOWS_SIZE)]
@@ -41,6 +504,21 @@ pub struct Args {
     pub dna_conc: f32,
     #[arg(long, env = "ANNEALING_TEMP", default_value_t = ANNEALING_TEMP)]
     pub annealing_temp: f32,
+    #[arg(
+        long,
+        env = "ANNEALING_TM_MARGIN",
+        default_value_t = ANNEALING_TM_MARGIN,
+        help = "Minimum distance of a primer Tm above the annealing temperature."
+    )]
+    pub annealing_tm_margin: f32,
+    #[arg(
+        long,
+        env = "TM_METHOD",
+        value_enum,
+        default_value_t = TmMethod::Nn,
+        help = "Method used to calculate Tm of the primers."
+    )]
+    pub tm_method: TmMethod,
     #[arg(long, env = "MIN_TM", default_value_t = PRIMER_MIN_TM)]
     pub min_tm: f32,
     #[arg(long, env = "MAX_TM", default_value_t = PRIMER_MAX_TM)]
@@ -51,6 +529,85 @@ pub struct Args {
     pub max_self_dimer_end_tm: f32,
     #[arg(long, env = "MAX_HAIRPIN_TM", default_value_t = PRIMER_MAX_HAIRPIN_TH)]
     pub max_hairpin_tm: f32,
+    #[arg(
+        long,
+        env = "GC_CLAMP_LENGTH",
+        default_value_t = GC_CLAMP_LENGTH,
+        help = "Number of bases at the 3' end checked for the GC clamp."
+    )]
+    pub gc_clamp_length: usize,
+    #[arg(
+        long,
+        env = "MIN_GC_CLAMP",
+        default_value_t = MIN_GC_CLAMP,
+        help = "Minimum number of G or C in the 3' end bases, 0 disables the GC clamp check."
+    )]
+    pub min_gc_clamp: usize,
+    #[arg(
+        long,
+        env = "MIN_GC",
+        default_value_t = MIN_GC,
+        help = "Minimum GC content (%) of a primer, primers below are rejected as gc_low."
+    )]
+    pub min_gc: f32,
+    #[arg(
+        long,
+        env = "MAX_GC",
+        default_value_t = MAX_GC,
+        help = "Maximum GC content (%) of a primer, primers above are rejected as gc_high."
+    )]
+    pub max_gc: f32,
+    #[arg(
+        long,
+        env = "MIN_COMPLEXITY",
+        default_value_t = MIN_COMPLEXITY,
+        help = "Minimum linguistic complexity (0-1) of a primer, 0 disables the check."
+    )]
+    pub min_complexity: f32,
+    #[arg(
+        long,
+        env = "MAX_MONO_REPEATS",
+        default_value_t = MAX_MONO_REPEATS,
+        help = "Maximum length of a homopolymer run in a primer, 0 disables the check."
+    )]
+    pub max_mono_repeats: usize,
+    #[arg(
+        long,
+        env = "MAX_DI_REPEATS",
+        default_value_t = MAX_DI_REPEATS,
+        help = "Maximum consecutive repeats of a dinucleotide in a primer, 0 disables the check."
+    )]
+    pub max_di_repeats: usize,
+    #[arg(
+        long,
+        env = "MAX_TRI_REPEATS",
+        default_value_t = MAX_TRI_REPEATS,
+        help = "Maximum consecutive repeats of a trinucleotide in a primer, 0 disables the check."
+    )]
+    pub max_tri_repeats: usize,
+    #[arg(
+        long,
+        env = "MAX_THREE_PRIME_RUN",
+        default_value_t = MAX_THREE_PRIME_RUN,
+        help = "Longest homopolymer run at the 3' end of a primer, 0 disables the check."
+    )]
+    pub max_three_prime_run: usize,
+    #[arg(
+        long,
+        env = "MIN_THREE_PRIME_DG",
+        default_value_t = MIN_THREE_PRIME_DG,
+        allow_negative_numbers = true,
+        help = "Minimum ΔG (kcal/mol) of the five 3' terminal bases, more stable 3' ends are rejected."
+    )]
+    pub min_three_prime_dg: f32,
+    #[arg(
+        long,
+        env = "MIN_SELF_FOLD_DG",
+        default_value_t = MIN_SELF_FOLD_DG,
+        allow_negative_numbers = true,
+        help = "Minimum free energy (kcal/mol) of a primer folding on itself, more stable self-structures are rejected, e.g. -6.0. Disabled by default."
+    )]
+    pub min_self_fold_dg: f32,
     #[arg(long, env = "DELTA_G_THRESHOLD", default_value_t = DELTA_G_THRESHOLD, help = "Threshold for dG, default is -9000.0 J/mol")]
     pub delta_g_threshold: f32,
 
@@ -106,6 +663,22 @@ pub struct Args {
     )]
     pub tm_stddev: f32,
 
+    #[arg(
+        long,
+        env = "TM_FALLBACK_MARGIN",
+        default_value_t = TM_FALLBACK_MARGIN,
+        help = "Allowed distance of tm from the mean when there are fewer than two primers."
+    )]
+    pub tm_fallback_margin: f32,
+
+    #[arg(
+        long,
+        env = "MAX_POOL_TM_STD",
+        default_value_t = MAX_POOL_TM_STD,
+        help = "Warn when the standard deviation of the Tm of the final primers exceeds this (°C)."
+    )]
+    pub max_pool_tm_std: f32,
+
     #[arg(
         group = "flag",
         long,
@@ -128,12 +701,214 @@ pub struct Args {
     )]
     pub do_align: String,
 
+    #[arg(
+        group = "flag",
+        long,
+        env = "SOFT_MASK",
+        default_value = "false",
+        value_parser = ["true", "false"],
+        help = "Treats lowercase bases as masked, k-mers overlapping them are not used as primers."
+    )]
+    pub soft_mask: String,
+
+    #[arg(
+        long,
+        env = "DUPLICATE_NAMES",
+        value_enum,
+        default_value_t = DuplicateNames::Rename,
+        help = "Handling of input records sharing a name."
+    )]
+    pub duplicate_names: DuplicateNames,
+
+    #[arg(
+        long,
+        env = "MASK_PROXIMITY",
+        default_value_t = MASK_PROXIMITY,
+        help = "Flag primers as near_masked when a soft-masked or --mask base is within this many positions of their binding site."
+    )]
+    pub mask_proximity: usize,
+
+    #[arg(
+        group = "flag",
+        long,
+        env = "COUNT_OCCURRENCES",
+        default_value = "false",
+        value_parser = ["true", "false"],
+        help = "Counts every occurrence of a k-mer in a search window, instead of once per window."
+    )]
+    pub count_occurrences: String,
+
+    #[arg(
+        group = "flag",
+        long,
+        env = "CANONICAL",
+        default_value = "false",
+        value_parser = ["true", "false"],
+        help = "Counts a k-mer and its reverse complement as one canonical k-mer, so sequences of both orientations add up."
+    )]
+    pub canonical: String,
+
+    #[arg(
+        long,
+        env = "THREADS",
+        default_value_t = THREADS,
+        help = "Number of threads for parallel work and MAFFT, 0 uses all cores."
+    )]
+    pub threads: usize,
+
+    #[arg(
+        long,
+        env = "ALIGN_TIMEOUT",
+        default_value_t = ALIGN_TIMEOUT,
+        help = "Seconds MAFFT may run before it is killed, 0 waits indefinitely."
+    )]
+    pub align_timeout: u64,
+
+    #[arg(
+        group = "flag",
+        long,
+        env = "ALIGN_RETRY",
+        default_value = "false",
+        value_parser = ["true", "false"],
+        help = "Runs MAFFT a second time when it fails or times out."
+    )]
+    pub align_retry: String,
+
+    #[arg(
+        group = "flag",
+        long,
+        env = "PROGRESS",
+        default_value = "false",
+        value_parser = ["true", "false"],
+        help = "Shows progress bars for segmentation and primer selection, only when stderr is a terminal."
+    )]
+    pub progress: String,
+
+    #[arg(
+        group = "flag",
+        long,
+        env = "VERBOSE",
+        default_value = "false",
+        value_parser = ["true", "false"],
+        help = "Reports the elapsed time of every pipeline stage on stderr, also of a stage that fails."
+    )]
+    pub verbose: String,
+
+    #[arg(
+        long,
+        env = "LOG_FORMAT",
+        value_enum,
+        default_value_t = LogFormat::Text,
+        help = "Format of the log lines on stderr, json writes one object per line."
+    )]
+    pub log_format: LogFormat,
+
+    #[arg(
+        group = "flag",
+        long,
+        env = "DEDUPE_REV_COMP",
+        default_value = "true",
+        value_parser = ["true", "false"],
+        help = "Collapses forward and reverse primers that are reverse complements of each other."
+    )]
+    pub dedupe_rev_comp: String,
+
+    #[arg(
+        long,
+        env = "SEED",
+        help = "Seed of the order-dependent steps, the same seed gives the same primers on every run."
+    )]
+    pub seed: Option<u64>,
+
+    #[arg(
+        group = "flag",
+        long,
+        env = "DETERMINISTIC",
+        default_value = "false",
+        value_parser = ["true", "false"],
+        help = "Forces a stable order in every order-dependent step, so runs on the same input write identical outputs."
+    )]
+    pub deterministic: String,
+
+    #[arg(
+        group = "flag",
+        long,
+        env = "DRY_RUN",
+        default_value = "false",
+        value_parser = ["true", "false"],
+        help = "Runs the pipeline through filtering and prints stage counts to stderr, without writing any output."
+    )]
+    pub dry_run: String,
+
+    #[arg(
+        group = "flag",
+        long,
+        env = "STRICT_LENGTH",
+        default_value = "false",
+        value_parser = ["true", "false"],
+        help = "Fails instead of warning when the (aligned) sequences differ in length."
+    )]
+    pub strict_length: String,
+
+    #[arg(
+        long,
+        env = "SPECIFICITY_DB",
+        help = "BLAST database of off-target (e.g. host) sequences, primers with significant hits are removed."
+    )]
+    pub specificity_db: Option<String>,
+
+    #[arg(
+        long,
+        env = "EXCLUDE",
+        help = "File of sequences never selected as primers, one per line, reverse complements are excluded too."
+    )]
+    pub exclude: Option<String>,
+
+    #[arg(
+        long,
+        env = "COMPLEMENT_TABLE",
+        help = "File of extra base complements, one base and its complement per line, e.g. I and C for inosine."
+    )]
+    pub complement_table: Option<String>,
+
+    #[arg(
+        long,
+        env = "CONSERVATION",
+        help = "TSV of the 1-based position and conservation (0-1) of the aligned sequences, weighting the k-mers by the conservation of their positions."
+    )]
+    pub conservation: Option<String>,
+
+    #[arg(
+        long,
+        env = "SEQUENCE_WEIGHTS",
+        help = "TSV of the sequence name and its weight, e.g. the prevalence of its lineage, weighting the k-mers of every sequence. Missing sequences weigh 1.0."
+    )]
+    pub sequence_weights: Option<String>,
+
+    #[arg(
+        long,
+        env = "CHECK",
+        help = "FASTA or CSV of existing primers to evaluate against the input instead of designing new ones."
+    )]
+    pub check: Option<String>,
+
+    #[arg(
+        long,
+        env = "MAX_OFF_TARGET_MISMATCH",
+        default_value_t = MAX_OFF_TARGET_MISMATCH,
+        help = "Maximum unmatched bases of a BLAST hit reaching the 3' end to count as off-target."
+    )]
+    pub max_off_target_mismatch: usize,
+
     // vendor binary path
     #[arg(long, env ="NTTHAL", default_value_t = DEFAULT_NTTHAL_PATH.to_string())]
     pub ntthal: String,
 
     #[arg(long, env = "PRIMER3", default_value_t = DEFAULT_PRIMER3_PATH.to_string())]
     pub primer3: String,
+
+    #[arg(long, env = "BLASTN", default_value_t = DEFAULT_BLASTN_PATH.to_string())]
+    pub blastn: String,
 }
 
 #[derive(Clone)]
@@ -144,25 +919,152 @@ pub struct PrimerConfig {
     pub max_self_dimer_any_tm: f32,
     pub max_self_dimer_end_tm: f32,
     pub max_hairpin_tm: f32,
+    pub gc_clamp_length: usize,
+    pub min_gc_clamp: usize,
+    pub min_gc: f32,
+    pub max_gc: f32,
+    pub min_complexity: f32,
+    pub max_mono_repeats: usize,
+    pub max_di_repeats: usize,
+    pub max_tri_repeats: usize,
+    pub max_three_prime_run: usize,
+    pub min_three_prime_dg: f32,
+    pub min_self_fold_dg: f32,
+    pub annealing_temp: f32,
+    pub annealing_tm_margin: f32,
+    pub mv_conc: f32,
+    pub dv_conc: f32,
+    pub dntp_conc: f32,
+    pub dna_conc: f32,
 }
 
 #[derive(Clone)]
 pub struct ProgramConfig {
     pub ntthal_path: String,
     pub primer3_path: String,
+    pub blastn_path: String,
+    pub specificity_db: Option<String>,
+    pub max_off_target_mismatch: usize,
 
     pub max_iterations: usize,
     pub max_mismatch_segments: usize,
+    pub target_coverage: f32,
+    pub max_mismatch: usize,
+    pub min_frequency: f32,
+    pub max_direction_ratio: f32,
+    pub strategy: Strategy,
 
     pub keep_all: bool,
     pub check_cross_dimers: bool,
     pub check_self_dimers: bool,
     pub check_hairpin: bool,
+    pub tm_method: TmMethod,
     pub tm_stddev: f32,
+    pub tm_fallback_margin: f32,
     pub disable_tm_stddev: bool,
     pub do_align: bool,
+    pub soft_mask: bool,
+    pub duplicate_names: DuplicateNames,
+    pub threads: usize,
+    pub align_timeout: u64,
+    pub align_retry: bool,
+    pub progress: bool,
+    pub dedupe_rev_comp: bool,
+    pub seed: Option<u64>,
+    pub deterministic: bool,
+    // complements of `--complement-table`, consulted before the IUPAC ones
+    pub complement_table: Option<HashMap<char, char>>,
+
+    pub primer_config: PrimerConfig,
+}
 
-    pub(crate) primer_config: PrimerConfig,
+#[cfg(test)]
+pub fn get_test_program_config() -> ProgramConfig {
+    ProgramConfig {
+        ntthal_path: "".to_string(),
+        primer3_path: "".to_string(),
+        blastn_path: "".to_string(),
+        specificity_db: None,
+        max_off_target_mismatch: 2,
+        max_iterations: 0,
+        max_mismatch_segments: 0,
+        target_coverage: 1.0,
+        max_mismatch: 0,
+        min_frequency: 0.0,
+        max_direction_ratio: 0.0,
+        strategy: Strategy::MaxFrequency,
+        keep_all: false,
+        check_cross_dimers: true,
+        check_self_dimers: true,
+        check_hairpin: false,
+        tm_method: TmMethod::Nn,
+        tm_stddev: 2.0,
+        tm_fallback_margin: 5.0,
+        disable_tm_stddev: false,
+        do_align: false,
+        soft_mask: false,
+        duplicate_names: DuplicateNames::Rename,
+        threads: 0,
+        align_timeout: 0,
+        align_retry: false,
+        progress: false,
+        dedupe_rev_comp: true,
+        seed: None,
+        deterministic: false,
+        complement_table: None,
+        primer_config: PrimerConfig {
+            kmer_size: 13,
+            min_tm: 30.0,
+            max_tm: 60.0,
+            max_self_dimer_any_tm: 20.0,
+            max_self_dimer_end_tm: 20.0,
+            max_hairpin_tm: 20.0,
+            gc_clamp_length: 3,
+            min_gc_clamp: 1,
+            min_gc: 0.0,
+            max_gc: 100.0,
+            min_complexity: 0.75,
+            max_mono_repeats: 5,
+            max_di_repeats: 4,
+            // explicit, the default leaves trinucleotide repeats unchecked
+            max_tri_repeats: 3,
+            max_three_prime_run: 2,
+            min_three_prime_dg: -8.0,
+            // explicit, the default rejects no fold
+            min_self_fold_dg: -6.0,
+            annealing_temp: 25.0,
+            annealing_tm_margin: 5.0,
+            mv_conc: 50.0,
+            dv_conc: 3.0,
+            dntp_conc: 0.0,
+            dna_conc: 250.0,
+        },
+    }
+}
+
+/// Parse a `start:end` region of the aligned sequences, 0-based with the end exclusive.
+pub fn parse_region(region: &str) -> Result<(usize, usize), String> {
+    let Some((start, end)) = region.split_once(':') else {
+        return Err(format!("expected start:end, got {}", region));
+    };
+    let start = start.trim().parse::<usize>().map_err(|e| e.to_string())?;
+    let end = end.trim().parse::<usize>().map_err(|e| e.to_string())?;
+    if start >= end {
+        return Err(format!("start {} must be before end {}", start, end));
+    }
+    Ok((start, end))
+}
+
+/// Parse `--max-n`, bounded as every N multiplies the resolved k-mers by four.
+pub fn parse_max_n(max_n: &str) -> Result<usize, String> {
+    let max_n = max_n.trim().parse::<usize>().map_err(|e| e.to_string())?;
+    if max_n > MAX_N_LIMIT {
+        return Err(format!(
+            "{} is more than {}, each N resolves to 4 k-mers",
+            max_n, MAX_N_LIMIT
+        ));
+    }
+    Ok(max_n)
 }
 
 pub fn find_executable(name: &str, exact: bool) -> Option<String> {
@@ -2,8 +2,24 @@ pub const KMER_SIZE: usize = 13;
 pub const WINDOW_SIZE: usize = 500;
 pub const OVERLAP_SIZE: usize = 250;
 pub const MAX_MISMATCH_SEGMENTS: usize = 1;
+// fraction of the segments covered at which the primer selection stops, 1 for full coverage
+pub const TARGET_COVERAGE: f32 = 1.0;
+pub const MAX_MISMATCH: usize = 0;
+pub const MAX_DEGENERACY: usize = 1;
+pub const MAX_N: usize = 0;
+// each N resolves to four k-mers, so three already make 64 per k-mer
+pub const MAX_N_LIMIT: usize = 3;
+// oligos shorter than this get the Wallace rule Tm with `--tm-method auto`
+pub const WALLACE_MAX_LENGTH: usize = 14;
+pub const MAX_DIRECTION_RATIO: f32 = 0.0;
+// Minimum k-mer frequency, a fraction of the input sequences when below 1
+pub const MIN_FREQUENCY: f32 = 0.0;
 pub const MAX_ITERATIONS: usize = 1000;
 pub const SEARCH_WINDOWS_SIZE: usize = 50;
+pub const MIN_AMPLICON_LENGTH: usize = 100;
+pub const MAX_AMPLICON_LENGTH: usize = WINDOW_SIZE;
+// Maximum Tm difference of a primer pair (°C)
+pub const MAX_DELTA_TM: f32 = 5.0;
 // Monovalent cation concentration (mM)
 pub const MV_CONC: f32 = 50.0;
 // Divalent cation concentration (mM)
@@ -14,14 +30,59 @@ pub const DNTP_CONC: f32 = 0.0;
 pub const DNA_CONC: f32 = 250.0;
 // Annealing temperature (°C)
 pub const ANNEALING_TEMP: f32 = 25.0;
+// minimum distance of a primer Tm above the annealing temperature
+pub const ANNEALING_TM_MARGIN: f32 = 5.0;
 pub const PRIMER_MIN_TM: f32 = 30.0;
 pub const PRIMER_MAX_TM: f32 = 60.0;
 pub const PRIMER_MAX_SELF_ANY_TH: f32 = PRIMER_MIN_TM - 10.0;
 pub const PRIMER_MAX_SELF_END_TH: f32 = PRIMER_MIN_TM - 10.0;
 pub const PRIMER_MAX_HAIRPIN_TH: f32 = PRIMER_MIN_TM - 10.0;
+pub const GC_CLAMP_LENGTH: usize = 3;
+pub const MIN_GC_CLAMP: usize = 1;
+// GC content range (%) of a primer, no primer is rejected by default
+pub const MIN_GC: f32 = 0.0;
+pub const MAX_GC: f32 = 100.0;
+pub const MIN_COMPLEXITY: f32 = 0.75;
+// Maximum consecutive repeats of a 1, 2 and 3 nt unit in a primer, trinucleotides unchecked
+pub const MAX_MONO_REPEATS: usize = 5;
+pub const MAX_DI_REPEATS: usize = 4;
+pub const MAX_TRI_REPEATS: usize = 0;
+// Longest homopolymer run at the 3' end of a primer
+pub const MAX_THREE_PRIME_RUN: usize = 2;
 pub const DELTA_G_THRESHOLD: f32 = -9000.0;
-pub const SEQ_DIR_FWD: u8 = 0x00;
-pub const SEQ_DIR_REV: u8 = 0x01;
+// ΔG of the five 3' terminal bases (kcal/mol), ends of four strong pairs are rejected
+pub const MIN_THREE_PRIME_DG: f32 = -8.0;
+
+// Minimum free energy (kcal/mol) of a primer folding on itself, no fold is rejected by default
+pub const MIN_SELF_FOLD_DG: f32 = f32::NEG_INFINITY;
+
+// Unmatched bases of an off-target BLAST hit still flagging the primer
+pub const MAX_OFF_TARGET_MISMATCH: usize = 2;
+
+// Standard deviation of the Tm of the final pool (°C) above which it is logged as a warning
+pub const MAX_POOL_TM_STD: f32 = 2.5;
+
+// Allowed distance (°C) of a primer Tm from the mean when there are too few primers for a
+// standard deviation
+pub const TM_FALLBACK_MARGIN: f32 = 5.0;
+
+// Sequences a collapsed consensus primer may stand for, 1 keeps every primer
+pub const COLLAPSE_DEGENERACY: usize = 1;
+
+// Bases between the binding sites of primers of the same direction, 0 disables the spacing
+pub const MIN_PRIMER_SPACING: usize = 0;
+
+// Positions around a binding site checked for masked bases to flag the primer `near_masked`
+pub const MASK_PROXIMITY: usize = 10;
+
+// Number of worker threads, 0 uses all cores
+pub const THREADS: usize = 0;
+// Seconds MAFFT may run before it is killed, 0 waits indefinitely
+pub const ALIGN_TIMEOUT: u64 = 0;
+
+// Input/output path standing for stdin/stdout
+pub const STDIO_PATH: &str = "-";
 
 pub const DEFAULT_NTTHAL_PATH: &str = "ntthal";
 pub const DEFAULT_PRIMER3_PATH: &str = "primer3_core";
+pub const DEFAULT_BLASTN_PATH: &str = "blastn";
@@ -15,6 +15,168 @@ impl Edge {
     }
 }
 
+/// SantaLucia (1998) unified nearest-neighbor ΔG at 37°C (kcal/mol).
+fn nn_delta_g(pair: &str) -> Option<f32> {
+    match pair {
+        "AA" | "TT" => Some(-1.00),
+        "AT" => Some(-0.88),
+        "TA" => Some(-0.58),
+        "CA" | "TG" => Some(-1.45),
+        "GT" | "AC" => Some(-1.44),
+        "CT" | "AG" => Some(-1.28),
+        "GA" | "TC" => Some(-1.30),
+        "CG" => Some(-2.17),
+        "GC" => Some(-2.24),
+        "GG" | "CC" => Some(-1.84),
+        _ => None,
+    }
+}
+
+/**
+ * ΔG (kcal/mol) of the five 3' terminal bases, the end stability reported by Primer3
+ *
+ * Primers of both directions are stored 5' to 3' as the oligo sequence (reverse primers are
+ * already reverse complemented), so the 3' end is always the end of the word. Pairs with
+ * ambiguous or masked bases do not contribute.
+ */
+pub fn three_prime_delta_g(kmer: &str) -> f32 {
+    let bases: Vec<char> = kmer.chars().collect();
+    let start = bases.len().saturating_sub(5);
+    bases[start..]
+        .windows(2)
+        .filter_map(|pair| nn_delta_g(&pair.iter().collect::<String>()))
+        .sum()
+}
+
+/**
+ * ΔG (kcal/mol) of the most stable ungapped duplex of two primers, both 5' to 3'
+ *
+ * The primers are slid antiparallel along each other and the nearest-neighbor stacks of every
+ * run of Watson-Crick pairs are summed, the most negative run of all offsets is the dimer
+ * ΔG. A primer with itself is its self-dimer, and swapping the primers gives the same ΔG.
+ */
+pub fn dimer_delta_g(a: &str, b: &str) -> f32 {
+    let a: Vec<char> = a.chars().collect();
+    // the bases of b facing a, 3' to 5'
+    let b: Vec<char> = b.chars().rev().collect();
+    let is_pair =
+        |x: char, y: char| matches!((x, y), ('A', 'T') | ('T', 'A') | ('C', 'G') | ('G', 'C'));
+    let mut best = 0.0f32;
+    for shift in -(b.len() as isize)..(a.len() as isize) {
+        let mut run = 0.0f32;
+        for i in 0..a.len().saturating_sub(1) {
+            let (j, next) = (i as isize - shift, i as isize + 1 - shift);
+            let paired = |i: usize, j: isize| {
+                j >= 0 && (j as usize) < b.len() && is_pair(a[i], b[j as usize])
+            };
+            if !paired(i, j) || !paired(i + 1, next) {
+                run = 0.0;
+                continue;
+            }
+            run += nn_delta_g(&a[i..i + 2].iter().collect::<String>()).unwrap_or_default();
+            best = best.min(run);
+        }
+    }
+    best
+}
+
+/// Hairpin loop initiation ΔG at 37°C (kcal/mol) of 3 to 9 unpaired bases, SantaLucia and
+/// Hicks (2004).
+const HAIRPIN_LOOP_DG: [f32; 7] = [3.5, 3.5, 3.3, 4.0, 4.2, 4.3, 4.5];
+
+// shortest hairpin loop and longest interior loop of a self-fold
+const MIN_HAIRPIN_LOOP: usize = 3;
+const MAX_INTERIOR_LOOP: usize = 30;
+
+/// Initiation ΔG (kcal/mol) of a hairpin loop, extrapolated past 9 bases by 1.75RT ln(n/9).
+fn hairpin_loop_delta_g(size: usize) -> f32 {
+    match size {
+        0..MIN_HAIRPIN_LOOP => f32::INFINITY,
+        3..=9 => HAIRPIN_LOOP_DG[size - MIN_HAIRPIN_LOOP],
+        _ => HAIRPIN_LOOP_DG[6] + 1.08 * (size as f32 / 9.0).ln(),
+    }
+}
+
+/// Initiation ΔG (kcal/mol) of a bulge or interior loop of `size` unpaired bases on both
+/// sides, a single base bulge costs the most as it breaks the stacking.
+fn interior_loop_delta_g(size: usize) -> f32 {
+    match size {
+        0 => 0.0,
+        1 => 3.8,
+        _ => 2.0 + 1.08 * (size as f32 / 2.0).ln(),
+    }
+}
+
+/**
+ * Minimum free energy (kcal/mol at 37°C) of a primer folding on itself
+ *
+ * A simplified Zuker recursion over the Watson-Crick pairs within the k-mer: stems are scored
+ * by the nearest-neighbor stacks, salt corrected for `mv` monovalent and `dv` divalent cations
+ * (mM), and closed by hairpin, bulge or interior loop penalties. Multi-branch loops are not
+ * considered, they hardly fit in a primer. Ambiguous bases don't pair and a primer without
+ * any stable structure gets 0.
+ */
+pub fn self_fold_delta_g(kmer: &str, mv: f64, dv: f64) -> f32 {
+    let bases: Vec<u8> = kmer.bytes().map(|b| b.to_ascii_uppercase()).collect();
+    let n = bases.len();
+    let is_pair = |i: usize, j: usize| {
+        matches!(
+            (bases[i], bases[j]),
+            (b'A', b'T') | (b'T', b'A') | (b'C', b'G') | (b'G', b'C')
+        )
+    };
+    // SantaLucia (1998) salt correction per stack, divalent cations as equivalent sodium
+    // (von Ahsen et al. 2001)
+    let sodium = (mv + 120.0 * dv.max(0.0).sqrt()) / 1000.0;
+    let salt = match sodium > 0.0 {
+        true => -0.114 * sodium.ln() as f32,
+        false => 0.0,
+    };
+    let stack = |i: usize| {
+        nn_delta_g(std::str::from_utf8(&bases[i..i + 2]).unwrap_or_default()).unwrap_or_default()
+            + salt
+    };
+
+    // energy of the best structure closed by the pair (i, j)
+    let mut closed = vec![vec![f32::INFINITY; n]; n];
+    for span in MIN_HAIRPIN_LOOP + 1..n {
+        for i in 0..n - span {
+            let j = i + span;
+            if !is_pair(i, j) {
+                continue;
+            }
+            let mut best = hairpin_loop_delta_g(span - 1);
+            for (p, closed_p) in closed.iter().enumerate().take(j).skip(i + 1) {
+                for q in (p + MIN_HAIRPIN_LOOP + 1..j).rev() {
+                    let size = (p - i - 1) + (j - q - 1);
+                    if size > MAX_INTERIOR_LOOP {
+                        break;
+                    }
+                    if !closed_p[q].is_finite() {
+                        continue;
+                    }
+                    let loop_dg = match size {
+                        0 => stack(i),
+                        _ => interior_loop_delta_g(size),
+                    };
+                    best = best.min(loop_dg + closed_p[q]);
+                }
+            }
+            closed[i][j] = best;
+        }
+    }
+
+    // best structure of the first j bases, unpaired bases are free
+    let mut prefix = vec![0.0f32; n + 1];
+    for j in 1..=n {
+        prefix[j] = prefix[j - 1];
+        for i in 0..j {
+            prefix[j] = prefix[j].min(prefix[i] + closed[i][j - 1]);
+        }
+    }
+    prefix[n].min(0.0)
+}
+
 pub struct NtthalOptions {
     pub mv: f32,
     pub dv: f32,
@@ -64,7 +226,9 @@ pub fn format_ntthal_input(primers: &[String], program_config: ProgramConfig) ->
     for a in primers {
         for b in primers {
             // Skip if the primers are the same and program_config.check_self_dimers is false
-            if !program_config.check_self_dimers && (a == b || reverse_complement(b) == *a) {
+            if !program_config.check_self_dimers
+                && (a == b || reverse_complement(b, program_config.complement_table.as_ref()) == *a)
+            {
                 continue;
             }
             // Skip if program_config.check_cross_dimers is false
@@ -144,10 +308,7 @@ pub fn run_ntthal(
     // Read the output from the stdout of the process
     let output = cmd.wait_with_output()?;
     if !output.status.success() {
-        return Err(std::io::Error::new(
-            std::io::ErrorKind::Other,
-            "ntthal process failed",
-        ));
+        return Err(std::io::Error::other("ntthal process failed"));
     }
 
     // Process the output as needed
@@ -157,34 +318,17 @@ pub fn run_ntthal(
 
 #[cfg(test)]
 mod tests {
-    use crate::config::{PrimerConfig, ProgramConfig};
-    use crate::delta_g::{format_ntthal_input, parse_ntthal_output};
+    use crate::config::get_test_program_config;
+    use crate::delta_g::{
+        dimer_delta_g, format_ntthal_input, parse_ntthal_output, self_fold_delta_g,
+        three_prime_delta_g,
+    };
     use crate::graphdb::get_edge_id;
 
     #[test]
     pub fn test_format_ntthal_input() {
         let primers = vec!["GAAGCAGTATTTT".to_string(), "AATATAGAGGCTG".to_string()];
-        let program_config = ProgramConfig {
-            ntthal_path: "".to_string(),
-            primer3_path: "".to_string(),
-            max_iterations: 0,
-            max_mismatch_segments: 0,
-            keep_all: false,
-            check_cross_dimers: true,
-            check_self_dimers: true,
-            check_hairpin: false,
-            tm_stddev: 2.0,
-            disable_tm_stddev: false,
-            do_align: false,
-            primer_config: PrimerConfig {
-                kmer_size: 13,
-                min_tm: 30.0,
-                max_tm: 60.0,
-                max_self_dimer_any_tm: 20.0,
-                max_self_dimer_end_tm: 20.0,
-                max_hairpin_tm: 20.0,
-            },
-        };
+        let program_config = get_test_program_config();
         let result = format_ntthal_input(&primers, program_config.clone());
         let expected = "\
             GAAGCAGTATTTT,GAAGCAGTATTTT\n\
@@ -265,4 +409,43 @@ mod tests {
         let edge_de = graph.get_edge(&edge_de_id);
         assert_eq!(edge_de.unwrap().get_dg(), -3209.05);
     }
+
+    #[test]
+    pub fn test_three_prime_delta_g() {
+        // only the last five bases count, GCGCG: GC + CG + GC + CG
+        let gc_rich = three_prime_delta_g("ATATATATGCGCG");
+        assert!((gc_rich - -8.82).abs() < 1e-4);
+        assert!((three_prime_delta_g("GCGCGCGCATATA") - -2.92).abs() < 1e-4);
+        assert_eq!(three_prime_delta_g("A"), 0.0);
+        assert_eq!(three_prime_delta_g(""), 0.0);
+    }
+
+    #[test]
+    pub fn test_self_fold_delta_g() {
+        // GCGCGC stem closed by an AAAA loop: five stacks, salt corrected, plus the loop
+        let hairpin = self_fold_delta_g("GCGCGCAAAAGCGCGC", 50.0, 0.0);
+        let salt = -0.114 * 0.05f32.ln();
+        assert!((hairpin - (-11.06 + 5.0 * salt + 3.5)).abs() < 1e-3);
+        // divalent cations stabilize the stem
+        assert!(self_fold_delta_g("GCGCGCAAAAGCGCGC", 50.0, 3.0) < hairpin);
+        // a two base loop can't close, the stem shrinks to leave a loop of four
+        let short_loop = self_fold_delta_g("GCGCAAGCGC", 50.0, 0.0);
+        assert!((short_loop - (-4.41 + 2.0 * salt + 3.5)).abs() < 1e-3);
+        assert_eq!(self_fold_delta_g("GCGC", 50.0, 3.0), 0.0);
+        // nothing pairs along a linear primer
+        assert_eq!(self_fold_delta_g("ACACACACACACACAC", 50.0, 3.0), 0.0);
+        assert_eq!(self_fold_delta_g("", 50.0, 3.0), 0.0);
+    }
+
+    #[test]
+    pub fn test_dimer_delta_g() {
+        // fully complementary: GC CG GC CG GG GA AA AA AA
+        let dg = dimer_delta_g("GCGCGGAAAA", "TTTTCCGCGC");
+        assert!((dg - -14.96).abs() < 1e-4);
+        assert_eq!(dg, dimer_delta_g("TTTTCCGCGC", "GCGCGGAAAA"));
+        // the poly-A pairs with the 3' T run only
+        assert!((dimer_delta_g("AAAAAAAAAA", "TTTTCCGCGC") - -3.0).abs() < 1e-4);
+        assert_eq!(dimer_delta_g("AAAAAAAAAA", "AAAAAAAAAA"), 0.0);
+        assert_eq!(dimer_delta_g("", "ACGT"), 0.0);
+    }
 }
@@ -0,0 +1,79 @@
+use std::io;
+use thiserror::Error;
+
+/// Errors of the primer design pipeline.
+#[derive(Error, Debug)]
+pub enum DesignError {
+    #[error("failed to align sequences with MAFFT: {0}")]
+    AlignmentFailed(String),
+
+    #[error("MAFFT did not finish within {0:?}, raise --align-timeout or set it to 0")]
+    AlignmentTimeout(std::time::Duration),
+
+    #[error("failed to check primer specificity with blastn: {0}")]
+    SpecificityCheckFailed(String),
+
+    #[error("no sequences found in the input")]
+    EmptyInput,
+
+    #[error(
+        "overlap size ({overlap_size}) must be greater or equal than search windows size ({window_size})"
+    )]
+    InvalidWindowSizes {
+        overlap_size: usize,
+        window_size: usize,
+    },
+
+    #[error(
+        "k-mer size ({kmer_size}) must not exceed the search windows size ({window_size}), no k-mer would fit in a window"
+    )]
+    KmerLongerThanWindow {
+        kmer_size: usize,
+        window_size: usize,
+    },
+
+    #[error(
+        "sequences differ in length (min: {min}, max: {max}), align them or set --do-align true"
+    )]
+    UnequalLengths { min: usize, max: usize },
+
+    #[error("invalid segment index: {0}")]
+    InvalidIndex(String),
+
+    #[error("segment index was saved with other options: {0}")]
+    IndexMismatch(String),
+
+    #[error("invalid FASTA input: {0}")]
+    FastaParse(String),
+
+    #[error(
+        "{count} records share the name of an earlier record (first: {name}), rename them or set --duplicate-names rename"
+    )]
+    DuplicateNames { count: usize, name: String },
+
+    #[error("invalid complement table: {0}")]
+    InvalidComplementTable(String),
+
+    #[error("invalid conservation track: {0}")]
+    InvalidConservation(String),
+
+    #[error("invalid sequence weights: {0}")]
+    InvalidSequenceWeights(String),
+
+    #[error("invalid mask: {0}")]
+    InvalidMask(String),
+
+    #[error("invalid primers to check: {0}")]
+    InvalidCheckPrimers(String),
+
+    #[error("invalid FASTQ input: {0}")]
+    FastqParse(String),
+
+    #[error(
+        "binary {name}({path}) not found in the system, make sure the program is installed and specify the path with --{name}"
+    )]
+    MissingExecutable { name: &'static str, path: String },
+
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
@@ -89,6 +89,12 @@ impl GraphDB {
     }
 }
 
+impl Edge {
+    pub fn get_id(&self) -> &String {
+        &self.id
+    }
+}
+
 impl PartialEq for Edge {
     fn eq(&self, other: &Self) -> bool {
         self.id == other.id
@@ -0,0 +1,299 @@
+use crate::error::DesignError;
+use crate::{
+    KmerRecord, PartitioningOption, Segment, SegmentManager, SequenceRecord, get_segment_manager,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::BufWriter;
+
+/// Format version of the saved index, raised when its layout changes.
+const INDEX_VERSION: u32 = 4;
+
+/// Segment of a saved index, the sequence is referenced by its position in the records.
+#[derive(Serialize, Deserialize)]
+struct IndexedSegment {
+    record: usize,
+    partition_no: u16,
+    index: usize,
+    start: usize,
+    end: usize,
+    kmers: [Vec<KmerRecord>; 2],
+}
+
+/// Aligned sequences with their segments and k-mers, saved with `--save-index` so runs
+/// tuning the downstream thresholds can skip the alignment and segmentation.
+#[derive(Serialize, Deserialize)]
+pub struct SegmentIndex {
+    // first, so an index of another version is detected before reading the rest
+    version: u32,
+    // options the segments were made with
+    pub options: PartitioningOption,
+    pub records: Vec<SequenceRecord>,
+    segments: Vec<IndexedSegment>,
+}
+
+impl SegmentIndex {
+    pub fn new(
+        records: &[SequenceRecord],
+        manager: &SegmentManager,
+        options: &PartitioningOption,
+    ) -> Self {
+        let positions: HashMap<*const SequenceRecord, usize> = records
+            .iter()
+            .enumerate()
+            .map(|(idx, record)| (std::ptr::from_ref(record), idx))
+            .collect();
+        let segments = manager
+            .segments
+            .iter()
+            .map(|segment| IndexedSegment {
+                record: *positions
+                    .get(&std::ptr::from_ref(segment.sequence))
+                    .expect("segment of an unknown sequence"),
+                partition_no: segment.partition_no,
+                index: segment.index,
+                start: segment.start,
+                end: segment.end,
+                kmers: segment.kmers.clone(),
+            })
+            .collect();
+        SegmentIndex {
+            version: INDEX_VERSION,
+            options: options.clone(),
+            records: records.to_vec(),
+            segments,
+        }
+    }
+
+    /// Check the index was saved with the partitioning `options`, its segments would not match
+    /// the ones of other options.
+    pub fn check_options(&self, options: &PartitioningOption) -> Result<(), DesignError> {
+        let saved = &self.options;
+        let differences: Vec<String> = [
+            ("--window-size", saved.segment_size, options.segment_size),
+            ("--overlap-size", saved.overlap_size, options.overlap_size),
+            (
+                "--search-windows-size",
+                saved.window_size,
+                options.window_size,
+            ),
+            ("--kmer-size", saved.kmer_size, options.kmer_size),
+            (
+                "--max-degeneracy",
+                saved.max_degeneracy,
+                options.max_degeneracy,
+            ),
+            ("--max-n", saved.max_n, options.max_n),
+        ]
+        .into_iter()
+        .filter(|(_, saved, given)| saved != given)
+        .map(|(name, saved, given)| format!("{} {} (given {})", name, saved, given))
+        .chain(
+            [
+                (
+                    "--count-occurrences",
+                    saved.count_occurrences == options.count_occurrences,
+                ),
+                ("--canonical", saved.canonical == options.canonical),
+                ("--conservation", saved.conservation == options.conservation),
+                ("--sequence-weights", saved.weights == options.weights),
+                ("--mask", saved.mask == options.mask),
+                ("--region", saved.region == options.region),
+            ]
+            .into_iter()
+            .filter(|(_, same)| !same)
+            .map(|(name, _)| format!("a different {}", name)),
+        )
+        .collect();
+        if differences.is_empty() {
+            return Ok(());
+        }
+        Err(DesignError::IndexMismatch(differences.join(", ")))
+    }
+
+    /// Segments of the index, referencing its records.
+    pub fn get_segment_manager(&self) -> Result<SegmentManager<'_>, DesignError> {
+        let segments = self
+            .segments
+            .iter()
+            .map(|segment| {
+                let Some(sequence) = self.records.get(segment.record) else {
+                    return Err(DesignError::InvalidIndex(format!(
+                        "segment {} references missing sequence {}",
+                        segment.index, segment.record
+                    )));
+                };
+                Ok(Segment {
+                    sequence,
+                    partition_no: segment.partition_no,
+                    index: segment.index,
+                    start: segment.start,
+                    end: segment.end,
+                    kmers: segment.kmers.clone(),
+                })
+            })
+            .collect::<Result<Vec<Segment>, DesignError>>()?;
+        Ok(SegmentManager { segments })
+    }
+
+    /// Segment `records` with the options of the index and add them, returning how many
+    /// segments were added. The records must already be aligned to the indexed records, the
+    /// indexed segments are kept as they are.
+    pub fn append(&mut self, records: Vec<SequenceRecord>) -> Result<usize, DesignError> {
+        let manager = get_segment_manager(&records, self.options.clone())?;
+        let added = SegmentIndex::new(&records, &manager, &self.options);
+        let record_offset = self.records.len();
+        let index_offset = self.segments.len();
+        let total = added.segments.len();
+        self.segments
+            .extend(added.segments.into_iter().map(|segment| IndexedSegment {
+                record: segment.record + record_offset,
+                index: segment.index + index_offset,
+                ..segment
+            }));
+        self.records.extend(added.records);
+        Ok(total)
+    }
+}
+
+/// Write the index to `path` with bincode.
+pub fn save_index(path: &str, index: &SegmentIndex) -> Result<(), DesignError> {
+    let writer = BufWriter::new(File::create(path)?);
+    bincode::serialize_into(writer, index).map_err(|e| DesignError::InvalidIndex(e.to_string()))
+}
+
+/// Read an index written by `save_index`, rejecting the indexes of other format versions.
+pub fn load_index(path: &str) -> Result<SegmentIndex, DesignError> {
+    let bytes = std::fs::read(path)?;
+    let version: u32 =
+        bincode::deserialize(&bytes).map_err(|e| DesignError::InvalidIndex(e.to_string()))?;
+    if version != INDEX_VERSION {
+        return Err(DesignError::InvalidIndex(format!(
+            "format version {} is not supported, save the index again with version {}",
+            version, INDEX_VERSION
+        )));
+    }
+    bincode::deserialize(&bytes).map_err(|e| DesignError::InvalidIndex(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_index_round_trip() {
+        let records = vec![
+            SequenceRecord::new("seq1".to_string(), "ACTGAGGTATTAACGT".to_string()),
+            SequenceRecord::new("seq2".to_string(), "ACTGAGGTGGAAACGT".to_string()),
+        ];
+        let opt = PartitioningOption {
+            segment_size: 8,
+            overlap_size: 4,
+            window_size: 4,
+            kmer_size: 3,
+            ..Default::default()
+        };
+        let manager = get_segment_manager(&records, opt.clone()).unwrap();
+        let path = std::env::temp_dir().join("od-msspe-test-index.bin");
+        let path = path.to_str().unwrap();
+        save_index(path, &SegmentIndex::new(&records, &manager, &opt)).unwrap();
+
+        let index = load_index(path).unwrap();
+        assert_eq!(index.options.kmer_size, 3);
+        assert!(index.check_options(&opt).is_ok());
+        let result = index.check_options(&PartitioningOption {
+            overlap_size: 6,
+            window_size: 5,
+            canonical: true,
+            ..opt.clone()
+        });
+        assert_eq!(
+            result.err().unwrap().to_string(),
+            "segment index was saved with other options: --overlap-size 4 (given 6), \
+             --search-windows-size 4 (given 5), a different --canonical"
+        );
+
+        // an index of another format version is rejected before reading it
+        let mut bytes = std::fs::read(path).unwrap();
+        bytes[..4].copy_from_slice(&(INDEX_VERSION + 1).to_le_bytes());
+        std::fs::write(path, bytes).unwrap();
+        let result = load_index(path);
+        std::fs::remove_file(path).unwrap();
+        assert!(
+            matches!(result, Err(DesignError::InvalidIndex(message)) if message.contains(&format!("format version {}", INDEX_VERSION + 1)))
+        );
+        let loaded = index.get_segment_manager().unwrap();
+        assert_eq!(loaded.segments.len(), manager.segments.len());
+        for (a, b) in loaded.segments.iter().zip(&manager.segments) {
+            assert_eq!(a.sequence.name, b.sequence.name);
+            assert_eq!(a.sequence.sequence, b.sequence.sequence);
+            assert_eq!(
+                (a.partition_no, a.index, a.start, a.end),
+                (b.partition_no, b.index, b.start, b.end)
+            );
+            for (ka, kb) in a.kmers.iter().zip(&b.kmers) {
+                let words = |kmers: &[KmerRecord]| {
+                    kmers
+                        .iter()
+                        .map(|k| (k.word.clone(), k.direction, k.count))
+                        .collect::<Vec<_>>()
+                };
+                assert_eq!(words(ka), words(kb));
+            }
+        }
+
+        assert!(matches!(
+            load_index("/nonexistent/index.bin"),
+            Err(DesignError::Io(_))
+        ));
+    }
+
+    #[test]
+    fn test_index_append() {
+        let records = vec![
+            SequenceRecord::new("seq1".to_string(), "ACTGAGGTATTAACGT".to_string()),
+            SequenceRecord::new("seq2".to_string(), "ACTGAGGTGGAAACGT".to_string()),
+        ];
+        let opt = PartitioningOption {
+            segment_size: 8,
+            overlap_size: 4,
+            window_size: 4,
+            kmer_size: 3,
+            ..Default::default()
+        };
+        let manager = get_segment_manager(&records, opt.clone()).unwrap();
+        let before = manager.segments.len();
+        let count = |manager: &SegmentManager, word: &str| {
+            manager
+                .segments
+                .iter()
+                .flat_map(|s| s.kmers[0].iter())
+                .filter(|k| k.word == word)
+                .map(|k| k.count)
+                .sum::<usize>()
+        };
+        let before_count = count(&manager, "ACT");
+        assert_eq!(before_count, 2);
+        let path = std::env::temp_dir().join("od-msspe-test-index-append.bin");
+        let path = path.to_str().unwrap();
+        save_index(path, &SegmentIndex::new(&records, &manager, &opt)).unwrap();
+
+        let mut index = load_index(path).unwrap();
+        std::fs::remove_file(path).unwrap();
+        let added = index
+            .append(vec![SequenceRecord::new(
+                "seq3".to_string(),
+                "ACTGAGGTATTAACGT".to_string(),
+            )])
+            .unwrap();
+        assert_eq!(index.records.len(), 3);
+        let updated = index.get_segment_manager().unwrap();
+        assert_eq!(updated.segments.len(), before + added);
+        assert_eq!(added, before / 2);
+        assert_eq!(count(&updated, "ACT"), before_count + 1);
+        assert_eq!(updated.segments.last().unwrap().sequence.name, "seq3");
+        let indexes: Vec<usize> = updated.segments.iter().map(|s| s.index).collect();
+        assert_eq!(indexes, (0..updated.segments.len()).collect::<Vec<_>>());
+    }
+}
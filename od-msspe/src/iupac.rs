@@ -0,0 +1,266 @@
+use crate::error::DesignError;
+use std::collections::{BTreeSet, HashMap};
+use std::sync::{LazyLock, Mutex};
+
+/// Bases without a complement that were already warned about.
+static UNKNOWN_BASES: LazyLock<Mutex<BTreeSet<char>>> = LazyLock::new(Default::default);
+
+/// Bases represented by an IUPAC nucleotide code, in ACGT order.
+fn get_bases(code: char) -> &'static [char] {
+    match code {
+        'A' => &['A'],
+        'C' => &['C'],
+        'G' => &['G'],
+        'T' => &['T'],
+        'R' => &['A', 'G'],
+        'Y' => &['C', 'T'],
+        'S' => &['C', 'G'],
+        'W' => &['A', 'T'],
+        'K' => &['G', 'T'],
+        'M' => &['A', 'C'],
+        'B' => &['C', 'G', 'T'],
+        'D' => &['A', 'G', 'T'],
+        'H' => &['A', 'C', 'T'],
+        'V' => &['A', 'C', 'G'],
+        'N' => &['A', 'C', 'G', 'T'],
+        _ => &[],
+    }
+}
+
+/// IUPAC code of a set of bases, `None` if the set has a base other than ACGT.
+pub fn get_iupac_code(bases: &[char]) -> Option<char> {
+    let has = |base| bases.contains(&base);
+    if bases.iter().any(|b| !matches!(b, 'A' | 'C' | 'G' | 'T')) {
+        return None;
+    }
+    let code = match (has('A'), has('C'), has('G'), has('T')) {
+        (true, false, false, false) => 'A',
+        (false, true, false, false) => 'C',
+        (false, false, true, false) => 'G',
+        (false, false, false, true) => 'T',
+        (true, false, true, false) => 'R',
+        (false, true, false, true) => 'Y',
+        (false, true, true, false) => 'S',
+        (true, false, false, true) => 'W',
+        (false, false, true, true) => 'K',
+        (true, true, false, false) => 'M',
+        (false, true, true, true) => 'B',
+        (true, false, true, true) => 'D',
+        (true, true, false, true) => 'H',
+        (true, true, true, false) => 'V',
+        (true, true, true, true) => 'N',
+        (false, false, false, false) => return None,
+    };
+    Some(code)
+}
+
+/// Fraction of the bases of an IUPAC code that are G or C, e.g. 1 for S and 0.5 for R or N,
+/// 0 for characters other than IUPAC codes.
+pub fn get_gc_fraction(code: char) -> f32 {
+    let bases = get_bases(code);
+    if bases.is_empty() {
+        return 0.0;
+    }
+    bases.iter().filter(|b| matches!(b, 'G' | 'C')).count() as f32 / bases.len() as f32
+}
+
+/// Parse a complement table, one base and its complement per line separated by whitespace,
+/// e.g. `I C` for inosine. Empty lines and `#` comments are skipped.
+pub fn parse_complement_table(content: &str) -> Result<HashMap<char, char>, DesignError> {
+    let mut table = HashMap::new();
+    for (i, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let [base, complement] = fields[..] else {
+            return Err(DesignError::InvalidComplementTable(format!(
+                "line {} is not a base and its complement",
+                i + 1
+            )));
+        };
+        let mut chars = (base.chars(), complement.chars());
+        match (
+            chars.0.next(),
+            chars.0.next(),
+            chars.1.next(),
+            chars.1.next(),
+        ) {
+            (Some(base), None, Some(complement), None) => {
+                table.insert(base.to_ascii_uppercase(), complement.to_ascii_uppercase());
+            }
+            _ => {
+                return Err(DesignError::InvalidComplementTable(format!(
+                    "line {} has more than one base per column",
+                    i + 1
+                )));
+            }
+        }
+    }
+    Ok(table)
+}
+
+/// Log the bases without a complement the first time each is seen.
+fn warn_unknown_base(code: char) {
+    let mut unknown = UNKNOWN_BASES.lock().unwrap_or_else(|e| e.into_inner());
+    if unknown.insert(code) {
+        log::warn!(
+            "Bases without a complement are kept as they are, add them with --complement-table: {:?}",
+            unknown
+        );
+    }
+}
+
+/// Complement of an IUPAC nucleotide code, or of the `--complement-table` when it has the
+/// code. Other characters are returned unchanged, unknown bases with a one-time warning.
+pub fn complement(code: char, table: Option<&HashMap<char, char>>) -> char {
+    if let Some(complement) = table.and_then(|t| t.get(&code)) {
+        return *complement;
+    }
+    match code {
+        'A' => 'T',
+        'T' => 'A',
+        'U' => 'A',
+        'C' => 'G',
+        'G' => 'C',
+        'R' => 'Y',
+        'Y' => 'R',
+        'K' => 'M',
+        'M' => 'K',
+        'B' => 'V',
+        'V' => 'B',
+        'D' => 'H',
+        'H' => 'D',
+        // self-complementary codes, gaps and soft-masked bases
+        'S' | 'W' | 'N' | '-' | '.' => code,
+        _ if code.is_ascii_lowercase() => code,
+        _ => {
+            warn_unknown_base(code);
+            code
+        }
+    }
+}
+
+/// Number of plain sequences a degenerate word stands for, 1 for a plain word.
+pub fn get_degeneracy(word: &str) -> usize {
+    word.chars().map(|c| get_bases(c).len().max(1)).product()
+}
+
+/// All plain sequences of a degenerate word, the word itself if it has no IUPAC codes.
+pub fn expand(word: &str) -> Vec<String> {
+    let mut expansions = vec![String::with_capacity(word.len())];
+    for c in word.chars() {
+        let bases = get_bases(c);
+        if bases.len() < 2 {
+            expansions.iter_mut().for_each(|e| e.push(c));
+            continue;
+        }
+        expansions = expansions
+            .iter()
+            .flat_map(|e| {
+                bases.iter().map(move |b| {
+                    let mut expansion = e.clone();
+                    expansion.push(*b);
+                    expansion
+                })
+            })
+            .collect();
+    }
+    expansions
+}
+
+/// Collapse k-mers at the same position of different sequences into one degenerate word.
+///
+/// Every position of the word gets the IUPAC code of the bases found there, `None` if the
+/// k-mers have a base other than ACGT (gaps, masked bases) or the word would stand for more
+/// than `max_degeneracy` sequences.
+pub fn get_consensus(kmers: &[&str], max_degeneracy: usize) -> Option<String> {
+    let kmer_size = kmers.first()?.len();
+    let mut consensus = String::with_capacity(kmer_size);
+    let mut degeneracy = 1;
+    for i in 0..kmer_size {
+        let bases: Vec<char> = kmers
+            .iter()
+            .map(|kmer| kmer.as_bytes().get(i).map_or('-', |b| *b as char))
+            .collect();
+        let code = get_iupac_code(&bases)?;
+        degeneracy *= get_bases(code).len();
+        if degeneracy > max_degeneracy {
+            return None;
+        }
+        consensus.push(code);
+    }
+    Some(consensus)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get_iupac_code() {
+        assert_eq!(get_iupac_code(&['A', 'G']), Some('R'));
+        assert_eq!(get_iupac_code(&['G', 'A', 'G']), Some('R'));
+        assert_eq!(get_iupac_code(&['T', 'T']), Some('T'));
+        assert_eq!(get_iupac_code(&['A', 'C', 'G', 'T']), Some('N'));
+        assert_eq!(get_iupac_code(&['A', '-']), None);
+        assert_eq!(get_iupac_code(&[]), None);
+    }
+
+    #[test]
+    fn test_expand() {
+        assert_eq!(expand("ACGT"), vec!["ACGT"]);
+        assert_eq!(expand("ACRT"), vec!["ACAT", "ACGT"]);
+        assert_eq!(expand("YCRT"), vec!["CCAT", "CCGT", "TCAT", "TCGT"]);
+        assert_eq!(get_degeneracy("YCRT"), 4);
+        assert_eq!(get_degeneracy("ACGT"), 1);
+    }
+
+    #[test]
+    fn test_get_gc_fraction() {
+        assert_eq!(get_gc_fraction('G'), 1.0);
+        assert_eq!(get_gc_fraction('S'), 1.0);
+        assert_eq!(get_gc_fraction('W'), 0.0);
+        assert_eq!(get_gc_fraction('R'), 0.5);
+        assert_eq!(get_gc_fraction('N'), 0.5);
+        assert_eq!(get_gc_fraction('-'), 0.0);
+    }
+
+    #[test]
+    fn test_get_consensus() {
+        // A/G at the third position collapses to R
+        assert_eq!(
+            get_consensus(&["ACATG", "ACGTG", "ACATG"], 2),
+            Some("ACRTG".to_string())
+        );
+        assert_eq!(get_consensus(&["ACATG", "ACGTC"], 2), None);
+        assert_eq!(
+            get_consensus(&["ACATG", "ACGTC"], 4),
+            Some("ACRTS".to_string())
+        );
+        assert_eq!(get_consensus(&["ACATG", "AC-TG"], 4), None);
+    }
+
+    #[test]
+    fn test_complement_table() {
+        let table = parse_complement_table("# inosine\nI\tC\n\n").unwrap();
+        assert_eq!(table, HashMap::from([('I', 'C')]));
+        assert!(matches!(
+            parse_complement_table("I\n"),
+            Err(DesignError::InvalidComplementTable(_))
+        ));
+        assert!(matches!(
+            parse_complement_table("IN C\n"),
+            Err(DesignError::InvalidComplementTable(_))
+        ));
+
+        assert_eq!(crate::reverse_complement("I", Some(&table)), "C");
+        assert_eq!(crate::reverse_complement("AIG", Some(&table)), "CCT");
+        // the IUPAC complements still apply, and only where the table is passed
+        assert_eq!(crate::reverse_complement("ACRN", Some(&table)), "NYGT");
+        assert_eq!(complement('I', None), 'I');
+        assert_eq!(complement('X', Some(&table)), 'X');
+        assert!(UNKNOWN_BASES.lock().unwrap().contains(&'X'));
+    }
+}
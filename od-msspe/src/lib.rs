@@ -0,0 +1,8013 @@
+pub mod config;
+mod constants;
+mod delta_g;
+pub mod error;
+mod graphdb;
+mod index;
+mod iupac;
+mod output;
+mod primer;
+pub mod progress;
+#[cfg(feature = "pyo3")]
+mod python;
+mod specificity;
+#[cfg(test)]
+mod testing;
+pub mod tm;
+
+use crate::config::{
+    DuplicateNames, PrimerConfig, ProgramConfig, Strategy, TmMethod, find_executable,
+};
+use crate::constants::STDIO_PATH;
+use crate::delta_g::{
+    NtthalOptions, dimer_delta_g, run_ntthal, self_fold_delta_g, three_prime_delta_g,
+};
+use crate::error::DesignError;
+use crate::primer::{CheckPrimerParams, PrimerInfo, check_primers};
+use crate::specificity::{SpecificityParams, find_off_target_primers};
+use crate::tm::{get_gc_percent, get_tm, get_tm_auto, get_tm_nn, get_tm_wallace};
+use config::{Args, Column, OutputFormat, SortBy};
+use graphdb::Edge;
+use itertools::Itertools;
+use seq_io::fasta::{Reader, Record};
+use seq_io::fastq::{self, Record as FastqRecord};
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fs::File;
+use std::hash::Hash;
+use std::io::{self, BufReader, Read, Write};
+use std::path::Path;
+use std_dev::standard_deviation;
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SequenceRecord {
+    pub name: String,
+    pub sequence: String,
+    species_name: Option<String>,
+    tax_id: Option<String>,
+    source: Option<String>,
+}
+
+impl SequenceRecord {
+    pub fn new(name: String, sequence: String) -> Self {
+        SequenceRecord {
+            name,
+            sequence,
+            species_name: None,
+            tax_id: None,
+            source: None,
+        }
+    }
+}
+
+/**
+ * Direction of a primer, reverse primers are stored as the reverse complement of the template
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Direction {
+    Forward,
+    Reverse,
+}
+
+impl Direction {
+    /// Directions in the order of the search windows of a segment
+    pub const ALL: [Direction; 2] = [Direction::Forward, Direction::Reverse];
+
+    /// Short label used in primer names and the CSV output
+    pub fn label(&self) -> &'static str {
+        match self {
+            Direction::Forward => "F",
+            Direction::Reverse => "R",
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct KmerRecord {
+    word: String,
+    direction: Direction,
+    // occurrences in the search window, not part of the k-mer identity
+    count: usize,
+    // whether the word and its reverse complement were seen in the window, only the
+    // k-mers of `--canonical` can have both
+    strands: [bool; 2],
+    // canonical k-mer of the word with `--canonical`, the identity of the record in place
+    // of the word, which stays in the orientation it was first observed in
+    canonical: Option<String>,
+    // weight ranking the k-mer in the selection, the weight of its sequence, not part of the
+    // occurrence counts
+    weight: f32,
+    // lowest conservation (0-1) of the k-mer positions with `--conservation`, ranking the
+    // k-mer along with its weight
+    conservation: f32,
+}
+
+impl KmerRecord {
+    /**
+     * Word the k-mer is counted by, the canonical k-mer if any
+     */
+    fn key(&self) -> &str {
+        self.canonical.as_deref().unwrap_or(&self.word)
+    }
+
+    /**
+     * Weight of a single occurrence in the selection, its sequence weight scaled by its
+     * conservation
+     */
+    fn rank_weight(&self) -> f64 {
+        self.weight as f64 * self.conservation as f64
+    }
+}
+
+impl PartialEq for KmerRecord {
+    fn eq(&self, other: &Self) -> bool {
+        self.key() == other.key() && self.direction == other.direction
+    }
+}
+
+impl Hash for KmerRecord {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.key().hash(state);
+        self.direction.hash(state);
+    }
+}
+
+impl Eq for KmerRecord {}
+
+#[derive(Clone)]
+pub struct KmerFrequency<'a> {
+    kmer: &'a KmerRecord,
+    frequency: usize,
+}
+
+impl PartialEq for KmerFrequency<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.kmer.key() == other.kmer.key()
+    }
+}
+
+impl Eq for KmerFrequency<'_> {}
+
+impl Hash for KmerFrequency<'_> {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.kmer.key().hash(state);
+    }
+}
+
+/**
+ * Stats of a primer and the criteria it passes
+ */
+#[derive(Clone, Serialize)]
+pub struct KmerStat {
+    pub word: String,
+    pub direction: Direction,
+    // name given by `--name-by-position`, the outputs name the primer by its index otherwise
+    pub name: Option<String>,
+    pub frequency: usize,
+    pub gc_percent: f32,
+    // lowest and highest GC of the sequences of a degenerate primer
+    pub gc_min: f32,
+    pub gc_max: f32,
+    pub mean: f32,
+    pub std: f32,
+    pub tm: f32,
+    // highest Tm of the sequences of a degenerate primer, `tm` is the lowest
+    pub tm_max: f32,
+    pub degeneracy: usize,
+    // names of the input sequences with a segment the primer binds
+    pub sequences: Vec<String>,
+    // binding sites on the full sequences outside the search windows of the primer
+    pub self_binding_count: usize,
+    // primers collapsed into this degenerate consensus by `--collapse-degeneracy`
+    pub constituents: Vec<String>,
+    pub tm_ok: bool,
+    pub self_any_th: f32,
+    pub self_end_th: f32,
+    pub hairpin_th: f32,
+    pub three_prime_dg: f32,
+    // minimum free energy of the primer folding on itself
+    pub self_fold_dg: f32,
+    pub runs: bool,
+    pub three_prime_run: bool,
+    pub gc_clamp: bool,
+    pub complexity_ok: bool,
+    pub specificity_ok: bool,
+    // binds within `--mask-proximity` bases of a masked base on some sequence
+    pub near_masked: bool,
+}
+
+pub struct Segment<'a> {
+    sequence: &'a SequenceRecord,
+    partition_no: u16,
+    index: usize,
+    // position of the partition on the (aligned) sequence, end exclusive
+    start: usize,
+    end: usize,
+    // k-mers of the start (forward) and end (reverse) search windows
+    kmers: [Vec<KmerRecord>; 2],
+}
+
+struct PrimerPair<'a> {
+    forward: &'a KmerStat,
+    reverse: &'a KmerStat,
+    segment: &'a Segment<'a>,
+    amplicon_length: usize,
+    forward_tm: f32,
+    reverse_tm: f32,
+    delta_tm: f32,
+}
+
+struct TilingInterval<'a> {
+    segment: &'a Segment<'a>,
+    primer: &'a KmerStat,
+    // index of the primer within its list of candidate primers
+    index: usize,
+    name: String,
+    direction: Direction,
+    // ungapped position on the sequence, end exclusive
+    start: usize,
+    end: usize,
+}
+
+pub struct SegmentManager<'a> {
+    pub segments: Vec<Segment<'a>>,
+}
+
+impl Hash for Segment<'_> {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.sequence.name.hash(state);
+        self.partition_no.hash(state);
+    }
+}
+
+impl PartialEq for Segment<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.sequence.name == other.sequence.name && self.partition_no == other.partition_no
+    }
+}
+
+impl Eq for Segment<'_> {}
+
+fn to_records(src: Vec<u8>, soft_mask: bool) -> Result<Vec<SequenceRecord>, DesignError> {
+    read_fasta_records(src.as_slice(), soft_mask)
+}
+
+/**
+ * Parse the records of a FASTA or FASTQ file, streaming through a buffered file handle
+ * instead of reading the whole file into memory first
+ */
+fn to_records_from_path(path: &Path, soft_mask: bool) -> Result<Vec<SequenceRecord>, DesignError> {
+    let mut reader = BufReader::new(File::open(path)?);
+    match is_fastq(&mut reader)? || has_fastq_extension(path) {
+        true => {
+            log::info!(
+                "Reading FASTQ records from {}, quality scores are ignored",
+                path.display()
+            );
+            read_fastq_records(reader, soft_mask)
+        }
+        false => read_fasta_records(reader, soft_mask),
+    }
+}
+
+fn has_fastq_extension(path: &Path) -> bool {
+    let extension = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or_default();
+    matches!(extension.to_lowercase().as_str(), "fastq" | "fq")
+}
+
+/**
+ * Whether the file holds FASTQ records, by its `.fastq`/`.fq` extension or its first record
+ * starting with `@`
+ */
+fn is_fastq_path(path: &Path) -> io::Result<bool> {
+    Ok(has_fastq_extension(path) || is_fastq(&mut BufReader::new(File::open(path)?))?)
+}
+
+/**
+ * Whether the input starts with a FASTQ record, the leading whitespace is consumed
+ */
+fn is_fastq<R: io::BufRead>(reader: &mut R) -> io::Result<bool> {
+    loop {
+        let buf = reader.fill_buf()?;
+        if buf.is_empty() {
+            return Ok(false);
+        }
+        match buf.iter().position(|b| !b.is_ascii_whitespace()) {
+            Some(i) => {
+                let is_fastq = buf[i] == b'@';
+                reader.consume(i);
+                return Ok(is_fastq);
+            }
+            None => {
+                let len = buf.len();
+                reader.consume(len);
+            }
+        }
+    }
+}
+
+/**
+ * Parse FASTA or FASTQ records, telling them apart by the first record
+ */
+fn read_sequence_records<R: io::Read>(
+    src: R,
+    soft_mask: bool,
+) -> Result<Vec<SequenceRecord>, DesignError> {
+    let mut reader = BufReader::new(src);
+    match is_fastq(&mut reader)? {
+        true => read_fastq_records(reader, soft_mask),
+        false => read_fasta_records(reader, soft_mask),
+    }
+}
+
+/**
+ * Record of a parsed sequence, uppercased and with U read as T
+ */
+fn to_sequence_record(
+    name: String,
+    sequence: String,
+    desc: Option<&str>,
+    soft_mask: bool,
+) -> SequenceRecord {
+    let sequence = match soft_mask {
+        true => sequence.replace("U", "T").replace("u", "t"),
+        false => sequence.to_uppercase().replace("U", "T"),
+    };
+    let mut sequence_record = SequenceRecord::new(name, sequence);
+    if let Some(desc) = desc {
+        (sequence_record.species_name, sequence_record.tax_id) = parse_header_taxonomy(desc);
+    }
+    sequence_record
+}
+
+/**
+ * Parse FASTA records, sequences are uppercased and U is read as T
+ *
+ * With `soft_mask`, lowercase bases are kept as they are so k-mers overlapping them are
+ * skipped by `find_kmers`.
+ */
+fn read_fasta_records<R: io::Read>(
+    src: R,
+    soft_mask: bool,
+) -> Result<Vec<SequenceRecord>, DesignError> {
+    let mut reader = Reader::new(BufReader::new(src));
+    let mut records = Vec::new();
+
+    while let Some(result) = reader.next() {
+        let record = result.map_err(|e| DesignError::FastaParse(e.to_string()))?;
+        let name = record
+            .id()
+            .map_err(|e| DesignError::FastaParse(e.to_string()))?
+            .to_string();
+        let sequence = String::from_utf8(record.full_seq().to_vec()).map_err(|_| {
+            DesignError::FastaParse(format!("sequence {} is not valid UTF-8", name))
+        })?;
+        let desc = record.desc().and_then(|d| d.ok());
+        records.push(to_sequence_record(name, sequence, desc, soft_mask));
+    }
+    Ok(records)
+}
+
+/**
+ * Parse FASTQ records like `read_fasta_records`, the quality scores are dropped
+ */
+fn read_fastq_records<R: io::Read>(
+    src: R,
+    soft_mask: bool,
+) -> Result<Vec<SequenceRecord>, DesignError> {
+    let mut reader = fastq::Reader::new(BufReader::new(src));
+    let mut records = Vec::new();
+
+    while let Some(result) = reader.next() {
+        let record = result.map_err(|e| DesignError::FastqParse(e.to_string()))?;
+        let name = FastqRecord::id(&record)
+            .map_err(|e| DesignError::FastqParse(e.to_string()))?
+            .to_string();
+        let sequence = String::from_utf8(FastqRecord::seq(&record).to_vec()).map_err(|_| {
+            DesignError::FastqParse(format!("sequence {} is not valid UTF-8", name))
+        })?;
+        let desc = FastqRecord::desc(&record).and_then(|d| d.ok());
+        records.push(to_sequence_record(name, sequence, desc, soft_mask));
+    }
+    Ok(records)
+}
+
+/**
+ * Write the records of a FASTQ file as FASTA for the alignment, keeping the headers
+ */
+fn write_fastq_as_fasta<W: Write>(path: &Path, writer: &mut W) -> Result<(), DesignError> {
+    let mut reader = fastq::Reader::new(BufReader::new(File::open(path)?));
+    while let Some(result) = reader.next() {
+        let record = result.map_err(|e| DesignError::FastqParse(e.to_string()))?;
+        writer.write_all(b">")?;
+        writer.write_all(FastqRecord::head(&record))?;
+        writer.write_all(b"\n")?;
+        writer.write_all(FastqRecord::seq(&record))?;
+        writer.write_all(b"\n")?;
+    }
+    Ok(())
+}
+
+/**
+ * Extract the species name and taxonomy id from a FASTA header description
+ *
+ * Recognizes `key=value` and bracketed `[key=value]` annotations (`organism`, `species`,
+ * `taxid`, `tax_id`), e.g. `Severe acute respiratory syndrome coronavirus 2 taxid=2697049`.
+ * Without an explicit organism annotation, the free text before the first comma is used,
+ * cut at the usual `isolate`/`strain`/`segment` qualifiers.
+ */
+fn parse_header_taxonomy(desc: &str) -> (Option<String>, Option<String>) {
+    let mut species_name = None;
+    let mut tax_id = None;
+    let mut free_text: Vec<&str> = Vec::new();
+
+    let mut rest = desc.trim();
+    while !rest.is_empty() {
+        let (token, remaining) = if let Some(stripped) = rest.strip_prefix('[') {
+            match stripped.find(']') {
+                Some(end) => (&stripped[..end], &stripped[end + 1..]),
+                None => (stripped, ""),
+            }
+        } else {
+            match rest.find(char::is_whitespace) {
+                Some(end) => (&rest[..end], &rest[end..]),
+                None => (rest, ""),
+            }
+        };
+        rest = remaining.trim_start();
+
+        match token.split_once('=') {
+            Some((key, value)) => match key.to_lowercase().as_str() {
+                "organism" | "species" => species_name = Some(value.trim().to_string()),
+                "taxid" | "tax_id" => tax_id = Some(value.trim().to_string()),
+                _ => (),
+            },
+            None => free_text.push(token),
+        }
+    }
+
+    if species_name.is_none() {
+        let text = free_text.join(" ");
+        let mut text = text.split(',').next().unwrap_or_default();
+        for qualifier in [
+            " isolate ",
+            " strain ",
+            " segment ",
+            " complete ",
+            " partial ",
+        ] {
+            if let Some(idx) = text.find(qualifier) {
+                text = &text[..idx];
+            }
+        }
+        if !text.trim().is_empty() {
+            species_name = Some(text.trim().to_string());
+        }
+    }
+
+    (
+        species_name.filter(|s| !s.is_empty()),
+        tax_id.filter(|s| !s.is_empty()),
+    )
+}
+
+/**
+ * Find the most common value of the records, or the default when no record has any value
+ */
+fn most_common_value<'a>(
+    values: impl Iterator<Item = &'a Option<String>>,
+    default: &str,
+) -> String {
+    values
+        .flatten()
+        .counts()
+        .into_iter()
+        .max_by(|(a, count_a), (b, count_b)| count_a.cmp(count_b).then_with(|| b.cmp(a)))
+        .map(|(value, _)| value.clone())
+        .unwrap_or_else(|| default.to_string())
+}
+
+/**
+ * Check the (aligned) records share the same length, partitions are only comparable across
+ * sequences when they do. Differing lengths are logged as a warning, or an error if `strict`
+ */
+fn check_uniform_length<'a>(
+    records: impl IntoIterator<Item = &'a SequenceRecord>,
+    strict: bool,
+) -> Result<(), DesignError> {
+    let Some((min, max)) = records
+        .into_iter()
+        .map(|r| r.sequence.len())
+        .minmax()
+        .into_option()
+    else {
+        return Ok(());
+    };
+    if min == max {
+        return Ok(());
+    }
+    let error = DesignError::UnequalLengths { min, max };
+    if strict {
+        return Err(error);
+    }
+    log::warn!("{}", error);
+    Ok(())
+}
+
+/**
+ * MAFFT `--thread` value, -1 lets MAFFT use all cores
+ */
+fn get_mafft_threads(threads: usize) -> String {
+    match threads {
+        0 => "-1".to_string(),
+        n => n.to_string(),
+    }
+}
+
+/**
+ * Bound the global rayon thread pool, 0 uses all cores
+ */
+#[cfg(feature = "rayon")]
+fn configure_threads(threads: usize) {
+    if let Err(e) = rayon::ThreadPoolBuilder::new()
+        .num_threads(threads)
+        .build_global()
+    {
+        log::warn!("Cannot configure the thread pool: {}", e);
+    }
+}
+
+#[cfg(not(feature = "rayon"))]
+fn configure_threads(threads: usize) {
+    if threads > 0 {
+        log::warn!(
+            "--threads {} is ignored, built without the rayon feature",
+            threads
+        );
+    }
+}
+
+/**
+ * Run a command to completion, killing it once the timeout passes.
+ *
+ * stdout and stderr are drained on their own threads, so a chatty child cannot block on a
+ * full pipe while we wait for it. On unix the command leads its own process group and the
+ * whole group is killed, `mafft` is a shell script running the actual aligner as children.
+ */
+fn run_with_timeout(
+    command: &mut std::process::Command,
+    timeout: Option<std::time::Duration>,
+) -> Result<std::process::Output, DesignError> {
+    #[cfg(unix)]
+    std::os::unix::process::CommandExt::process_group(command, 0);
+    let mut child = command
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .map_err(|e| DesignError::AlignmentFailed(e.to_string()))?;
+    let drain = |pipe: Option<Box<dyn Read + Send>>| {
+        std::thread::spawn(move || {
+            let mut buf = Vec::new();
+            if let Some(mut pipe) = pipe {
+                let _ = pipe.read_to_end(&mut buf);
+            }
+            buf
+        })
+    };
+    let stdout = drain(
+        child
+            .stdout
+            .take()
+            .map(|p| Box::new(p) as Box<dyn Read + Send>),
+    );
+    let stderr = drain(
+        child
+            .stderr
+            .take()
+            .map(|p| Box::new(p) as Box<dyn Read + Send>),
+    );
+
+    let started = std::time::Instant::now();
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if let Some(timeout) = timeout.filter(|t| started.elapsed() >= *t) {
+            #[cfg(unix)]
+            // SAFETY: kill has no memory effects, the group id is the pid of the child, which
+            // leads the group and is not reaped before the wait below
+            unsafe {
+                libc::kill(-(child.id() as libc::pid_t), libc::SIGKILL);
+            }
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(DesignError::AlignmentTimeout(timeout));
+        }
+        std::thread::sleep(std::time::Duration::from_millis(50));
+    };
+
+    Ok(std::process::Output {
+        status,
+        stdout: stdout.join().unwrap_or_default(),
+        stderr: stderr.join().unwrap_or_default(),
+    })
+}
+
+/**
+ * Aligns sequences using MAFFT, once more on failure with `--align-retry`. `inputs` are
+ * passed after the alignment options, usually just the FASTA path.
+ */
+fn align_sequences(
+    inputs: &[&str],
+    program_config: &ProgramConfig,
+) -> Result<Vec<u8>, DesignError> {
+    let attempts = if program_config.align_retry { 2 } else { 1 };
+    let mut attempt = 1;
+    loop {
+        match align_sequences_once(inputs, program_config) {
+            Err(e) if attempt < attempts => {
+                log::warn!("{}, retrying the alignment", e);
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+fn align_sequences_once(
+    inputs: &[&str],
+    program_config: &ProgramConfig,
+) -> Result<Vec<u8>, DesignError> {
+    let timeout = match program_config.align_timeout {
+        0 => None,
+        secs => Some(std::time::Duration::from_secs(secs)),
+    };
+    let mut command = std::process::Command::new("mafft");
+    command.args([
+        "--auto",
+        "--quiet",
+        "--preservecase",
+        "--thread",
+        &get_mafft_threads(program_config.threads),
+        "--op",
+        "1.53",
+        "--ep",
+        "0.123",
+        "--jtt",
+        "200",
+    ]);
+    command.args(inputs);
+    let output = run_with_timeout(&mut command, timeout)?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        return Err(DesignError::AlignmentFailed(match stderr.is_empty() {
+            true => output.status.to_string(),
+            false => stderr,
+        }));
+    }
+    // a killed MAFFT may still exit cleanly with nothing written
+    if output.stdout.iter().all(|b| b.is_ascii_whitespace()) {
+        return Err(DesignError::AlignmentFailed(
+            "MAFFT returned no alignment".to_string(),
+        ));
+    }
+
+    Ok(output.stdout)
+}
+
+/**
+ * Read the records of all input files, tagging each record with the file it came from.
+ *
+ * Multiple files are aligned as one combined set, so records of every file end up with the
+ * same aligned length.
+ */
+fn read_records(
+    filepaths: &[String],
+    program_config: &ProgramConfig,
+) -> Result<Vec<SequenceRecord>, DesignError> {
+    let do_align = program_config.do_align;
+    let soft_mask = program_config.soft_mask;
+    // stdin can only be read once, keep a copy when it has to be aligned as well
+    if do_align && filepaths.iter().any(|f| is_stdio(f)) {
+        let stdin_path =
+            std::env::temp_dir().join(format!("od-msspe-{}-stdin.fasta", std::process::id()));
+        io::copy(&mut io::stdin().lock(), &mut File::create(&stdin_path)?)?;
+        let stdin_source = stdin_path.display().to_string();
+        let spooled_filepaths: Vec<String> = filepaths
+            .iter()
+            .map(|f| match is_stdio(f) {
+                true => stdin_source.clone(),
+                false => f.clone(),
+            })
+            .collect();
+        let records = read_records(&spooled_filepaths, program_config);
+        std::fs::remove_file(&stdin_path)?;
+        let mut records = records?;
+        for record in records.iter_mut() {
+            if record.source.as_ref() == Some(&stdin_source) {
+                record.source = Some(STDIO_PATH.to_string());
+            }
+        }
+        return Ok(records);
+    }
+
+    let mut records: Vec<SequenceRecord> = Vec::new();
+    let mut sources: Vec<&String> = Vec::new();
+    for filepath in filepaths {
+        let file_records = match is_stdio(filepath) {
+            true => read_sequence_records(io::stdin().lock(), soft_mask)?,
+            false => to_records_from_path(Path::new(filepath), soft_mask)?,
+        };
+        sources.extend(std::iter::repeat_n(filepath, file_records.len()));
+        if !do_align {
+            records.extend(file_records);
+        }
+    }
+
+    if do_align {
+        // MAFFT reads FASTA only, FASTQ inputs are converted into the combined input
+        let mut fastq_paths = HashSet::new();
+        for filepath in filepaths {
+            if is_fastq_path(Path::new(filepath))? {
+                fastq_paths.insert(filepath);
+            }
+        }
+        let src = match filepaths {
+            [filepath] if fastq_paths.is_empty() => align_sequences(&[filepath], program_config)?,
+            _ => {
+                let combined_path =
+                    std::env::temp_dir().join(format!("od-msspe-{}.fasta", std::process::id()));
+                let mut combined = File::create(&combined_path)?;
+                for filepath in filepaths {
+                    match fastq_paths.contains(filepath) {
+                        true => write_fastq_as_fasta(Path::new(filepath), &mut combined)?,
+                        false => {
+                            io::copy(&mut File::open(filepath)?, &mut combined)?;
+                        }
+                    }
+                    combined.write_all(b"\n")?;
+                }
+                let aligned =
+                    align_sequences(&[&combined_path.display().to_string()], program_config);
+                std::fs::remove_file(&combined_path)?;
+                aligned?
+            }
+        };
+        records = to_records(src, soft_mask)?;
+    }
+
+    // MAFFT keeps the input order, records are matched back to their file by position
+    for (record, source) in records.iter_mut().zip(sources) {
+        record.source = Some(source.clone());
+    }
+    let dropped = drop_empty_records(&mut records);
+    if dropped > 0 {
+        log::warn!("Skipped {} records without any base", dropped);
+    }
+    let duplicates = dedupe_record_names(&mut records, program_config.duplicate_names)?;
+    if duplicates > 0 {
+        log::warn!(
+            "Renamed {} records sharing the name of an earlier record",
+            duplicates
+        );
+    }
+
+    if records.is_empty() {
+        return Err(DesignError::EmptyInput);
+    }
+    Ok(records)
+}
+
+/**
+ * Align `new_records` to the already aligned `records` with MAFFT `--add --keeplength`,
+ * which keeps the existing alignment and its length by deleting the insertions of the new
+ * records. Faster than aligning everything again, but the new records can be placed less
+ * accurately than in a full alignment.
+ */
+fn align_to_records(
+    records: &[SequenceRecord],
+    new_records: Vec<SequenceRecord>,
+    program_config: &ProgramConfig,
+) -> Result<Vec<SequenceRecord>, DesignError> {
+    let write_records = |path: &Path, records: &[SequenceRecord]| -> io::Result<()> {
+        let mut writer = io::BufWriter::new(File::create(path)?);
+        for record in records {
+            writeln!(writer, ">{}\n{}", record.name, record.sequence)?;
+        }
+        writer.flush()
+    };
+    let temp_dir = std::env::temp_dir();
+    let existing_path = temp_dir.join(format!("od-msspe-{}-existing.fasta", std::process::id()));
+    let new_path = temp_dir.join(format!("od-msspe-{}-new.fasta", std::process::id()));
+    write_records(&existing_path, records)?;
+    write_records(&new_path, &new_records)?;
+    let aligned = align_sequences(
+        &[
+            "--add",
+            &new_path.display().to_string(),
+            "--keeplength",
+            &existing_path.display().to_string(),
+        ],
+        program_config,
+    );
+    std::fs::remove_file(&existing_path)?;
+    std::fs::remove_file(&new_path)?;
+
+    // MAFFT writes the existing records first, then the added ones in their input order
+    let mut aligned = to_records(aligned?, program_config.soft_mask)?;
+    let added = aligned.split_off(records.len().min(aligned.len()));
+    if added.len() != new_records.len() {
+        return Err(DesignError::AlignmentFailed(format!(
+            "MAFFT returned {} of {} added sequences",
+            added.len(),
+            new_records.len()
+        )));
+    }
+    Ok(new_records
+        .into_iter()
+        .zip(added)
+        .map(|(record, aligned)| SequenceRecord {
+            sequence: aligned.sequence,
+            ..record
+        })
+        .collect())
+}
+
+/**
+ * Add the records of `filepaths` to the index saved at `index_file` and write it back,
+ * aligning them to the indexed records first when aligning is enabled. The indexed
+ * segments are kept, so `options` must match the options the index was saved with.
+ */
+fn update_index(
+    index_file: &str,
+    filepaths: &[String],
+    options: &PartitioningOption,
+    program_config: &ProgramConfig,
+    strict_length: bool,
+    dry_run: bool,
+) -> Result<index::SegmentIndex, DesignError> {
+    let mut segment_index = index::load_index(index_file)?;
+    segment_index.check_options(options)?;
+    let new_records = read_records(
+        filepaths,
+        &ProgramConfig {
+            do_align: false,
+            ..program_config.clone()
+        },
+    )?;
+    let new_records = match program_config.do_align {
+        true => {
+            log::info!(
+                "Aligning {} sequences to the {} indexed sequences...",
+                new_records.len(),
+                segment_index.records.len()
+            );
+            align_to_records(&segment_index.records, new_records, program_config)?
+        }
+        false => new_records,
+    };
+    check_uniform_length(
+        segment_index.records.iter().chain(&new_records),
+        strict_length,
+    )?;
+
+    let total = new_records.len();
+    let added = segment_index.append(new_records)?;
+    log::info!(
+        "Added {} sequences and {} segments to {}",
+        total,
+        added,
+        index_file
+    );
+    if !dry_run {
+        index::save_index(index_file, &segment_index)?;
+        log::info!("Saved the updated segment index to {}", index_file);
+    }
+    Ok(segment_index)
+}
+
+/**
+ * Make the record names unique, the outputs and the per-sequence support refer to sequences
+ * by name. With `DuplicateNames::Rename` a record named like an earlier one gets the first
+ * free `_<n>` suffix from 2, returning how many were renamed, otherwise the duplicates are an
+ * error.
+ */
+fn dedupe_record_names(
+    records: &mut [SequenceRecord],
+    duplicate_names: DuplicateNames,
+) -> Result<usize, DesignError> {
+    let names: HashSet<String> = records.iter().map(|r| r.name.clone()).collect();
+    if duplicate_names == DuplicateNames::Error && names.len() < records.len() {
+        let mut seen = HashSet::new();
+        let first = records.iter().find(|r| !seen.insert(r.name.as_str()));
+        return Err(DesignError::DuplicateNames {
+            count: records.len() - names.len(),
+            name: first.map(|r| r.name.clone()).unwrap_or_default(),
+        });
+    }
+    let mut taken: HashSet<String> = HashSet::new();
+    let mut duplicates = 0;
+    for record in records.iter_mut() {
+        if taken.insert(record.name.clone()) {
+            continue;
+        }
+        let mut n = 2;
+        let mut unique = format!("{}_{}", record.name, n);
+        while names.contains(&unique) || taken.contains(&unique) {
+            n += 1;
+            unique = format!("{}_{}", record.name, n);
+        }
+        log::debug!("Renamed duplicate record {} to {}", record.name, unique);
+        taken.insert(unique.clone());
+        record.name = unique;
+        duplicates += 1;
+    }
+    Ok(duplicates)
+}
+
+/**
+ * Remove the records that are empty or only gaps and Ns, which have no k-mers to design
+ * from, returning how many were removed
+ */
+fn drop_empty_records(records: &mut Vec<SequenceRecord>) -> usize {
+    let total = records.len();
+    records.retain(|record| {
+        record
+            .sequence
+            .chars()
+            .any(|c| !matches!(c, '-' | 'N' | 'n') && !c.is_whitespace())
+    });
+    total - records.len()
+}
+
+/**
+ * `-` or an empty path stands for stdin/stdout
+ */
+fn is_stdio(path: &str) -> bool {
+    path.is_empty() || path == STDIO_PATH
+}
+
+/**
+ * Create the output file, along with its parent directories when they don't exist yet
+ */
+fn create_file(path: &str) -> io::Result<File> {
+    if let Some(parent) = Path::new(path).parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    File::create(path)
+}
+
+/**
+ * Create the writer of the output file, or stdout for `-`
+ */
+fn create_writer(path: &str) -> io::Result<Box<dyn Write>> {
+    match is_stdio(path) {
+        true => Ok(Box::new(io::stdout().lock())),
+        false => Ok(Box::new(create_file(path)?)),
+    }
+}
+
+/**
+ * Reverse complement with the IUPAC codes, and the `--complement-table` when given. The table
+ * is passed where the words may come from the user, checked, excluded or dimer-checked
+ * primers; words cut from the input sequences only hold IUPAC codes.
+ */
+fn reverse_complement(sequence: &str, table: Option<&HashMap<char, char>>) -> String {
+    sequence
+        .chars()
+        .rev()
+        .map(|code| iupac::complement(code, table))
+        .collect()
+}
+
+/**
+ * Find the distinct k-mers of the sequence in order of first appearance, see
+ * `find_kmers_counted` to keep the number of occurrences
+ */
+pub fn find_kmers(sequence: &str, kmer_size: usize) -> Vec<String> {
+    find_kmers_counted(sequence, kmer_size)
+        .into_iter()
+        .map(|(kmer, _)| kmer)
+        .collect()
+}
+
+/**
+ * Find the distinct k-mers of the sequence in order of first appearance, along with the
+ * number of times each occurs in the sequence
+ */
+pub fn find_kmers_counted(sequence: &str, kmer_size: usize) -> Vec<(String, usize)> {
+    find_kmers_resolved(sequence, kmer_size, 0)
+}
+
+/**
+ * Find the distinct k-mers of the sequence like `find_kmers_counted`, keeping k-mers with
+ * up to `max_n` ambiguous bases (N). An N is a wildcard, the k-mer is resolved to the k-mers
+ * of every base at its positions, so any primer matching the other bases covers the window.
+ */
+fn find_kmers_resolved<'a>(
+    sequence: &'a str,
+    kmer_size: usize,
+    max_n: usize,
+) -> Vec<(String, usize)> {
+    let mut kmers: Vec<(String, usize)> = Vec::new();
+    // k-mers are looked up as slices of the sequence, only the resolved ones of an N are owned
+    let mut positions: HashMap<Cow<str>, usize> = HashMap::new();
+    let mut add = |kmer: Cow<'a, str>| match positions.get(kmer.as_ref()) {
+        Some(&idx) => kmers[idx].1 += 1,
+        None => {
+            kmers.push((kmer.to_string(), 1));
+            positions.insert(kmer, kmers.len() - 1);
+        }
+    };
+    for (start, window) in sequence.as_bytes().windows(kmer_size).enumerate() {
+        if !window.iter().all(|b| b"ATCGUN".contains(b)) {
+            continue;
+        }
+        // the window is ASCII, so it starts and ends on char boundaries
+        let kmer = &sequence[start..start + kmer_size];
+        match window.iter().filter(|b| **b == b'N').count() {
+            0 => add(Cow::Borrowed(kmer)),
+            n if n <= max_n => iupac::expand(kmer)
+                .into_iter()
+                .for_each(|kmer| add(Cow::Owned(kmer))),
+            _ => {}
+        }
+    }
+    kmers
+}
+
+/**
+ * Split the sequence into overlapping partitions of `size`, stepping by `overlap_size`
+ *
+ * When the last full partition doesn't reach the end of the sequence, a shorter trailing
+ * partition covering the remainder is added, as long as it is at least `min_size` long.
+ */
+fn partitioning_sequence(
+    sequence: &str,
+    size: usize,
+    overlap_size: usize,
+    min_size: usize,
+) -> Vec<String> {
+    let chars = sequence.chars().collect::<Vec<char>>();
+    let mut partitions: Vec<String> = chars
+        .windows(size)
+        .step_by(overlap_size)
+        .map(|window| window.iter().collect())
+        .collect();
+
+    let covered_end = match partitions.len() {
+        0 => 0,
+        n => (n - 1) * overlap_size + size,
+    };
+    if covered_end < chars.len() {
+        let tail_start = partitions.len() * overlap_size;
+        if chars.len() - tail_start >= min_size {
+            partitions.push(chars[tail_start..].iter().collect());
+        }
+    }
+    partitions
+}
+
+/**
+ * Window (partition) size giving amplicons of about `target_amplicon` bases
+ *
+ * A forward primer binds the first and a reverse primer the last `search_windows_size`
+ * bases of a window, so a window of `w` bases yields amplicons from `w - 2 * s + 2 * k` to
+ * `w` bases (`s` the search window, `k` the k-mer size), `w - s` between the centres of the
+ * search windows. The window is therefore `target_amplicon + s`. Adjacent windows overlap
+ * by half, the overlap size being `w / 2` like the defaults (500 and 250), which must stay
+ * at least `s` so the search windows of consecutive windows don't leave gaps.
+ */
+fn window_from_amplicon(target_amplicon: usize, search_windows_size: usize) -> usize {
+    target_amplicon + search_windows_size
+}
+
+/**
+ * Get the start and end search windows of a partition
+ *
+ * Returns `None` when the partition is shorter than the search window.
+ */
+fn get_sequence_on_search_windows(
+    sequence: &str,
+    search_windows_size: usize,
+) -> Option<(String, String)> {
+    if sequence.len() < search_windows_size {
+        return None;
+    }
+    let first = &sequence[..search_windows_size];
+    let second = &sequence[sequence.len() - search_windows_size..];
+    Some((first.to_string(), second.to_string()))
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct PartitioningOption {
+    pub segment_size: usize,
+    pub overlap_size: usize,
+    pub window_size: usize,
+    pub kmer_size: usize,
+    pub count_occurrences: bool,
+    pub max_degeneracy: usize,
+    pub max_n: usize,
+    pub canonical: bool,
+    // conservation (0-1) of every aligned position, weighting the k-mers when given
+    pub conservation: Option<Vec<f32>>,
+    // weight of the input sequences by name, e.g. the prevalence of their lineage, 1.0 if missing
+    pub weights: Option<HashMap<String, f32>>,
+    // ranges of the aligned sequences no k-mer may overlap, end exclusive
+    pub mask: Option<Vec<(usize, usize)>>,
+    // range of the aligned sequences to segment, the whole sequences if `None`
+    pub region: Option<(usize, usize)>,
+    #[serde(skip)]
+    pub progress: bool,
+}
+
+impl Default for PartitioningOption {
+    /** The defaults of the command line */
+    fn default() -> Self {
+        PartitioningOption {
+            segment_size: constants::WINDOW_SIZE,
+            overlap_size: constants::OVERLAP_SIZE,
+            window_size: constants::SEARCH_WINDOWS_SIZE,
+            kmer_size: constants::KMER_SIZE,
+            count_occurrences: false,
+            max_degeneracy: constants::MAX_DEGENERACY,
+            max_n: constants::MAX_N,
+            canonical: false,
+            conservation: None,
+            weights: None,
+            mask: None,
+            region: None,
+            progress: false,
+        }
+    }
+}
+
+/**
+ * Check the search windows fit between the overlapping partitions and hold at least one
+ * k-mer, a longer k-mer would silently leave every window without primers
+ */
+fn check_window_sizes(opt: &PartitioningOption) -> Result<(), DesignError> {
+    if opt.overlap_size < opt.window_size {
+        return Err(DesignError::InvalidWindowSizes {
+            overlap_size: opt.overlap_size,
+            window_size: opt.window_size,
+        });
+    }
+    if opt.kmer_size > opt.window_size {
+        return Err(DesignError::KmerLongerThanWindow {
+            kmer_size: opt.kmer_size,
+            window_size: opt.window_size,
+        });
+    }
+    Ok(())
+}
+
+/**
+ * Split every sequence into segments with the k-mers of their search windows
+ *
+ * The forward k-mers come from the start window, the reverse ones are the reverse complement
+ * of the end window k-mers. A word found in both windows, e.g. a palindrome, is kept once
+ * per direction rather than merged: the two records are different primers binding opposite
+ * strands, each counted toward the frequency of its own direction only.
+ */
+pub fn get_segment_manager(
+    records: &[SequenceRecord],
+    opt: PartitioningOption,
+) -> Result<SegmentManager<'_>, DesignError> {
+    let mut manager = SegmentManager {
+        segments: Vec::new(),
+    };
+
+    check_window_sizes(&opt)?;
+
+    let bar = progress::get_progress_bar(
+        records.len(),
+        opt.progress,
+        "Segmenting {bar:40} {pos}/{len} sequences",
+    );
+    // search windows of every segment, to collapse varying k-mers into degenerate primers
+    let mut windows: Vec<[String; 2]> = Vec::new();
+    let (region_start, region_end) = opt.region.unwrap_or((0, usize::MAX));
+    for record in records.iter() {
+        bar.inc(1);
+        // sequences ending before the region have no segments
+        let end = region_end.min(record.sequence.len());
+        let Some(sequence) = record.sequence.get(region_start..end) else {
+            continue;
+        };
+        let partitions = partitioning_sequence(
+            sequence,
+            opt.segment_size,
+            opt.overlap_size,
+            opt.window_size,
+        );
+        for (j, partition) in partitions.iter().enumerate() {
+            let Some((mut start, mut end)) =
+                get_sequence_on_search_windows(partition, opt.window_size)
+            else {
+                log::debug!(
+                    "Skipping partition {} of {}, shorter than search window",
+                    j,
+                    record.name
+                );
+                continue;
+            };
+            let partition_start = region_start + j * opt.overlap_size;
+            let end_start = partition_start + partition.len() - opt.window_size;
+            if let Some(mask) = &opt.mask {
+                start = mask_window(&start, partition_start, mask);
+                end = mask_window(&end, end_start, mask);
+            }
+            let start_kmers = find_kmers_resolved(&start, opt.kmer_size, opt.max_n);
+            let end_kmers = find_kmers_resolved(&end, opt.kmer_size, opt.max_n);
+            // without occurrence counting, a k-mer counts once per window
+            let count = |n: usize| if opt.count_occurrences { n } else { 1 };
+            let mut kmers: [Vec<KmerRecord>; 2] = [Vec::new(), Vec::new()];
+            for (kmer, n) in start_kmers.into_iter() {
+                kmers[0].push(KmerRecord {
+                    word: kmer,
+                    direction: Direction::Forward,
+                    count: count(n),
+                    strands: [true, false],
+                    canonical: None,
+                    weight: 1.0,
+                    conservation: 1.0,
+                });
+            }
+            for (kmer, n) in end_kmers.iter() {
+                kmers[1].push(KmerRecord {
+                    word: reverse_complement(kmer, None),
+                    direction: Direction::Reverse,
+                    count: count(*n),
+                    strands: [true, false],
+                    canonical: None,
+                    weight: 1.0,
+                    conservation: 1.0,
+                });
+            }
+            if let Some(conservation) = &opt.conservation {
+                let windows = [(start.as_str(), partition_start), (end.as_str(), end_start)];
+                weight_kmers(&mut kmers, windows, opt.kmer_size, conservation);
+            }
+            manager.segments.push(Segment {
+                sequence: record,
+                partition_no: j as u16,
+                index: manager.segments.len(),
+                start: partition_start,
+                end: partition_start + partition.len(),
+                kmers,
+            });
+            windows.push([start, end]);
+        }
+    }
+
+    bar.finish_and_clear();
+    if opt.max_degeneracy > 1 {
+        add_degenerate_kmers(
+            &mut manager.segments,
+            &windows,
+            opt.kmer_size,
+            opt.max_degeneracy,
+        );
+    }
+    if opt.canonical {
+        for segment in manager.segments.iter_mut() {
+            for kmers in segment.kmers.iter_mut() {
+                *kmers = canonicalize_kmers(std::mem::take(kmers), opt.count_occurrences);
+            }
+        }
+    }
+    if let Some(weights) = &opt.weights {
+        weight_sequences(&mut manager.segments, weights);
+    }
+    Ok(manager)
+}
+
+/**
+ * Replace the bases of a search window starting at `position` of the alignment that fall in
+ * a masked range by gaps, so no k-mer or degenerate primer overlaps them
+ */
+fn mask_window(window: &str, position: usize, mask: &[(usize, usize)]) -> String {
+    window
+        .chars()
+        .enumerate()
+        .map(|(i, c)| {
+            let p = position + i;
+            match mask.iter().any(|(start, end)| (*start..*end).contains(&p)) {
+                true => '-',
+                false => c,
+            }
+        })
+        .collect()
+}
+
+/**
+ * Parse the masked ranges of a BED file, the 0-based start and exclusive end of the aligned
+ * sequences in the second and third columns. The first column is not matched against the
+ * sequence names, a range masks every sequence. Empty, `#`, `track` and `browser` lines
+ * are skipped.
+ */
+fn parse_mask(content: &str) -> Result<Vec<(usize, usize)>, DesignError> {
+    let mut mask: Vec<(usize, usize)> = Vec::new();
+    for (i, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty()
+            || line.starts_with('#')
+            || line.starts_with("track")
+            || line.starts_with("browser")
+        {
+            continue;
+        }
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let range = match (fields.get(1), fields.get(2)) {
+            (Some(start), Some(end)) => start.parse::<usize>().ok().zip(end.parse::<usize>().ok()),
+            _ => None,
+        };
+        match range {
+            Some((start, end)) if start < end => mask.push((start, end)),
+            _ => {
+                return Err(DesignError::InvalidMask(format!(
+                    "invalid range on line {}, expected the name, start and end with start before end",
+                    i + 1
+                )));
+            }
+        }
+    }
+    Ok(mask)
+}
+
+/**
+ * Weight the k-mers of every segment by the weight of its sequence, so k-mers of heavier
+ * sequences rank higher in the selection while their counts stay occurrences. Sequences
+ * missing from the weights weigh 1.0, k-mers of sequences weighted 0 are dropped.
+ */
+fn weight_sequences(segments: &mut [Segment], weights: &HashMap<String, f32>) {
+    for segment in segments.iter_mut() {
+        let weight = weights.get(&segment.sequence.name).copied().unwrap_or(1.0);
+        for kmers in segment.kmers.iter_mut() {
+            for kmer in kmers.iter_mut() {
+                kmer.weight *= weight;
+            }
+            kmers.retain(|k| k.weight > 0.0);
+        }
+    }
+}
+
+/**
+ * Parse the weights of the input sequences, a TSV of the sequence name and its weight, e.g.
+ * the prevalence of its lineage. Empty lines, `#` comments and a header are skipped.
+ */
+fn parse_sequence_weights(content: &str) -> Result<HashMap<String, f32>, DesignError> {
+    let mut weights: HashMap<String, f32> = HashMap::new();
+    let mut is_first = true;
+    for (i, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let is_header = std::mem::replace(&mut is_first, false);
+        let fields: Vec<&str> = line.split('\t').collect();
+        let (Some(name), Some(weight)) = (fields.first(), fields.get(1)) else {
+            return Err(DesignError::InvalidSequenceWeights(format!(
+                "line {} has no weight",
+                i + 1
+            )));
+        };
+        match weight.trim().parse::<f32>() {
+            Ok(weight) if weight.is_finite() && weight >= 0.0 => {
+                weights.insert(name.trim().to_string(), weight);
+            }
+            Err(_) if is_header => continue,
+            _ => {
+                return Err(DesignError::InvalidSequenceWeights(format!(
+                    "invalid weight {} on line {}, weights are 0 or greater",
+                    weight,
+                    i + 1
+                )));
+            }
+        }
+    }
+    Ok(weights)
+}
+
+/**
+ * Conservation of every k-mer of a search window starting at `position` of the alignment,
+ * the lowest conservation of its positions. Positions missing from the track count as not
+ * conserved, and a k-mer found more than once gets its best conservation.
+ */
+fn get_window_conservation(
+    window: &str,
+    position: usize,
+    kmer_size: usize,
+    conservation: &[f32],
+) -> HashMap<String, f32> {
+    let mut scores: HashMap<String, f32> = HashMap::new();
+    for offset in 0..(window.len() + 1).saturating_sub(kmer_size) {
+        let score = (position + offset..position + offset + kmer_size)
+            .map(|p| conservation.get(p).copied().unwrap_or(0.0))
+            .fold(1.0f32, f32::min);
+        let score = score.clamp(0.0, 1.0);
+        for kmer in iupac::expand(&window[offset..offset + kmer_size]) {
+            let entry = scores.entry(kmer).or_default();
+            *entry = entry.max(score);
+        }
+    }
+    scores
+}
+
+/**
+ * Set the conservation of the k-mers of both search windows, so the selection prefers
+ * conserved k-mers even for a single consensus sequence while their counts stay
+ * occurrences. K-mers of positions without any conservation are dropped.
+ */
+fn weight_kmers(
+    kmers: &mut [Vec<KmerRecord>; 2],
+    windows: [(&str, usize); 2],
+    kmer_size: usize,
+    conservation: &[f32],
+) {
+    for (kmers, (window, position)) in kmers.iter_mut().zip(windows) {
+        let scores = get_window_conservation(window, position, kmer_size, conservation);
+        for kmer in kmers.iter_mut() {
+            // reverse k-mers are stored as the reverse complement of the window
+            let template = match kmer.direction {
+                Direction::Forward => kmer.word.clone(),
+                Direction::Reverse => reverse_complement(&kmer.word, None),
+            };
+            kmer.conservation = scores.get(&template).copied().unwrap_or_default();
+        }
+        kmers.retain(|k| k.conservation > 0.0);
+    }
+}
+
+/**
+ * Parse a conservation track, a TSV of the 1-based aligned position and its conservation
+ * from 0 (variable) to 1 (conserved). Empty lines, `#` comments and a header are skipped.
+ */
+fn parse_conservation(content: &str) -> Result<Vec<f32>, DesignError> {
+    let mut conservation: Vec<f32> = Vec::new();
+    let mut is_first = true;
+    for (i, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let is_header = std::mem::replace(&mut is_first, false);
+        let fields: Vec<&str> = line.split('\t').collect();
+        let (Some(position), Some(score)) = (fields.first(), fields.get(1)) else {
+            return Err(DesignError::InvalidConservation(format!(
+                "line {} has no score",
+                i + 1
+            )));
+        };
+        let Ok(position) = position.trim().parse::<usize>() else {
+            if is_header {
+                continue;
+            }
+            return Err(DesignError::InvalidConservation(format!(
+                "invalid position {} on line {}",
+                position,
+                i + 1
+            )));
+        };
+        let score = match score.trim().parse::<f32>() {
+            Ok(score) if position > 0 && (0.0..=1.0).contains(&score) => score,
+            _ => {
+                return Err(DesignError::InvalidConservation(format!(
+                    "invalid position or score on line {}, positions start at 1 and scores are 0-1",
+                    i + 1
+                )));
+            }
+        };
+        if conservation.len() < position {
+            conservation.resize(position, 0.0);
+        }
+        conservation[position - 1] = score;
+    }
+    Ok(conservation)
+}
+
+/**
+ * Canonical k-mer of a word, the lexicographically smaller of the word and its reverse
+ * complement
+ */
+fn get_canonical_kmer(word: &str) -> String {
+    let reverse = reverse_complement(word, None);
+    match reverse.as_str() < word {
+        true => reverse,
+        false => word.to_string(),
+    }
+}
+
+/**
+ * Key the words of a window by their canonical k-mers, so both strands of the same site
+ * count as one k-mer
+ *
+ * A word and its reverse complement in the same window are merged, their counts added with
+ * `count_occurrences` or counted once otherwise. The merged record keeps the word first
+ * observed, so primers are reported in an orientation found in the template, and `strands`
+ * keeps which of the word and its reverse complement were seen.
+ */
+fn canonicalize_kmers(kmers: Vec<KmerRecord>, count_occurrences: bool) -> Vec<KmerRecord> {
+    let mut canonical: Vec<KmerRecord> = Vec::with_capacity(kmers.len());
+    let mut positions: HashMap<String, usize> = HashMap::new();
+    for kmer in kmers {
+        let key = get_canonical_kmer(&kmer.word);
+        match positions.get(&key) {
+            Some(&idx) => {
+                let merged = &mut canonical[idx];
+                let strands = match merged.word == kmer.word {
+                    true => kmer.strands,
+                    false => [kmer.strands[1], kmer.strands[0]],
+                };
+                merged.count = match count_occurrences {
+                    true => merged.count + kmer.count,
+                    false => merged.count.max(kmer.count),
+                };
+                merged.strands = [
+                    merged.strands[0] || strands[0],
+                    merged.strands[1] || strands[1],
+                ];
+                merged.weight = merged.weight.max(kmer.weight);
+                merged.conservation = merged.conservation.max(kmer.conservation);
+            }
+            None => {
+                positions.insert(key.clone(), canonical.len());
+                canonical.push(KmerRecord {
+                    canonical: Some(key),
+                    weight: 1.0,
+                    conservation: 1.0,
+                    ..kmer
+                });
+            }
+        }
+    }
+    canonical
+}
+
+/**
+ * Add degenerate k-mers to the segments for k-mers that vary across the sequences
+ *
+ * At every offset of the search windows of a partition, the k-mers of all sequences are
+ * collapsed into one IUPAC word if it stands for at most `max_degeneracy` sequences. The
+ * word is added to every segment whose k-mer it covers, k-mers with gaps or masked bases
+ * are left out. Reverse words are the reverse complement of the end window consensus.
+ */
+fn add_degenerate_kmers(
+    segments: &mut [Segment],
+    windows: &[[String; 2]],
+    kmer_size: usize,
+    max_degeneracy: usize,
+) {
+    let partitions = (0..segments.len()).into_group_map_by(|&i| segments[i].partition_no);
+    let mut degenerate: BTreeMap<(usize, Direction), BTreeSet<String>> = BTreeMap::new();
+    for indices in partitions.values() {
+        for (d, direction) in Direction::ALL.into_iter().enumerate() {
+            let Some(len) = indices.iter().map(|&i| windows[i][d].len()).min() else {
+                continue;
+            };
+            for offset in 0..(len + 1).saturating_sub(kmer_size) {
+                let kmers: Vec<(usize, &str)> = indices
+                    .iter()
+                    .map(|&i| (i, &windows[i][d][offset..offset + kmer_size]))
+                    .filter(|(_, kmer)| kmer.chars().all(|c| "ACGT".contains(c)))
+                    .collect();
+                if kmers.iter().map(|(_, kmer)| kmer).unique().count() < 2 {
+                    continue;
+                }
+                let words: Vec<&str> = kmers.iter().map(|(_, kmer)| *kmer).collect();
+                let Some(consensus) = iupac::get_consensus(&words, max_degeneracy) else {
+                    continue;
+                };
+                let word = match direction {
+                    Direction::Forward => consensus,
+                    Direction::Reverse => reverse_complement(&consensus, None),
+                };
+                for (i, _) in kmers {
+                    degenerate
+                        .entry((i, direction))
+                        .or_default()
+                        .insert(word.clone());
+                }
+            }
+        }
+    }
+    for ((i, direction), words) in degenerate {
+        log::debug!(
+            "{} degenerate {:?} k-mers for segment {}",
+            words.len(),
+            direction,
+            i
+        );
+        for word in words {
+            segments[i].kmers[direction as usize].push(KmerRecord {
+                word,
+                direction,
+                count: 1,
+                strands: [true, false],
+                canonical: None,
+                weight: 1.0,
+                conservation: 1.0,
+            });
+        }
+    }
+}
+
+/**
+ * All words within `max_mismatch` substitutions of the k-mer, including the k-mer itself
+ */
+fn hamming_neighbours(word: &str, max_mismatch: usize) -> HashSet<String> {
+    let mut neighbours = HashSet::from([word.to_string()]);
+    for _ in 0..max_mismatch {
+        let mut next = neighbours.clone();
+        for neighbour in neighbours.iter() {
+            for (pos, c) in neighbour.char_indices() {
+                for base in ['A', 'C', 'G', 'T'] {
+                    if base != c {
+                        let mut variant = neighbour.clone();
+                        variant
+                            .replace_range(pos..pos + c.len_utf8(), base.encode_utf8(&mut [0; 4]));
+                        next.insert(variant);
+                    }
+                }
+            }
+        }
+        neighbours = next;
+    }
+    neighbours
+}
+
+/**
+ * Find the k-mers of `observed` matching any k-mer of the window within `max_mismatch`
+ * substitutions, each k-mer counts at most once per window with the highest weight of the
+ * window k-mers it matches
+ */
+fn get_matched_kmers<'a>(
+    kmers: &[KmerRecord],
+    direction: Direction,
+    observed: &HashSet<&'a KmerRecord>,
+    max_mismatch: usize,
+) -> HashMap<&'a KmerRecord, f64> {
+    let mut matched: HashMap<&'a KmerRecord, f64> = HashMap::new();
+    for kmer in kmers.iter() {
+        if kmer.direction != direction {
+            continue;
+        }
+        for neighbour in hamming_neighbours(&kmer.word, max_mismatch) {
+            let key = KmerRecord {
+                canonical: kmer
+                    .canonical
+                    .as_ref()
+                    .map(|_| get_canonical_kmer(&neighbour)),
+                word: neighbour,
+                direction,
+                count: 0,
+                strands: [true, false],
+                weight: 1.0,
+                conservation: 1.0,
+            };
+            if let Some(k) = observed.get(&key) {
+                let weight = matched.entry(*k).or_default();
+                *weight = weight.max(kmer.rank_weight());
+            }
+        }
+    }
+    matched
+}
+
+fn get_observed_kmers<'a>(segments: &'a [Segment<'a>]) -> HashSet<&'a KmerRecord> {
+    segments
+        .iter()
+        .flat_map(|s| s.kmers.iter().flatten())
+        .collect()
+}
+
+fn make_kmer_segments_windows_mapping<'a>(
+    segments: &'a Vec<Segment<'a>>,
+    max_mismatch: usize,
+) -> HashMap<&'a KmerRecord, Vec<u32>> {
+    let mut kmer_segments_mapping: HashMap<&'a KmerRecord, Vec<u32>> = HashMap::new();
+    if max_mismatch > 0 {
+        let observed = get_observed_kmers(segments);
+        for segment in segments.iter() {
+            for (direction, kmers) in Direction::ALL.into_iter().zip(segment.kmers.iter()) {
+                for kmer in get_matched_kmers(kmers, direction, &observed, max_mismatch).into_keys()
+                {
+                    kmer_segments_mapping
+                        .entry(kmer)
+                        .or_default()
+                        .push(segment.index as u32);
+                }
+            }
+        }
+        return kmer_segments_mapping;
+    }
+
+    for segment in segments.iter() {
+        for (direction, kmers) in Direction::ALL.into_iter().zip(segment.kmers.iter()) {
+            for kmer in kmers.iter() {
+                if kmer.direction != direction {
+                    continue;
+                }
+                kmer_segments_mapping
+                    .entry(kmer)
+                    .or_default()
+                    .push(segment.index as u32);
+            }
+        }
+    }
+    kmer_segments_mapping
+}
+
+/**
+ * Frequency, number of segments and weighted frequency of every k-mer of the uncovered
+ * segments, the weighted frequency ranking the k-mers
+ */
+type KmerCounts<'a> = HashMap<&'a KmerRecord, (usize, usize, f64)>;
+
+/**
+ * Add the k-mers of the window of a segment to the counts, with mismatches allowed a k-mer
+ * counts every window holding a near-identical k-mer
+ */
+fn count_segment_kmers<'a>(
+    counts: &mut KmerCounts<'a>,
+    segment: &'a Segment,
+    direction: Direction,
+    observed: &HashSet<&'a KmerRecord>,
+    max_mismatch: usize,
+) {
+    let kmers = &segment.kmers[direction as usize];
+    if max_mismatch > 0 {
+        for (kmer, weight) in get_matched_kmers(kmers, direction, observed, max_mismatch) {
+            let entry = counts.entry(kmer).or_insert((0, 0, 0.0));
+            entry.0 += 1;
+            entry.1 += 1;
+            entry.2 += weight;
+        }
+        return;
+    }
+    for kmer in kmers.iter() {
+        let entry = counts.entry(kmer).or_insert((0, 0, 0.0));
+        entry.0 += kmer.count;
+        entry.1 += 1;
+        entry.2 += kmer.count as f64 * kmer.rank_weight();
+    }
+}
+
+#[cfg(any(test, not(feature = "rayon")))]
+fn count_kmers_serial<'a>(
+    segments: &'a [Segment],
+    direction: Direction,
+    ignored_segments_windows: &HashSet<u32>,
+    observed: &HashSet<&'a KmerRecord>,
+    max_mismatch: usize,
+) -> KmerCounts<'a> {
+    let mut counts = KmerCounts::new();
+    for (idx, segment) in segments.iter().enumerate() {
+        if ignored_segments_windows.contains(&(idx as u32)) {
+            continue;
+        }
+        count_segment_kmers(&mut counts, segment, direction, observed, max_mismatch);
+    }
+    counts
+}
+
+/**
+ * Count the k-mers of the segments in parallel, the counts are sums so they don't depend
+ * on how the segments are split between the threads
+ */
+#[cfg(feature = "rayon")]
+fn count_kmers<'a>(
+    segments: &'a [Segment],
+    direction: Direction,
+    ignored_segments_windows: &HashSet<u32>,
+    observed: &HashSet<&'a KmerRecord>,
+    max_mismatch: usize,
+) -> KmerCounts<'a> {
+    use rayon::prelude::*;
+    segments
+        .par_iter()
+        .enumerate()
+        .filter(|(idx, _)| !ignored_segments_windows.contains(&(*idx as u32)))
+        .fold(KmerCounts::new, |mut counts, (_, segment)| {
+            count_segment_kmers(&mut counts, segment, direction, observed, max_mismatch);
+            counts
+        })
+        .reduce(KmerCounts::new, |mut a, b| {
+            for (kmer, (frequency, segments, weighted)) in b {
+                let entry = a.entry(kmer).or_insert((0, 0, 0.0));
+                entry.0 += frequency;
+                entry.1 += segments;
+                entry.2 += weighted;
+            }
+            a
+        })
+}
+
+#[cfg(not(feature = "rayon"))]
+fn count_kmers<'a>(
+    segments: &'a [Segment],
+    direction: Direction,
+    ignored_segments_windows: &HashSet<u32>,
+    observed: &HashSet<&'a KmerRecord>,
+    max_mismatch: usize,
+) -> KmerCounts<'a> {
+    count_kmers_serial(
+        segments,
+        direction,
+        ignored_segments_windows,
+        observed,
+        max_mismatch,
+    )
+}
+
+/**
+ * Find the best k-mer of the uncovered segments for the selection strategy
+ *
+ * `MaxFrequency` picks the k-mer with the highest frequency, `MaxCoverage` the one found in
+ * the most uncovered segments, breaking ties by frequency. They only differ when a k-mer
+ * counts more than once per segment, with `--count-occurrences`.
+ */
+fn find_most_freq_kmer<'a>(
+    segments: &'a Vec<Segment>,
+    direction: Direction,
+    ignored_segments_windows: &HashSet<u32>,
+    max_mismatch: usize,
+    strategy: Strategy,
+) -> Option<KmerFrequency<'a>> {
+    let observed = match max_mismatch {
+        0 => HashSet::new(),
+        _ => get_observed_kmers(segments),
+    };
+    let counts = count_kmers(
+        segments,
+        direction,
+        ignored_segments_windows,
+        &observed,
+        max_mismatch,
+    );
+    get_best_kmer(&counts, strategy)
+}
+
+/**
+ * K-mer with the best counts for the selection strategy
+ *
+ * The weighted frequency ranks the k-mers, equal to the frequency without weights. Ties are
+ * broken by the frequency, then the lexicographically smallest word and the direction, so
+ * the selection doesn't depend on the HashMap iteration order.
+ */
+fn get_best_kmer<'a>(counts: &KmerCounts<'a>, strategy: Strategy) -> Option<KmerFrequency<'a>> {
+    counts
+        .iter()
+        .max_by(
+            |(a, (freq_a, segments_a, weighted_a)), (b, (freq_b, segments_b, weighted_b))| {
+                let coverage = match strategy {
+                    Strategy::MaxFrequency => std::cmp::Ordering::Equal,
+                    Strategy::MaxCoverage => segments_a.cmp(segments_b),
+                };
+                coverage
+                    .then_with(|| weighted_a.total_cmp(weighted_b))
+                    .then_with(|| freq_a.cmp(freq_b))
+                    .then_with(|| b.key().cmp(a.key()))
+                    .then_with(|| b.direction.cmp(&a.direction))
+            },
+        )
+        .map(|(k, &(f, _, _))| KmerFrequency {
+            kmer: k,
+            frequency: f,
+        })
+}
+
+/**
+ * Segments left uncovered, more ignored than total segments means the accounting of the
+ * ignored windows is off, which is logged instead of underflowing
+ */
+fn get_remaining_segments(total_segments: usize, ignored_segments: usize) -> usize {
+    if ignored_segments > total_segments {
+        log::warn!(
+            "Ignored segments ({}) exceed the total segments ({})",
+            ignored_segments,
+            total_segments
+        );
+    }
+    total_segments.saturating_sub(ignored_segments)
+}
+
+/**
+ * Whether the covered segments reached the `--target-coverage` fraction of all segments
+ */
+fn is_target_coverage_reached(remaining: usize, total_segments: usize, target: f32) -> bool {
+    total_segments > 0
+        && (total_segments - remaining) as f32 / total_segments as f32 >= target.min(1.0)
+}
+
+/**
+ * Whether the selection stopped at `--max-iterations` rather than running out of k-mers,
+ * reaching `max_mismatch_segments` or the target coverage, which is logged with the segments
+ * left uncovered
+ */
+fn is_selection_capped(
+    reports: &[IterationReport],
+    direction: Direction,
+    total_segments: usize,
+    config: &ProgramConfig,
+) -> bool {
+    let remaining = reports.last().map_or(total_segments, |r| r.remaining);
+    if reports.len() < config.max_iterations
+        || remaining <= config.max_mismatch_segments
+        || is_target_coverage_reached(remaining, total_segments, config.target_coverage)
+    {
+        return false;
+    }
+    log::warn!(
+        "Primer selection ({:?}) stopped at --max-iterations ({}) with {} segments uncovered, \
+         raise it for full coverage",
+        direction,
+        config.max_iterations,
+        remaining
+    );
+    true
+}
+
+/**
+ * Report of one iteration of the greedy primer selection
+ */
+pub struct IterationReport {
+    pub iteration: usize,
+    pub direction: Direction,
+    pub word: String,
+    pub frequency: usize,
+    // segments covered by the winner that were not covered before
+    pub newly_skipped: usize,
+    pub remaining: usize,
+}
+
+pub fn find_candidates_kmers<'a>(
+    segment_manager: &'a SegmentManager,
+    direction: Direction,
+    config: ProgramConfig,
+) -> Option<Vec<KmerFrequency<'a>>> {
+    find_candidates_kmers_traced(segment_manager, direction, config).0
+}
+
+/**
+ * Greedily select the k-mers covering the most segments, along with a report of every
+ * iteration of the selection
+ */
+pub fn find_candidates_kmers_traced<'a>(
+    segment_manager: &'a SegmentManager,
+    direction: Direction,
+    config: ProgramConfig,
+) -> (Option<Vec<KmerFrequency<'a>>>, Vec<IterationReport>) {
+    let mut candidates = iter_candidates_kmers(segment_manager, direction, config);
+    let candidate_kmers: Vec<KmerFrequency> = candidates.by_ref().collect();
+    let reports = candidates.into_reports();
+
+    if candidate_kmers.is_empty() {
+        return (None, reports);
+    }
+
+    (Some(candidate_kmers), reports)
+}
+
+/**
+ * Greedily select the k-mers covering the most segments, yielding every winner as soon as
+ * its iteration is done. Dropping the iterator stops the selection.
+ */
+pub fn iter_candidates_kmers<'a>(
+    segment_manager: &'a SegmentManager,
+    direction: Direction,
+    config: ProgramConfig,
+) -> CandidateKmers<'a> {
+    let total_segments = segment_manager.segments.len();
+    CandidateKmers {
+        segment_manager,
+        direction,
+        kmer_segments_windows_mappings: make_kmer_segments_windows_mapping(
+            &segment_manager.segments,
+            config.max_mismatch,
+        ),
+        ignored_segments_windows: HashSet::new(),
+        total_segments,
+        reports: Vec::new(),
+        bar: progress::get_progress_bar(
+            total_segments,
+            config.progress,
+            "Selecting {bar:40} {pos}/{len} segments covered, {msg}",
+        ),
+        config,
+        state: SelectionState::Running,
+    }
+}
+
+#[derive(PartialEq)]
+enum SelectionState {
+    Running,
+    // the last winner reached the stop criteria, the selection ends at the next call
+    Stopping,
+    Finished,
+}
+
+/**
+ * Iterator over the winners of the greedy primer selection, see `iter_candidates_kmers`
+ */
+pub struct CandidateKmers<'a> {
+    segment_manager: &'a SegmentManager<'a>,
+    direction: Direction,
+    config: ProgramConfig,
+    kmer_segments_windows_mappings: HashMap<&'a KmerRecord, Vec<u32>>,
+    ignored_segments_windows: HashSet<u32>,
+    total_segments: usize,
+    reports: Vec<IterationReport>,
+    bar: indicatif::ProgressBar,
+    state: SelectionState,
+}
+
+impl CandidateKmers<'_> {
+    /**
+     * Reports of the iterations run so far
+     */
+    pub fn reports(&self) -> &[IterationReport] {
+        &self.reports
+    }
+
+    pub fn into_reports(self) -> Vec<IterationReport> {
+        self.reports
+    }
+
+    fn finish(&mut self) {
+        if self.state == SelectionState::Finished {
+            return;
+        }
+        self.state = SelectionState::Finished;
+        self.bar.finish_and_clear();
+        is_selection_capped(
+            &self.reports,
+            self.direction,
+            self.total_segments,
+            &self.config,
+        );
+    }
+}
+
+impl<'a> Iterator for CandidateKmers<'a> {
+    type Item = KmerFrequency<'a>;
+
+    fn next(&mut self) -> Option<KmerFrequency<'a>> {
+        let iter_no = self.reports.len();
+        if self.state != SelectionState::Running || iter_no >= self.config.max_iterations {
+            self.finish();
+            return None;
+        }
+        log::trace!("Iteration: {}", iter_no + 1);
+        let kmer_freq = match find_most_freq_kmer(
+            &self.segment_manager.segments,
+            self.direction,
+            &self.ignored_segments_windows,
+            self.config.max_mismatch,
+            self.config.strategy,
+        ) {
+            Some(k) => {
+                if k.frequency == 1 {
+                    log::trace!(
+                        "Iteration: {}, only 1 shared window found, stop ...",
+                        iter_no
+                    );
+                    self.finish();
+                    return None;
+                }
+                k
+            }
+            None => {
+                log::trace!("Iteration: {}, no k-mers found, stop ...", iter_no);
+                self.finish();
+                return None;
+            }
+        };
+        // update ignored segments
+        let mut count = 0;
+        let total_ignored = self.ignored_segments_windows.len();
+        for idx in self
+            .kmer_segments_windows_mappings
+            .get(kmer_freq.kmer)
+            .unwrap()
+        {
+            count += 1;
+            self.ignored_segments_windows.insert(*idx);
+        }
+        log::debug!(
+            iteration = iter_no + 1,
+            direction = self.direction.label(),
+            winner = kmer_freq.kmer.word.as_str();
+            "Iteration: {}, direction: {:?} winner: {} (strands {:?}), windows removed: {}, \
+             total removed: {}",
+            iter_no,
+            self.direction,
+            kmer_freq.kmer.word,
+            kmer_freq.kmer.strands,
+            count,
+            self.ignored_segments_windows.len()
+        );
+        // stop once at most `max_mismatch_segments` segments are left uncovered
+        let total_segments = self.total_segments;
+        let remaining_segments =
+            get_remaining_segments(total_segments, self.ignored_segments_windows.len());
+        self.reports.push(IterationReport {
+            iteration: iter_no + 1,
+            direction: self.direction,
+            word: kmer_freq.kmer.word.clone(),
+            frequency: kmer_freq.frequency,
+            newly_skipped: self.ignored_segments_windows.len() - total_ignored,
+            remaining: remaining_segments,
+        });
+        self.bar
+            .set_position((total_segments - remaining_segments) as u64);
+        self.bar.set_message(format!(
+            "{} primers selected, {} segments remaining",
+            self.reports.len(),
+            remaining_segments
+        ));
+        if remaining_segments <= self.config.max_mismatch_segments {
+            log::info!(
+                "Max mismatch segments reached, {} segments remaining, exiting...",
+                remaining_segments
+            );
+            self.state = SelectionState::Stopping;
+        } else if is_target_coverage_reached(
+            remaining_segments,
+            total_segments,
+            self.config.target_coverage,
+        ) {
+            log::info!(
+                "Target coverage reached, {}/{} segments covered ({:.2}%), exiting...",
+                total_segments - remaining_segments,
+                total_segments,
+                (total_segments - remaining_segments) as f32 / total_segments as f32 * 100.0
+            );
+            self.state = SelectionState::Stopping;
+        }
+        Some(kmer_freq)
+    }
+}
+
+/**
+ * Names of the sequences with a search window of the direction holding the word, with up
+ * to `max_mismatch` substitutions, in the order of the input
+ */
+fn get_supporting_sequences(
+    segments: &[Segment],
+    word: &str,
+    direction: Direction,
+    max_mismatch: usize,
+) -> Vec<String> {
+    let matches = |kmer: &KmerRecord| {
+        kmer.direction == direction
+            && kmer.word.len() == word.len()
+            && kmer
+                .word
+                .bytes()
+                .zip(word.bytes())
+                .filter(|(a, b)| a != b)
+                .count()
+                <= max_mismatch
+    };
+    let mut sequences: Vec<String> = Vec::new();
+    for segment in segments {
+        let name = &segment.sequence.name;
+        if sequences.contains(name) {
+            continue;
+        }
+        if segment.kmers.iter().flatten().any(matches) {
+            sequences.push(name.clone());
+        }
+    }
+    sequences
+}
+
+/**
+ * Occurrences of every k-mer on the ungapped input sequences of the segments, each sequence
+ * counted once
+ */
+fn get_site_counts(segments: &[Segment], kmer_size: usize) -> HashMap<String, usize> {
+    let mut seen: HashSet<*const SequenceRecord> = HashSet::new();
+    let records: Vec<&SequenceRecord> = segments
+        .iter()
+        .map(|segment| segment.sequence)
+        .filter(|record| seen.insert(std::ptr::from_ref(*record)))
+        .collect();
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    for record in records {
+        let sequence: Vec<u8> = record.sequence.bytes().filter(|b| *b != b'-').collect();
+        for site in sequence.windows(kmer_size) {
+            let site = String::from_utf8_lossy(site).into_owned();
+            *counts.entry(site).or_default() += 1;
+        }
+    }
+    counts
+}
+
+/**
+ * Binding sites of the primer on the full sequences, on either strand and with up to
+ * `max_mismatch` substitutions, outside the search windows of its direction. Every window
+ * holding the word is one intended site, the rest would prime spurious amplicons.
+ */
+fn get_self_binding_count(
+    segments: &[Segment],
+    site_counts: &HashMap<String, usize>,
+    word: &str,
+    direction: Direction,
+    max_mismatch: usize,
+    complement_table: Option<&HashMap<char, char>>,
+) -> usize {
+    let mut sites: HashSet<String> = HashSet::new();
+    let reverse = reverse_complement(word, complement_table);
+    for strand in [word, reverse.as_str()] {
+        for expansion in iupac::expand(strand) {
+            sites.extend(hamming_neighbours(&expansion, max_mismatch));
+        }
+    }
+    let total: usize = sites.iter().filter_map(|s| site_counts.get(s)).sum();
+    let matches = |kmer: &KmerRecord| {
+        kmer.word.len() == word.len()
+            && kmer
+                .word
+                .bytes()
+                .zip(word.bytes())
+                .filter(|(a, b)| a != b)
+                .count()
+                <= max_mismatch
+    };
+    let index = match direction {
+        Direction::Forward => 0,
+        Direction::Reverse => 1,
+    };
+    let intended = segments
+        .iter()
+        .filter(|segment| segment.kmers[index].iter().any(matches))
+        .count();
+    total.saturating_sub(intended)
+}
+
+/**
+ * A primer given to `--check`, reverse primers are the oligo like the designed ones
+ */
+struct CheckPrimer {
+    name: String,
+    word: String,
+    direction: Direction,
+}
+
+/**
+ * Coverage of a checked primer over the input, the segments of its direction it binds and
+ * its binding sites outside them
+ */
+struct PrimerCoverage {
+    segments: usize,
+    sequences: Vec<String>,
+    self_binding_count: usize,
+}
+
+/**
+ * Direction of a checked primer by its label, `F`/`R` like the CSV output or the name
+ * suffix of a FASTA record such as `_R`, `-REV` or `_reverse`
+ */
+fn parse_check_direction(label: &str) -> Option<Direction> {
+    match label.trim().to_uppercase().as_str() {
+        "F" | "FW" | "FWD" | "FORWARD" | "LEFT" => Some(Direction::Forward),
+        "R" | "RV" | "REV" | "REVERSE" | "RIGHT" => Some(Direction::Reverse),
+        _ => None,
+    }
+}
+
+/**
+ * Parse the primers to check, FASTA records named with a direction suffix (forward when
+ * missing) or a CSV with a header of at least the `primers` column, e.g. a previous output
+ */
+fn parse_check_primers(content: &str) -> Result<Vec<CheckPrimer>, DesignError> {
+    let mut primers: Vec<CheckPrimer> = Vec::new();
+    if content.trim_start().starts_with('>') {
+        for block in content.split('>').skip(1) {
+            let mut lines = block.lines();
+            let name = lines
+                .next()
+                .and_then(|header| header.split_whitespace().next())
+                .unwrap_or_default()
+                .to_string();
+            let word: String = lines.map(|l| l.trim().to_uppercase()).collect();
+            if word.is_empty() {
+                return Err(DesignError::InvalidCheckPrimers(format!(
+                    "no sequence for {}",
+                    name
+                )));
+            }
+            let direction = name
+                .rsplit(['_', '-'])
+                .next()
+                .filter(|suffix| suffix.len() < name.len())
+                .and_then(parse_check_direction)
+                .unwrap_or(Direction::Forward);
+            primers.push(CheckPrimer {
+                name,
+                word,
+                direction,
+            });
+        }
+        return Ok(primers);
+    }
+
+    let mut reader = csv::Reader::from_reader(content.as_bytes());
+    let headers = reader
+        .headers()
+        .map_err(|e| DesignError::InvalidCheckPrimers(e.to_string()))?
+        .clone();
+    let column = |name: &str| headers.iter().position(|h| h.trim() == name);
+    let Some(word_column) = column("primers") else {
+        return Err(DesignError::InvalidCheckPrimers(
+            "no primers column in the CSV header".to_string(),
+        ));
+    };
+    for (i, row) in reader.records().enumerate() {
+        let row = row.map_err(|e| DesignError::InvalidCheckPrimers(e.to_string()))?;
+        let field = |column: Option<usize>| column.and_then(|c| row.get(c)).unwrap_or_default();
+        let direction = match field(column("direction")) {
+            "" => Direction::Forward,
+            label => parse_check_direction(label).ok_or_else(|| {
+                DesignError::InvalidCheckPrimers(format!(
+                    "invalid direction {} on row {}",
+                    label,
+                    i + 1
+                ))
+            })?,
+        };
+        let name = match field(column("name")) {
+            "" => format!("Primer_{}_{}", i, direction.label()),
+            name => name.to_string(),
+        };
+        primers.push(CheckPrimer {
+            name,
+            word: field(Some(word_column)).trim().to_uppercase(),
+            direction,
+        });
+    }
+    Ok(primers)
+}
+
+/**
+ * Coverage of every checked primer, the segments with a k-mer of its direction matching any
+ * of its sequences within `max_mismatch` substitutions. The input is segmented once per
+ * primer length, so the primers don't have to be as long as `--kmer-size`.
+ */
+fn get_primer_coverage(
+    records: &[SequenceRecord],
+    options: &PartitioningOption,
+    primers: &[CheckPrimer],
+    max_mismatch: usize,
+    complement_table: Option<&HashMap<char, char>>,
+) -> Result<Vec<PrimerCoverage>, DesignError> {
+    let mut coverage: Vec<Option<PrimerCoverage>> = primers.iter().map(|_| None).collect();
+    let lengths = primers.iter().map(|p| p.word.len()).unique().sorted();
+    for kmer_size in lengths {
+        let manager = get_segment_manager(
+            records,
+            PartitioningOption {
+                kmer_size,
+                progress: false,
+                ..options.clone()
+            },
+        )?;
+        let site_counts = get_site_counts(&manager.segments, kmer_size);
+        for (primer, coverage) in primers.iter().zip(coverage.iter_mut()) {
+            if primer.word.len() != kmer_size {
+                continue;
+            }
+            let expansions: Vec<KmerRecord> = iupac::expand(&primer.word)
+                .into_iter()
+                .map(|word| KmerRecord {
+                    canonical: options.canonical.then(|| get_canonical_kmer(&word)),
+                    weight: 1.0,
+                    conservation: 1.0,
+                    word,
+                    direction: primer.direction,
+                    count: 0,
+                    strands: [true, false],
+                })
+                .collect();
+            let observed: HashSet<&KmerRecord> = expansions.iter().collect();
+            let mut sequences: Vec<String> = Vec::new();
+            let mut segments = 0;
+            for segment in manager.segments.iter() {
+                let kmers = &segment.kmers[primer.direction as usize];
+                if get_matched_kmers(kmers, primer.direction, &observed, max_mismatch).is_empty() {
+                    continue;
+                }
+                segments += 1;
+                if !sequences.contains(&segment.sequence.name) {
+                    sequences.push(segment.sequence.name.clone());
+                }
+            }
+            *coverage = Some(PrimerCoverage {
+                segments,
+                sequences,
+                self_binding_count: get_self_binding_count(
+                    &manager.segments,
+                    &site_counts,
+                    &primer.word,
+                    primer.direction,
+                    max_mismatch,
+                    complement_table,
+                ),
+            });
+        }
+    }
+    Ok(coverage.into_iter().flatten().collect())
+}
+
+/**
+ * Evaluate existing primers against the input without any selection, the stats of every
+ * primer with its coverage as the frequency, in the order given
+ */
+fn evaluate_primers(
+    records: &[SequenceRecord],
+    options: &PartitioningOption,
+    primers: &[CheckPrimer],
+    program_config: &ProgramConfig,
+) -> Result<Vec<Vec<KmerStat>>, DesignError> {
+    let coverage = get_primer_coverage(
+        records,
+        options,
+        primers,
+        program_config.max_mismatch,
+        program_config.complement_table.as_ref(),
+    )?;
+    let kmers: Vec<KmerRecord> = primers
+        .iter()
+        .map(|p| KmerRecord {
+            word: p.word.clone(),
+            direction: p.direction,
+            count: 0,
+            strands: [true, false],
+            canonical: None,
+            weight: 1.0,
+            conservation: 1.0,
+        })
+        .collect();
+    let kmer_records: Vec<KmerFrequency> = kmers
+        .iter()
+        .zip(&coverage)
+        .map(|(kmer, coverage)| KmerFrequency {
+            kmer,
+            frequency: coverage.segments,
+        })
+        .collect();
+    let stats = get_kmer_stats(kmer_records, program_config.clone())?;
+    let mut evaluated = vec![Vec::new(), Vec::new()];
+    for ((mut stat, primer), coverage) in stats.into_iter().zip(primers).zip(coverage) {
+        log::info!(
+            "Primer {} binds {} segments of {}/{} sequences",
+            primer.name,
+            coverage.segments,
+            coverage.sequences.len(),
+            records.len()
+        );
+        stat.name = Some(primer.name.clone());
+        stat.sequences = coverage.sequences;
+        stat.self_binding_count = coverage.self_binding_count;
+        evaluated[primer.direction as usize].push(stat);
+    }
+    Ok(evaluated)
+}
+
+/**
+ * Find which segments are covered by the final primer pool. A segment is covered when
+ * both a forward and a reverse primer bind its search windows.
+ */
+fn get_segment_coverage(
+    segments: &[Segment],
+    candidate_primers: &[Vec<KmerStat>],
+) -> output::SegmentCoverage {
+    let primers: HashSet<(&str, Direction)> = candidate_primers
+        .iter()
+        .flatten()
+        .map(|p| (p.word.as_str(), p.direction))
+        .collect();
+    get_covered_segments(segments, &primers)
+}
+
+fn get_covered_segments(
+    segments: &[Segment],
+    primers: &HashSet<(&str, Direction)>,
+) -> output::SegmentCoverage {
+    let mut coverage = output::SegmentCoverage {
+        covered: Vec::new(),
+        uncovered: Vec::new(),
+    };
+    // canonical k-mers match a primer in either orientation
+    let canonical: HashSet<(String, Direction)> = primers
+        .iter()
+        .map(|&(word, direction)| (get_canonical_kmer(word), direction))
+        .collect();
+    for segment in segments.iter() {
+        let is_covered = segment.kmers.iter().all(|kmers| {
+            kmers.iter().any(|k| match &k.canonical {
+                Some(key) => canonical.contains(&(key.clone(), k.direction)),
+                None => primers.contains(&(k.word.as_str(), k.direction)),
+            })
+        });
+        if is_covered {
+            coverage.covered.push(segment.index);
+        } else {
+            coverage.uncovered.push(segment.index);
+        }
+    }
+    coverage
+}
+
+/**
+ * Run the segmentation and primer selection with every k-mer size, reporting the number of
+ * selected primers and the segment coverage of each. Coverage is of the selected candidates,
+ * before the Tm and secondary structure filters.
+ */
+fn sweep_kmer_sizes(
+    records: &[SequenceRecord],
+    options: &PartitioningOption,
+    kmer_sizes: &[usize],
+    excluded: &HashSet<String>,
+    config: &ProgramConfig,
+) -> Result<Vec<output::KmerSizeReport>, DesignError> {
+    kmer_sizes
+        .iter()
+        .map(|&kmer_size| {
+            let options = PartitioningOption {
+                kmer_size,
+                ..options.clone()
+            };
+            let mut manager = get_segment_manager(records, options)?;
+            exclude_kmers(&mut manager.segments, excluded);
+            let fwd = find_candidates_kmers(&manager, Direction::Forward, config.clone())
+                .unwrap_or_default();
+            let rev = find_candidates_kmers(&manager, Direction::Reverse, config.clone())
+                .unwrap_or_default();
+            let primers: HashSet<(&str, Direction)> = fwd
+                .iter()
+                .chain(&rev)
+                .map(|k| (k.kmer.word.as_str(), k.kmer.direction))
+                .collect();
+            let coverage = get_covered_segments(&manager.segments, &primers);
+            log::info!(
+                "k-mer size {}: primers fwd={}, rev={}, coverage {:.2}%",
+                kmer_size,
+                fwd.len(),
+                rev.len(),
+                coverage.percent()
+            );
+            Ok(output::KmerSizeReport {
+                kmer_size,
+                primers_fwd: fwd.len(),
+                primers_rev: rev.len(),
+                coverage,
+            })
+        })
+        .collect()
+}
+
+/**
+ * The k-mer size with the best coverage, ties go to the fewest primers, then the smallest size
+ */
+fn get_best_kmer_size(reports: &[output::KmerSizeReport]) -> Option<usize> {
+    reports
+        .iter()
+        .max_by(|a, b| {
+            a.coverage
+                .percent()
+                .total_cmp(&b.coverage.percent())
+                .then_with(|| (b.primers_fwd + b.primers_rev).cmp(&(a.primers_fwd + a.primers_rev)))
+                .then_with(|| b.kmer_size.cmp(&a.kmer_size))
+        })
+        .map(|r| r.kmer_size)
+}
+
+/**
+ * Parse a primer exclusion list, one sequence per line normalized like the FASTA input
+ * (uppercased, U read as T). Each sequence is excluded along with its reverse complement.
+ */
+fn parse_exclude_list(
+    content: &str,
+    complement_table: Option<&HashMap<char, char>>,
+) -> HashSet<String> {
+    content
+        .lines()
+        .map(|line| line.trim().to_uppercase().replace("U", "T"))
+        .filter(|line| !line.is_empty())
+        .flat_map(|word| [reverse_complement(&word, complement_table), word])
+        .collect()
+}
+
+/**
+ * Remove the excluded k-mers from the search windows of all segments, so they are never
+ * selected, returns the number of k-mers removed
+ */
+fn exclude_kmers(segments: &mut [Segment], excluded: &HashSet<String>) -> usize {
+    let mut removed = 0;
+    for segment in segments.iter_mut() {
+        for kmers in segment.kmers.iter_mut() {
+            let total = kmers.len();
+            kmers.retain(|k| !excluded.contains(&k.word));
+            removed += total - kmers.len();
+        }
+    }
+    removed
+}
+
+/**
+ * Start of the first partition each primer binds, keyed by word and direction
+ */
+fn get_primer_positions<'a>(segments: &'a [Segment]) -> HashMap<(&'a str, Direction), usize> {
+    let mut positions: HashMap<(&str, Direction), usize> = HashMap::new();
+    for segment in segments.iter() {
+        for kmer in segment.kmers.iter().flatten() {
+            positions
+                .entry((kmer.word.as_str(), kmer.direction))
+                .and_modify(|p| *p = (*p).min(segment.start))
+                .or_insert(segment.start);
+        }
+    }
+    positions
+}
+
+/**
+ * Sort the primers of a direction for output, ties are broken by the word so the order is
+ * stable across runs
+ */
+fn sort_primers(primers: &mut [KmerStat], sort_by: SortBy, segments: &[Segment]) {
+    let positions = match sort_by {
+        SortBy::Position => get_primer_positions(segments),
+        _ => HashMap::new(),
+    };
+    let position = |p: &KmerStat| {
+        positions
+            .get(&(p.word.as_str(), p.direction))
+            .copied()
+            .unwrap_or(usize::MAX)
+    };
+    primers.sort_by(|a, b| {
+        match sort_by {
+            SortBy::Frequency => b.frequency.cmp(&a.frequency),
+            SortBy::Tm => b.tm.total_cmp(&a.tm),
+            SortBy::Gc => b.gc_percent.total_cmp(&a.gc_percent),
+            SortBy::Position => position(a).cmp(&position(b)),
+        }
+        .then_with(|| a.word.cmp(&b.word))
+    });
+}
+
+/**
+ * Keep the first `top_n` of the sorted forward and reverse primers.
+ *
+ * Balanced directions get half of the primers each, a direction with fewer primers leaving
+ * the rest to the other. Otherwise the best primers of both directions in the `sort_by`
+ * order are kept, which are the first primers of each direction.
+ */
+fn take_top_primers(
+    mut candidate_primers: Vec<Vec<KmerStat>>,
+    top_n: usize,
+    balanced: bool,
+    sort_by: SortBy,
+    segments: &[Segment],
+) -> Vec<Vec<KmerStat>> {
+    let (total_fwd, total_rev) = (candidate_primers[0].len(), candidate_primers[1].len());
+    let (n_fwd, n_rev) = match balanced {
+        true => {
+            let n_rev = (top_n - top_n.div_ceil(2).min(total_fwd)).min(total_rev);
+            ((top_n - n_rev).min(total_fwd), n_rev)
+        }
+        false => {
+            let mut primers: Vec<KmerStat> = candidate_primers.iter().flatten().cloned().collect();
+            sort_primers(&mut primers, sort_by, segments);
+            let n_fwd = primers
+                .iter()
+                .take(top_n)
+                .filter(|p| p.direction == Direction::Forward)
+                .count();
+            (n_fwd, top_n.min(primers.len()) - n_fwd)
+        }
+    };
+    candidate_primers[0].truncate(n_fwd);
+    candidate_primers[1].truncate(n_rev);
+    candidate_primers
+}
+
+/**
+ * Cap the primers of each direction at `max_ratio` times the primers of the other direction,
+ * so a design isn't made of mostly one direction. The primers come in their selection order,
+ * the ones selected first, covering the most segments, are kept and a direction without
+ * primers counts as one. A ratio below 1 disables the cap.
+ */
+fn balance_directions<T>(
+    mut primers_fwd: Vec<T>,
+    mut primers_rev: Vec<T>,
+    max_ratio: f32,
+) -> (Vec<T>, Vec<T>) {
+    if max_ratio < 1.0 {
+        return (primers_fwd, primers_rev);
+    }
+    let cap = |other: usize| (other.max(1) as f32 * max_ratio).floor() as usize;
+    let (max_fwd, max_rev) = (cap(primers_rev.len()), cap(primers_fwd.len()));
+    for (direction, primers, max) in [
+        (Direction::Forward, &mut primers_fwd, max_fwd),
+        (Direction::Reverse, &mut primers_rev, max_rev),
+    ] {
+        if primers.len() > max {
+            log::info!(
+                "Capping {:?} primers at {} of {}",
+                direction,
+                max,
+                primers.len()
+            );
+            primers.truncate(max);
+        }
+    }
+    (primers_fwd, primers_rev)
+}
+
+/**
+ * Collapse forward and reverse primers that are reverse complements of each other. The
+ * higher frequency primer is kept with the frequencies of both, ties keep the forward one.
+ */
+fn dedupe_reverse_complements<'a>(
+    kmers_fwd: Vec<KmerFrequency<'a>>,
+    kmers_rev: Vec<KmerFrequency<'a>>,
+) -> (Vec<KmerFrequency<'a>>, Vec<KmerFrequency<'a>>) {
+    let mut kmers_fwd: Vec<Option<KmerFrequency>> = kmers_fwd.into_iter().map(Some).collect();
+    let mut kmers_rev: Vec<Option<KmerFrequency>> = kmers_rev.into_iter().map(Some).collect();
+    let fwd_index: HashMap<String, usize> = kmers_fwd
+        .iter()
+        .enumerate()
+        .filter_map(|(i, k)| k.as_ref().map(|k| (k.kmer.word.clone(), i)))
+        .collect();
+
+    for rev in kmers_rev.iter_mut() {
+        let Some(r) = rev else { continue };
+        let Some(&i) = fwd_index.get(&reverse_complement(&r.kmer.word, None)) else {
+            continue;
+        };
+        let Some(f) = kmers_fwd[i].as_mut() else {
+            continue;
+        };
+        log::debug!(
+            "Collapsing reverse complement primers, fwd: {}, rev: {}",
+            f.kmer.word,
+            r.kmer.word
+        );
+        let frequency = f.frequency + r.frequency;
+        if r.frequency > f.frequency {
+            r.frequency = frequency;
+            kmers_fwd[i] = None;
+        } else {
+            f.frequency = frequency;
+            *rev = None;
+        }
+    }
+
+    (
+        kmers_fwd.into_iter().flatten().collect(),
+        kmers_rev.into_iter().flatten().collect(),
+    )
+}
+
+/**
+ * Get the minimum frequency of a k-mer, `min_frequency` below 1 is a fraction of the input
+ * sequences, otherwise an absolute count
+ */
+fn get_min_frequency(min_frequency: f32, total_sequences: usize) -> usize {
+    if min_frequency < 1.0 {
+        (min_frequency * total_sequences as f32).ceil() as usize
+    } else {
+        min_frequency as usize
+    }
+}
+
+fn filter_frequency(kmers: Vec<KmerFrequency>, min_frequency: usize) -> Vec<KmerFrequency> {
+    kmers
+        .into_iter()
+        .filter(|k| k.frequency >= min_frequency)
+        .collect()
+}
+
+/**
+ * Get the amplicon length of a primer pair on the segment, counting ungapped bases from the
+ * 5' end of the forward primer to the 5' end of the reverse primer
+ */
+fn get_amplicon_length(segment: &Segment, forward: &str, reverse: &str) -> Option<usize> {
+    let partition = &segment.sequence.sequence[segment.start..segment.end];
+    let start = partition.find(forward)?;
+    let end = partition.rfind(&reverse_complement(reverse, None))? + reverse.len();
+    if end <= start {
+        return None;
+    }
+    Some(partition[start..end].chars().filter(|c| *c != '-').count())
+}
+
+/**
+ * Number of bases before the aligned position, skipping gaps
+ */
+fn get_ungapped_position(sequence: &str, position: usize) -> usize {
+    sequence[..position].chars().filter(|c| *c != '-').count()
+}
+
+/**
+ * Aligned start of the primer on the partition of the segment, `None` when the primer isn't
+ * one of the k-mers of the segment. Reverse primers are located by their reverse complement
+ * on the template.
+ */
+fn locate_primer(primer: &KmerStat, segment: &Segment) -> Option<usize> {
+    let kmers = &segment.kmers[primer.direction as usize];
+    if !kmers.iter().any(|k| k.word == primer.word) {
+        return None;
+    }
+    let partition = &segment.sequence.sequence[segment.start..segment.end];
+    let offset = match primer.direction {
+        Direction::Forward => partition.find(&primer.word),
+        Direction::Reverse => partition.rfind(&reverse_complement(&primer.word, None)),
+    };
+    if offset.is_none() {
+        log::trace!(
+            "Primer {} not found on segment {}",
+            primer.word,
+            segment.index
+        );
+    }
+    offset.map(|offset| segment.start + offset)
+}
+
+/**
+ * Flag the primers binding within `proximity` aligned positions of a masked base on any
+ * segment, a lowercase base with `soft_mask` or a position of the `mask` ranges, returning
+ * how many were flagged. The primers themselves never hold masked bases.
+ */
+fn flag_near_masked(
+    candidate_primers: &mut [Vec<KmerStat>],
+    segments: &[Segment],
+    soft_mask: bool,
+    mask: Option<&[(usize, usize)]>,
+    proximity: usize,
+) -> usize {
+    let is_masked = |sequence: &str, position: usize| {
+        (soft_mask && sequence.as_bytes()[position].is_ascii_lowercase())
+            || mask.is_some_and(|mask| {
+                mask.iter()
+                    .any(|(start, end)| (*start..*end).contains(&position))
+            })
+    };
+    let mut flagged = 0;
+    for primer in candidate_primers.iter_mut().flatten() {
+        primer.near_masked = segments.iter().any(|segment| {
+            let Some(start) = locate_primer(primer, segment) else {
+                return false;
+            };
+            let sequence = &segment.sequence.sequence;
+            let end = start + primer.word.len();
+            (start.saturating_sub(proximity)..(end + proximity).min(sequence.len()))
+                .any(|position| is_masked(sequence, position))
+        });
+        flagged += primer.near_masked as usize;
+    }
+    flagged
+}
+
+/**
+ * Get the interval of every primer on each segment it binds, in segment order with forward
+ * primers first. Intervals are ungapped positions on the sequence of the segment, reverse
+ * primers are located by their reverse complement on the template.
+ */
+fn get_tiling<'a>(
+    candidate_primers: &'a [Vec<KmerStat>],
+    segments: &'a [Segment<'a>],
+) -> Vec<TilingInterval<'a>> {
+    let mut intervals = Vec::new();
+    for segment in segments.iter() {
+        let sequence = &segment.sequence.sequence;
+        let mut segment_intervals = Vec::new();
+        for (idx, primer) in candidate_primers.iter().flat_map(|p| p.iter().enumerate()) {
+            let Some(start) = locate_primer(primer, segment) else {
+                continue;
+            };
+            segment_intervals.push(TilingInterval {
+                segment,
+                primer,
+                index: idx,
+                name: output::get_primer_name(primer, idx),
+                direction: primer.direction,
+                start: get_ungapped_position(sequence, start),
+                end: get_ungapped_position(sequence, start + primer.word.len()),
+            });
+        }
+        segment_intervals.sort_by_key(|i| (i.direction, i.start));
+        intervals.extend(segment_intervals);
+    }
+    intervals
+}
+
+/**
+ * Collapse near-identical primers binding the same partitions into one IUPAC consensus of
+ * at most `max_degeneracy` sequences, keeping the collapsed words in `constituents`.
+ *
+ * Primers are grouped in their order, so a group takes the place of its best primer. A
+ * primer joins a group of the same length sharing a partition when the consensus of the
+ * group stays within the degeneracy and stands for the primers of the group only, so every
+ * sequence of the consensus was scored. Degenerate primers are never collapsed.
+ */
+fn collapse_primers(
+    primers: Vec<KmerStat>,
+    segments: &[Segment],
+    max_degeneracy: usize,
+) -> Vec<KmerStat> {
+    let mut partitions: HashMap<(&str, Direction), BTreeSet<u16>> = HashMap::new();
+    let mut counts: HashMap<(&str, Direction), Vec<(usize, usize)>> = HashMap::new();
+    for segment in segments {
+        for kmer in segment.kmers.iter().flatten() {
+            partitions
+                .entry((kmer.word.as_str(), kmer.direction))
+                .or_default()
+                .insert(segment.partition_no);
+            counts
+                .entry((kmer.word.as_str(), kmer.direction))
+                .or_default()
+                .push((segment.index, kmer.count));
+        }
+    }
+    let empty = BTreeSet::new();
+    let primer_partitions: Vec<&BTreeSet<u16>> = primers
+        .iter()
+        .map(|p| {
+            partitions
+                .get(&(p.word.as_str(), p.direction))
+                .unwrap_or(&empty)
+        })
+        .collect();
+
+    let mut collapsed: Vec<KmerStat> = Vec::new();
+    let mut grouped = vec![false; primers.len()];
+    for (i, primer) in primers.iter().enumerate() {
+        if grouped[i] {
+            continue;
+        }
+        let mut group = vec![primer];
+        let mut group_partitions = primer_partitions[i].clone();
+        let mut consensus = primer.word.clone();
+        for (j, other) in primers.iter().enumerate().skip(i + 1) {
+            if grouped[j]
+                || primer.degeneracy > 1
+                || other.degeneracy > 1
+                || other.direction != primer.direction
+                || other.word.len() != primer.word.len()
+                || group_partitions.is_disjoint(primer_partitions[j])
+            {
+                continue;
+            }
+            let words: Vec<&str> = group
+                .iter()
+                .chain([&other])
+                .map(|p| p.word.as_str())
+                .collect();
+            // a consensus standing for other words than the primers was never scored
+            if let Some(word) = iupac::get_consensus(&words, max_degeneracy)
+                && iupac::get_degeneracy(&word) == words.len()
+            {
+                consensus = word;
+                group_partitions.extend(primer_partitions[j]);
+                group.push(other);
+                grouped[j] = true;
+            }
+        }
+        collapsed.push(match group.len() {
+            1 => primer.clone(),
+            _ => {
+                // every segment a primer of the group binds counts once, by its best count
+                let mut covered: HashMap<usize, usize> = HashMap::new();
+                for p in &group {
+                    for &(index, count) in counts
+                        .get(&(p.word.as_str(), p.direction))
+                        .into_iter()
+                        .flatten()
+                    {
+                        let entry = covered.entry(index).or_default();
+                        *entry = (*entry).max(count);
+                    }
+                }
+                merge_primers(consensus, &group, covered.values().sum())
+            }
+        });
+    }
+    collapsed
+}
+
+/**
+ * The consensus primer of a group with the frequency of the segments the group covers, the
+ * ranges of its primers and the flags of the worst of them
+ */
+fn merge_primers(consensus: String, group: &[&KmerStat], frequency: usize) -> KmerStat {
+    log::debug!(
+        "Collapsing primers {} into {}",
+        group.iter().map(|p| p.word.as_str()).join(", "),
+        consensus
+    );
+    let mut merged = group[0].clone();
+    merged.gc_percent = get_gc_percent(&consensus);
+    merged.degeneracy = iupac::get_degeneracy(&consensus);
+    merged.word = consensus;
+    merged.constituents = group.iter().map(|p| p.word.clone()).collect();
+    merged.frequency = frequency;
+    for primer in &group[1..] {
+        merged.gc_min = merged.gc_min.min(primer.gc_min);
+        merged.gc_max = merged.gc_max.max(primer.gc_max);
+        merged.tm = merged.tm.min(primer.tm);
+        merged.tm_max = merged.tm_max.max(primer.tm_max);
+        for sequence in &primer.sequences {
+            if !merged.sequences.contains(sequence) {
+                merged.sequences.push(sequence.clone());
+            }
+        }
+        merged.self_binding_count = merged.self_binding_count.max(primer.self_binding_count);
+        merged.tm_ok &= primer.tm_ok;
+        merged.self_any_th = merged.self_any_th.max(primer.self_any_th);
+        merged.self_end_th = merged.self_end_th.max(primer.self_end_th);
+        merged.hairpin_th = merged.hairpin_th.max(primer.hairpin_th);
+        merged.three_prime_dg = merged.three_prime_dg.min(primer.three_prime_dg);
+        merged.self_fold_dg = merged.self_fold_dg.min(primer.self_fold_dg);
+        merged.runs |= primer.runs;
+        merged.three_prime_run |= primer.three_prime_run;
+        merged.gc_clamp &= primer.gc_clamp;
+        merged.complexity_ok &= primer.complexity_ok;
+        merged.specificity_ok &= primer.specificity_ok;
+        merged.near_masked |= primer.near_masked;
+    }
+    merged
+}
+
+/**
+ * Drop the primers binding within `min_spacing` bases of a primer of the same direction kept
+ * before them, on any sequence both bind. Sites are the ungapped starts of the tiling, the
+ * primers are kept in their order and those not found on any segment are always kept.
+ */
+fn space_primers(
+    primers: Vec<KmerStat>,
+    segments: &[Segment],
+    min_spacing: usize,
+) -> Vec<KmerStat> {
+    if min_spacing == 0 {
+        return primers;
+    }
+    let mut sites: Vec<Vec<(&str, usize)>> = vec![Vec::new(); primers.len()];
+    for interval in get_tiling(std::slice::from_ref(&primers), segments) {
+        sites[interval.index].push((interval.segment.sequence.name.as_str(), interval.start));
+    }
+    let mut kept: Vec<usize> = Vec::new();
+    let mut keep = vec![true; primers.len()];
+    for (idx, primer_sites) in sites.iter().enumerate() {
+        let too_close = kept.iter().find(|&&other| {
+            primer_sites.iter().any(|(name, start)| {
+                sites[other].iter().any(|(other_name, other_start)| {
+                    name == other_name && start.abs_diff(*other_start) < min_spacing
+                })
+            })
+        });
+        match too_close {
+            Some(&other) => {
+                log::debug!(
+                    "Primer {} binds within {} bases of {}, dropped",
+                    primers[idx].word,
+                    min_spacing,
+                    primers[other].word
+                );
+                keep[idx] = false;
+            }
+            None => kept.push(idx),
+        }
+    }
+    primers
+        .into_iter()
+        .zip(keep)
+        .filter_map(|(primer, keep)| keep.then_some(primer))
+        .collect()
+}
+
+/**
+ * Name the primers by the first place they bind in the tiling, `<sequence>_<start>_F` with
+ * the ungapped 0-based start, e.g. `MN908947_1024_R`. Primers not found on any segment keep
+ * the index names, and a name taken by another primer gets a `_<n>` suffix.
+ */
+fn name_by_position(candidate_primers: &mut [Vec<KmerStat>], segments: &[Segment]) {
+    let mut positions: HashMap<String, String> = HashMap::new();
+    for interval in get_tiling(candidate_primers, segments) {
+        positions.entry(interval.name).or_insert_with(|| {
+            format!(
+                "{}_{}_{}",
+                interval.segment.sequence.name,
+                interval.start,
+                interval.direction.label()
+            )
+        });
+    }
+    let mut taken: HashSet<String> = HashSet::new();
+    for primers in candidate_primers.iter_mut() {
+        for (idx, primer) in primers.iter_mut().enumerate() {
+            let Some(name) = positions.get(&output::get_primer_name(primer, idx)) else {
+                log::debug!(
+                    "Primer {} not found on any segment, named by index",
+                    primer.word
+                );
+                continue;
+            };
+            let mut unique = name.clone();
+            let mut n = 1;
+            while !taken.insert(unique.clone()) {
+                n += 1;
+                unique = format!("{}_{}", name, n);
+            }
+            primer.name = Some(unique);
+        }
+    }
+}
+
+/**
+ * Dimer ΔG (kcal/mol) of every pair of primers, from the nearest-neighbor stacks of their
+ * most stable ungapped duplex. The matrix is symmetric and its diagonal is the self-dimer of
+ * each primer, the more negative the stronger the interaction.
+ */
+fn dimer_matrix(primers: &[KmerStat]) -> Vec<Vec<f32>> {
+    let mut matrix = vec![vec![0.0; primers.len()]; primers.len()];
+    for (i, a) in primers.iter().enumerate() {
+        for (j, b) in primers.iter().enumerate().skip(i) {
+            let dg = dimer_delta_g(&a.word, &b.word);
+            matrix[i][j] = dg;
+            matrix[j][i] = dg;
+        }
+    }
+    matrix
+}
+
+/**
+ * Pair forward primers of the start search window with reverse primers of the end search
+ * window of every segment, keeping pairs whose amplicon length is within `min_len..=max_len`
+ * and whose Tm differ by at most `max_delta_tm`
+ */
+fn pair_primers<'a>(
+    stats: &'a [KmerStat],
+    segments: &'a [Segment<'a>],
+    min_len: usize,
+    max_len: usize,
+    max_delta_tm: f32,
+) -> Vec<PrimerPair<'a>> {
+    let mut pairs = Vec::new();
+    for segment in segments.iter() {
+        let [fwd_kmers, rev_kmers] = &segment.kmers;
+        let binds = |stat: &KmerStat, kmers: &[KmerRecord]| {
+            kmers
+                .iter()
+                .any(|k| k.word == stat.word && k.direction == stat.direction)
+        };
+        let forwards = stats
+            .iter()
+            .filter(|s| s.direction == Direction::Forward && binds(s, fwd_kmers));
+        for forward in forwards {
+            let reverses = stats
+                .iter()
+                .filter(|s| s.direction == Direction::Reverse && binds(s, rev_kmers));
+            for reverse in reverses {
+                let Some(amplicon_length) =
+                    get_amplicon_length(segment, &forward.word, &reverse.word)
+                else {
+                    continue;
+                };
+                if amplicon_length < min_len || amplicon_length > max_len {
+                    log::trace!(
+                        "Rejecting pair {}/{}, amplicon length {} out of range",
+                        forward.word,
+                        reverse.word,
+                        amplicon_length
+                    );
+                    continue;
+                }
+                let delta_tm = (forward.tm - reverse.tm).abs();
+                if delta_tm > max_delta_tm {
+                    log::trace!(
+                        "Rejecting pair {}/{}, delta Tm {:.2} too large",
+                        forward.word,
+                        reverse.word,
+                        delta_tm
+                    );
+                    continue;
+                }
+                pairs.push(PrimerPair {
+                    forward,
+                    reverse,
+                    segment,
+                    amplicon_length,
+                    forward_tm: forward.tm,
+                    reverse_tm: reverse.tm,
+                    delta_tm,
+                });
+            }
+        }
+    }
+    pairs
+}
+
+fn get_kmer_stats(
+    kmer_records: Vec<KmerFrequency>,
+    program_config: ProgramConfig,
+) -> Result<Vec<KmerStat>, DesignError> {
+    let primer_config = &program_config.primer_config;
+    // first, finding the threshold for Tm
+    let primers: Vec<String> = kmer_records.iter().map(|k| k.kmer.word.clone()).collect();
+    // degenerate primers are checked by all the sequences they stand for
+    let expansions: Vec<Vec<String>> = primers.iter().map(|p| iupac::expand(p)).collect();
+    let expanded: Vec<String> = expansions.iter().flatten().unique().cloned().collect();
+
+    let params = CheckPrimerParams {
+        min_tm: primer_config.min_tm,
+        max_tm: primer_config.max_tm,
+        primer3_path: program_config.primer3_path.clone(),
+    };
+    let primer_info_list = check_primers(&expanded, params)?;
+    let mut primer_info_map = HashMap::new();
+    for info in &primer_info_list {
+        primer_info_map.entry(info.id).or_insert(info);
+    }
+    let empty_info = PrimerInfo::new();
+    let primer_infos: Vec<Vec<&PrimerInfo>> = expansions
+        .iter()
+        .map(|words| {
+            words
+                .iter()
+                .map(|word| match primer_info_map.get(word.as_str()) {
+                    Some(info) => *info,
+                    _ => &empty_info,
+                })
+                .collect()
+        })
+        .collect();
+    let off_target = match &program_config.specificity_db {
+        Some(db) => {
+            let params = SpecificityParams {
+                blastn_path: program_config.blastn_path.clone(),
+                db: db.clone(),
+                max_mismatch: program_config.max_off_target_mismatch,
+            };
+            find_off_target_primers(&primers, &params)?
+        }
+        None => HashSet::new(),
+    };
+    let stats = build_kmer_stats(
+        &kmer_records,
+        &expansions,
+        primer_infos,
+        &off_target,
+        &program_config,
+    );
+
+    let below_annealing = stats
+        .iter()
+        .filter(|s| {
+            !tm_above_annealing(
+                s.tm,
+                primer_config.annealing_temp,
+                primer_config.annealing_tm_margin,
+            )
+        })
+        .count();
+    log::info!(
+        "{} primers with Tm less than {} above the annealing temperature ({})",
+        below_annealing,
+        primer_config.annealing_tm_margin,
+        primer_config.annealing_temp
+    );
+    Ok(stats)
+}
+
+/**
+ * Calculate the stats of every primer from the primer3 results of its sequences (all the
+ * sequences of a degenerate primer) and the primers with off-target hits
+ */
+fn build_kmer_stats(
+    kmer_records: &[KmerFrequency],
+    expansions: &[Vec<String>],
+    primer_infos: Vec<Vec<&PrimerInfo>>,
+    off_target: &HashSet<String>,
+    program_config: &ProgramConfig,
+) -> Vec<KmerStat> {
+    let primer_config = &program_config.primer_config;
+    let tm_ranges = get_tm_values(expansions, &primer_infos, program_config);
+    let tm_values: Vec<f32> = tm_ranges.iter().map(|(min, _)| *min).collect();
+    let (mean, std) = get_tm_stat(&tm_values);
+    let margin = get_tm_margin(
+        tm_values.len(),
+        std,
+        program_config.tm_stddev,
+        program_config.tm_fallback_margin,
+    );
+
+    kmer_records
+        .iter()
+        .zip(primer_infos)
+        .zip(expansions)
+        .zip(tm_ranges)
+        .map(
+            |(((kmer_freq, primer_info), words), (tm, tm_max))| KmerStat {
+                word: kmer_freq.kmer.word.clone(),
+                direction: kmer_freq.kmer.direction,
+                name: None,
+                frequency: kmer_freq.frequency,
+                mean,
+                std,
+                gc_percent: get_gc_percent(&kmer_freq.kmer.word),
+                gc_min: words
+                    .iter()
+                    .map(|w| get_gc_percent(w))
+                    .fold(f32::INFINITY, f32::min),
+                gc_max: words
+                    .iter()
+                    .map(|w| get_gc_percent(w))
+                    .fold(f32::NEG_INFINITY, f32::max),
+                tm,
+                tm_max,
+                degeneracy: iupac::get_degeneracy(&kmer_freq.kmer.word),
+                sequences: Vec::new(),
+                self_binding_count: 0,
+                constituents: Vec::new(),
+                tm_ok: is_tm_ok(tm, mean, margin, program_config)
+                    && is_tm_ok(tm_max, mean, margin, program_config),
+                self_any_th: get_max_th(&primer_info, |info| info.self_any_th),
+                self_end_th: get_max_th(&primer_info, |info| info.self_end_th),
+                hairpin_th: get_max_th(&primer_info, |info| info.hairpin_th),
+                three_prime_dg: three_prime_delta_g(&kmer_freq.kmer.word),
+                self_fold_dg: self_fold_delta_g(
+                    &kmer_freq.kmer.word,
+                    primer_config.mv_conc as f64,
+                    primer_config.dv_conc as f64,
+                ),
+                runs: has_repeats(&kmer_freq.kmer.word, primer_config),
+                three_prime_run: has_3prime_run(
+                    &kmer_freq.kmer.word,
+                    primer_config.max_three_prime_run,
+                ),
+                gc_clamp: has_gc_clamp(
+                    &kmer_freq.kmer.word,
+                    primer_config.gc_clamp_length,
+                    primer_config.min_gc_clamp,
+                ),
+                complexity_ok: sequence_complexity(&kmer_freq.kmer.word)
+                    >= primer_config.min_complexity,
+                specificity_ok: !off_target.contains(&kmer_freq.kmer.word),
+                near_masked: false,
+            },
+        )
+        .collect()
+}
+
+/**
+ * Get the Tm of a primer with the configured method
+ */
+fn get_primer_tm(word: &str, primer_info: &PrimerInfo, program_config: &ProgramConfig) -> f32 {
+    let primer_config = &program_config.primer_config;
+    match program_config.tm_method {
+        TmMethod::Nn => get_tm_nn(
+            word,
+            primer_config.mv_conc as f64,
+            primer_config.dv_conc as f64,
+            primer_config.dntp_conc as f64,
+            primer_config.dna_conc as f64,
+        ),
+        TmMethod::Primer3 => primer_info.tm,
+        TmMethod::Basic => get_tm(
+            word,
+            primer_config.mv_conc as f64,
+            primer_config.dv_conc as f64,
+            primer_config.dntp_conc as f64,
+        ),
+        TmMethod::Wallace => get_tm_wallace(word),
+        TmMethod::Auto => get_tm_auto(
+            word,
+            primer_config.mv_conc as f64,
+            primer_config.dv_conc as f64,
+            primer_config.dntp_conc as f64,
+        ),
+    }
+}
+
+/**
+ * Get the lowest and highest Tm of the sequences of every primer, calculated once per
+ * distinct word. Both are the Tm of the primer unless it is degenerate.
+ */
+fn get_tm_values(
+    expansions: &[Vec<String>],
+    primer_infos: &[Vec<&PrimerInfo>],
+    program_config: &ProgramConfig,
+) -> Vec<(f32, f32)> {
+    let mut tm_cache: HashMap<&str, f32> = HashMap::new();
+    expansions
+        .iter()
+        .zip(primer_infos)
+        .map(|(words, infos)| {
+            words
+                .iter()
+                .zip(infos)
+                .map(|(word, primer_info)| {
+                    *tm_cache
+                        .entry(word.as_str())
+                        .or_insert_with(|| get_primer_tm(word, primer_info, program_config))
+                })
+                .fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), tm| {
+                    (min.min(tm), max.max(tm))
+                })
+        })
+        .collect()
+}
+
+/**
+ * Get the highest secondary structure Tm over the sequences of a (degenerate) primer
+ */
+fn get_max_th(primer_infos: &[&PrimerInfo], th: fn(&PrimerInfo) -> f32) -> f32 {
+    primer_infos
+        .iter()
+        .map(|info| th(info))
+        .fold(f32::NEG_INFINITY, f32::max)
+}
+
+/**
+ * Get the threshold for Tm
+ *
+ * Calculated by find (2*sd(Tm)) + mean(Tm) of the primers
+ */
+fn get_tm_stat(tm_values: &[f32]) -> (f32, f32) {
+    let mean = tm_values.iter().sum::<f32>() / tm_values.len() as f32;
+    let std = standard_deviation(tm_values);
+    (mean, std.standard_deviation)
+}
+
+/**
+ * Mean and standard deviation of the Tm of the final pool, `None` for an empty pool. A
+ * spread above `max_std` is not ok and logged as a warning, the primers are unlikely to
+ * multiplex at a single annealing temperature.
+ */
+fn check_pool_tm(candidate_primers: &[Vec<KmerStat>], max_std: f32) -> Option<output::PoolTm> {
+    let tm_values: Vec<f32> = candidate_primers.iter().flatten().map(|p| p.tm).collect();
+    if tm_values.is_empty() {
+        return None;
+    }
+    let (mean, std) = get_tm_stat(&tm_values);
+    let std = match std.is_nan() {
+        true => 0.0,
+        false => std,
+    };
+    log::info!(
+        "Tm of the {} final primers, mean: {:.2}, standard deviation: {:.2}",
+        tm_values.len(),
+        mean,
+        std
+    );
+    let ok = std <= max_std;
+    if !ok {
+        log::warn!(
+            "Tm standard deviation of the pool ({:.2}) exceeds {:.2}, the primers may not multiplex at a single annealing temperature",
+            std,
+            max_std
+        );
+    }
+    Some(output::PoolTm { mean, std, ok })
+}
+
+/**
+ * Get the allowed distance of Tm from the mean
+ *
+ * `diff` standard deviations, or the absolute `fallback` margin when there are fewer than two
+ * primers and the standard deviation is meaningless.
+ */
+fn get_tm_margin(n: usize, std: f32, diff: f32, fallback: f32) -> f32 {
+    if n < 2 || std.is_nan() {
+        return fallback;
+    }
+    diff * std
+}
+
+fn tm_in_threshold(tm: f32, mean: f32, margin: f32) -> bool {
+    (tm - mean).abs() <= margin
+}
+
+fn tm_in_bounds(tm: f32, min_tm: f32, max_tm: f32) -> bool {
+    tm > min_tm && tm < max_tm
+}
+
+/**
+ * Primers only bind reliably when their Tm is at least `margin` above the annealing temperature
+ */
+fn tm_above_annealing(tm: f32, annealing_temp: f32, margin: f32) -> bool {
+    tm - annealing_temp >= margin
+}
+
+/**
+ * Check the Tm against the absolute `min_tm`/`max_tm` bounds, the margin above the annealing
+ * temperature and, unless disabled, the allowed distance from the mean Tm of all primers
+ */
+fn is_tm_ok(tm: f32, mean: f32, margin: f32, program_config: &ProgramConfig) -> bool {
+    let primer_config = &program_config.primer_config;
+    tm_in_bounds(tm, primer_config.min_tm, primer_config.max_tm)
+        && tm_above_annealing(
+            tm,
+            primer_config.annealing_temp,
+            primer_config.annealing_tm_margin,
+        )
+        && (program_config.disable_tm_stddev || tm_in_threshold(tm, mean, margin))
+}
+
+/**
+ * Check if the kmer has a repeat unit of `unit_len` bases repeated consecutively more than
+ * `max_repeats` times, `max_repeats` of 0 disables the check
+ *
+ * Repeats are the number of consecutive identical units, counted from any offset in the
+ * kmer, so the first occurrence of the unit is already one repeat. For example, with
+ * `unit_len` 2 and `max_repeats` 4, ATATATATAT is exactly five AT units and returns `true`,
+ * while ATATATATGG (four units) returns `false`.
+ */
+fn has_low_complexity_motif(kmer: &str, unit_len: usize, max_repeats: usize) -> bool {
+    if unit_len == 0 || max_repeats == 0 {
+        return false;
+    }
+    let bases = kmer.as_bytes();
+    for start in 0..bases.len() {
+        let unit = &bases[start..(start + unit_len).min(bases.len())];
+        if unit.len() < unit_len {
+            break;
+        }
+        let repeats = bases[start..]
+            .chunks_exact(unit_len)
+            .take_while(|chunk| *chunk == unit)
+            .count();
+        if repeats > max_repeats {
+            return true;
+        }
+    }
+    false
+}
+
+/**
+ * Check the kmer for homopolymer, di- and trinucleotide repeats above the configured maximum
+ */
+fn has_repeats(kmer: &str, primer_config: &PrimerConfig) -> bool {
+    has_low_complexity_motif(kmer, 1, primer_config.max_mono_repeats)
+        || has_low_complexity_motif(kmer, 2, primer_config.max_di_repeats)
+        || has_low_complexity_motif(kmer, 3, primer_config.max_tri_repeats)
+}
+
+/**
+ * Whether the primer ends in a homopolymer run longer than `max_run` bases, 0 disables it
+ *
+ * Runs too short for `has_repeats` still misprime when they are the 3' terminal bases.
+ * Reverse primers are stored as the oligo, so the 3' end is the end of the word for both
+ * directions.
+ */
+fn has_3prime_run(kmer: &str, max_run: usize) -> bool {
+    let Some(last) = kmer.chars().last() else {
+        return false;
+    };
+    max_run > 0 && kmer.chars().rev().take_while(|c| *c == last).count() > max_run
+}
+
+/**
+ * Check if the 3' end of the kmer has at least `min_gc` G or C in the last `last_n` bases
+ *
+ * Reverse primers are stored as the reverse complement of the template, so the 3' end is
+ * the end of the word for both directions.
+ */
+fn has_gc_clamp(kmer: &str, last_n: usize, min_gc: usize) -> bool {
+    let start = kmer.len().saturating_sub(last_n);
+    kmer[start..]
+        .chars()
+        .filter(|c| matches!(c, 'G' | 'C'))
+        .count()
+        >= min_gc
+}
+
+/**
+ * Calculate the linguistic complexity of the kmer
+ *
+ * Number of distinct substrings of every length, over the maximum possible number of
+ * substrings for a kmer of this length. Repeats like ATGATGATGATGA score low, while
+ * sequences without any repeated motif score close to 1.
+ */
+fn sequence_complexity(kmer: &str) -> f32 {
+    let n = kmer.len();
+    let mut observed = 0;
+    let mut possible = 0;
+    for size in 1..=n {
+        observed += (0..=n - size)
+            .map(|i| &kmer[i..i + size])
+            .collect::<HashSet<&str>>()
+            .len();
+        possible += 4usize.saturating_pow(size as u32).min(n - size + 1);
+    }
+    if possible == 0 {
+        return 0.0;
+    }
+    observed as f32 / possible as f32
+}
+
+/**
+ * Filters of `filter_kmers` in the order they are checked, a rejected primer is counted by
+ * the first filter it fails
+ */
+const FILTERS: [&str; 13] = [
+    "self_any",
+    "self_end",
+    "hairpin",
+    "three_prime_dg",
+    "self_fold_dg",
+    "tm",
+    "runs",
+    "three_prime_run",
+    "gc_low",
+    "gc_high",
+    "gc_clamp",
+    "complexity",
+    "specificity",
+];
+
+/**
+ * Get the first filter the primer fails, `None` when it passes all of them
+ */
+fn get_failed_filter(kmer_stat: &KmerStat, program_config: &ProgramConfig) -> Option<&'static str> {
+    get_failed_filters(kmer_stat, program_config)
+        .first()
+        .copied()
+}
+
+/**
+ * Get every filter the primer fails, in the order of `FILTERS`
+ */
+fn get_failed_filters(kmer_stat: &KmerStat, program_config: &ProgramConfig) -> Vec<&'static str> {
+    let primer_config = &program_config.primer_config;
+    let pass_self_any = !program_config.check_hairpin
+        || (kmer_stat.self_any_th < primer_config.max_self_dimer_any_tm);
+    let pass_self_end = !program_config.check_hairpin
+        || (kmer_stat.self_end_th < primer_config.max_self_dimer_end_tm);
+    let pass_hairpin =
+        !program_config.check_hairpin || (kmer_stat.hairpin_th < primer_config.max_hairpin_tm);
+    let pass_three_prime_dg = kmer_stat.three_prime_dg >= primer_config.min_three_prime_dg;
+    let pass_self_fold_dg = kmer_stat.self_fold_dg >= primer_config.min_self_fold_dg;
+    // every sequence of a degenerate primer must be within the GC range
+    let pass_gc_low = kmer_stat.gc_min >= primer_config.min_gc;
+    let pass_gc_high = kmer_stat.gc_max <= primer_config.max_gc;
+
+    let passes = [
+        pass_self_any,
+        pass_self_end,
+        pass_hairpin,
+        pass_three_prime_dg,
+        pass_self_fold_dg,
+        kmer_stat.tm_ok,
+        !kmer_stat.runs,
+        !kmer_stat.three_prime_run,
+        pass_gc_low,
+        pass_gc_high,
+        kmer_stat.gc_clamp,
+        kmer_stat.complexity_ok,
+        kmer_stat.specificity_ok,
+    ];
+    FILTERS
+        .into_iter()
+        .zip(passes)
+        .filter(|(_, pass)| !pass)
+        .map(|(filter, _)| filter)
+        .collect()
+}
+
+fn filter_kmers(stats: Vec<KmerStat>, program_config: ProgramConfig) -> Vec<KmerStat> {
+    stats
+        .into_iter()
+        .filter(|kmer_stat| get_failed_filter(kmer_stat, &program_config).is_none())
+        .collect()
+}
+
+/**
+ * Summarize the Tm threshold and how many primers of a direction each filter removes
+ */
+fn get_filter_summary(
+    direction: Direction,
+    stats: &[KmerStat],
+    program_config: &ProgramConfig,
+) -> output::FilterSummary {
+    let primer_config = &program_config.primer_config;
+    let (tm_mean, tm_std) = stats.first().map(|s| (s.mean, s.std)).unwrap_or_default();
+    let mut removed: Vec<(&'static str, usize)> = FILTERS.iter().map(|f| (*f, 0)).collect();
+    let mut passed = 0;
+    for kmer_stat in stats {
+        match get_failed_filter(kmer_stat, program_config) {
+            Some(filter) => {
+                if let Some(entry) = removed.iter_mut().find(|(f, _)| *f == filter) {
+                    entry.1 += 1;
+                }
+            }
+            None => passed += 1,
+        }
+    }
+    output::FilterSummary {
+        direction,
+        candidates: stats.len(),
+        tm_mean,
+        tm_margin: get_tm_margin(
+            stats.len(),
+            tm_std,
+            program_config.tm_stddev,
+            program_config.tm_fallback_margin,
+        ),
+        min_tm: primer_config.min_tm,
+        max_tm: primer_config.max_tm,
+        gc_clamp_length: primer_config.gc_clamp_length,
+        min_gc_clamp: primer_config.min_gc_clamp,
+        removed,
+        passed,
+    }
+}
+
+/**
+ * Primer constraints from the command line arguments
+ */
+fn get_primer_config(args: &Args) -> PrimerConfig {
+    PrimerConfig {
+        kmer_size: args.kmer_size,
+        min_tm: args.min_tm,
+        max_tm: args.max_tm,
+        max_self_dimer_any_tm: args.max_self_dimer_any_tm,
+        max_self_dimer_end_tm: args.max_self_dimer_end_tm,
+        max_hairpin_tm: args.max_hairpin_tm,
+        gc_clamp_length: args.gc_clamp_length,
+        min_gc_clamp: args.min_gc_clamp,
+        min_gc: args.min_gc,
+        max_gc: args.max_gc,
+        min_complexity: args.min_complexity,
+        max_mono_repeats: args.max_mono_repeats,
+        max_di_repeats: args.max_di_repeats,
+        max_tri_repeats: args.max_tri_repeats,
+        max_three_prime_run: args.max_three_prime_run,
+        min_three_prime_dg: args.min_three_prime_dg,
+        min_self_fold_dg: args.min_self_fold_dg,
+        annealing_temp: args.annealing_temp,
+        annealing_tm_margin: args.annealing_tm_margin,
+        mv_conc: args.mv_conc,
+        dv_conc: args.dv_conc,
+        dntp_conc: args.dntp_conc,
+        dna_conc: args.dna_conc,
+    }
+}
+
+/**
+ * Program settings from the command line arguments and the resolved executable paths
+ */
+fn get_program_config(
+    args: &Args,
+    ntthal_path: String,
+    primer3_path: String,
+    blastn_path: String,
+) -> ProgramConfig {
+    ProgramConfig {
+        ntthal_path,
+        primer3_path,
+        blastn_path,
+        specificity_db: args.specificity_db.clone(),
+        max_off_target_mismatch: args.max_off_target_mismatch,
+
+        max_iterations: args.max_iterations,
+        max_mismatch_segments: args.max_mismatch_segments,
+        target_coverage: args.target_coverage,
+        max_mismatch: args.max_mismatch,
+        min_frequency: args.min_frequency,
+        max_direction_ratio: args.max_direction_ratio,
+        strategy: args.strategy,
+
+        keep_all: args.keep_all.as_str() == "true",
+        check_cross_dimers: args.check_cross_dimers.as_str() == "true",
+        check_self_dimers: args.check_self_dimers.as_str() == "true",
+        check_hairpin: args.check_hairpin.as_str() == "true",
+        tm_method: args.tm_method,
+        tm_stddev: args.tm_stddev,
+        tm_fallback_margin: args.tm_fallback_margin,
+        disable_tm_stddev: args.disable_tm_stddev.as_str() == "true",
+        do_align: args.do_align.as_str() == "true",
+        soft_mask: args.soft_mask.as_str() == "true",
+        duplicate_names: args.duplicate_names,
+        threads: args.threads,
+        align_timeout: args.align_timeout,
+        align_retry: args.align_retry == "true",
+        progress: args.progress.as_str() == "true",
+        dedupe_rev_comp: args.dedupe_rev_comp.as_str() == "true",
+        seed: args.seed,
+        complement_table: None,
+        deterministic: args.deterministic.as_str() == "true",
+
+        primer_config: get_primer_config(args),
+    }
+}
+
+/**
+ * Segmentation settings from the command line arguments
+ */
+fn get_partitioning_option(args: &Args, progress: bool) -> PartitioningOption {
+    let (segment_size, overlap_size) = match args.amplicon_size {
+        Some(amplicon_size) => {
+            let segment_size = window_from_amplicon(amplicon_size, args.search_windows_size);
+            log::info!(
+                "Window size {} and overlap size {} for {}bp amplicons",
+                segment_size,
+                segment_size / 2,
+                amplicon_size
+            );
+            (segment_size, segment_size / 2)
+        }
+        None => (args.window_size, args.overlap_size),
+    };
+    PartitioningOption {
+        segment_size,
+        overlap_size,
+        window_size: args.search_windows_size,
+        kmer_size: args.kmer_size,
+        count_occurrences: args.count_occurrences.as_str() == "true",
+        max_degeneracy: args.max_degeneracy,
+        max_n: args.max_n,
+        canonical: args.canonical.as_str() == "true",
+        conservation: None,
+        weights: None,
+        mask: None,
+        region: args.region,
+        progress,
+    }
+}
+
+/**
+ * Primers of both directions passing the criteria, with what the run reports about them
+ */
+struct Selection {
+    forward: Vec<KmerStat>,
+    reverse: Vec<KmerStat>,
+    // primers of each direction left after every stage
+    stages: Vec<(&'static str, usize, usize)>,
+    traces: [Vec<IterationReport>; 2],
+    // primers removed by each criterion, unless all primers are kept
+    filter_summaries: Option<[output::FilterSummary; 2]>,
+    // primers removed by the criteria, with all the criteria they fail
+    rejections: Vec<output::Rejection>,
+}
+
+/**
+ * Select the primers of the segments and filter them by the criteria (Tm, repeats, GC clamp,
+ * complexity and specificity)
+ */
+fn select_primers(
+    segment_manager: &SegmentManager,
+    total_records: usize,
+    program_config: &ProgramConfig,
+) -> Result<Selection, DesignError> {
+    log::info!("Calculating frequencies of k-mer for all segments...");
+    log::debug!("Total segments: {}", segment_manager.segments.len());
+    let (candidate_kmers_fwd, trace_fwd) =
+        find_candidates_kmers_traced(segment_manager, Direction::Forward, program_config.clone());
+    let (candidate_kmers_rev, trace_rev) =
+        find_candidates_kmers_traced(segment_manager, Direction::Reverse, program_config.clone());
+    let candidate_kmers_fwd = candidate_kmers_fwd.unwrap_or_default();
+    let candidate_kmers_rev = candidate_kmers_rev.unwrap_or_default();
+    log::info!(
+        "Done calculating, Total candidate k-mers: fwd: {}, rev: {}",
+        candidate_kmers_fwd.len(),
+        candidate_kmers_rev.len()
+    );
+    let mut stages = vec![(
+        "candidate_kmers",
+        candidate_kmers_fwd.len(),
+        candidate_kmers_rev.len(),
+    )];
+
+    let (candidate_kmers_fwd, candidate_kmers_rev) = match program_config.dedupe_rev_comp {
+        true => {
+            let total = candidate_kmers_fwd.len() + candidate_kmers_rev.len();
+            let (fwd, rev) = dedupe_reverse_complements(candidate_kmers_fwd, candidate_kmers_rev);
+            log::info!(
+                "Collapsed {} reverse complement primers",
+                total - fwd.len() - rev.len()
+            );
+            (fwd, rev)
+        }
+        false => (candidate_kmers_fwd, candidate_kmers_rev),
+    };
+    stages.push((
+        "rev_comp_dedupe",
+        candidate_kmers_fwd.len(),
+        candidate_kmers_rev.len(),
+    ));
+
+    // 4. Filtering out unmatched criteria
+    let (candidate_kmers_fwd, candidate_kmers_rev) = match program_config.keep_all {
+        true => (candidate_kmers_fwd, candidate_kmers_rev),
+        false => {
+            let min_frequency = get_min_frequency(program_config.min_frequency, total_records);
+            let total = candidate_kmers_fwd.len() + candidate_kmers_rev.len();
+            let fwd = filter_frequency(candidate_kmers_fwd, min_frequency);
+            let rev = filter_frequency(candidate_kmers_rev, min_frequency);
+            log::info!(
+                "Removed {} k-mers with frequency below {}",
+                total - fwd.len() - rev.len(),
+                min_frequency
+            );
+            (fwd, rev)
+        }
+    };
+    stages.push((
+        "frequency",
+        candidate_kmers_fwd.len(),
+        candidate_kmers_rev.len(),
+    ));
+    log::info!("Filtering out unmatched criteria (Tm and >5nt repeats, runs...)");
+    let kmer_stats_fwd = get_kmer_stats(candidate_kmers_fwd, program_config.clone())?;
+    let kmer_stats_rev = get_kmer_stats(candidate_kmers_rev, program_config.clone())?;
+    let filter_summaries = match program_config.keep_all {
+        true => None,
+        false => Some([
+            get_filter_summary(Direction::Forward, &kmer_stats_fwd, program_config),
+            get_filter_summary(Direction::Reverse, &kmer_stats_rev, program_config),
+        ]),
+    };
+    let rejections: Vec<output::Rejection> = match program_config.keep_all {
+        true => Vec::new(),
+        false => kmer_stats_fwd
+            .iter()
+            .chain(&kmer_stats_rev)
+            .filter_map(|stat| {
+                let reasons = get_failed_filters(stat, program_config);
+                (!reasons.is_empty()).then(|| output::Rejection::new(stat, reasons))
+            })
+            .collect(),
+    };
+    let mut candidate_primers_fwd: Vec<KmerStat> = match program_config.keep_all {
+        true => kmer_stats_fwd,
+        false => filter_kmers(kmer_stats_fwd, program_config.clone()),
+    };
+    let mut candidate_primers_rev: Vec<KmerStat> = match program_config.keep_all {
+        true => kmer_stats_rev,
+        false => filter_kmers(kmer_stats_rev, program_config.clone()),
+    };
+    if program_config.keep_all {
+        log::info!(".... SKIPPED.");
+    } else {
+        log::info!(".... DONE.");
+    }
+    let site_counts = get_site_counts(
+        &segment_manager.segments,
+        program_config.primer_config.kmer_size,
+    );
+    for primer in candidate_primers_fwd
+        .iter_mut()
+        .chain(candidate_primers_rev.iter_mut())
+    {
+        primer.sequences = get_supporting_sequences(
+            &segment_manager.segments,
+            &primer.word,
+            primer.direction,
+            program_config.max_mismatch,
+        );
+        primer.self_binding_count = get_self_binding_count(
+            &segment_manager.segments,
+            &site_counts,
+            &primer.word,
+            primer.direction,
+            program_config.max_mismatch,
+            program_config.complement_table.as_ref(),
+        );
+        // more spurious sites than sequences, on average every sequence has one
+        if primer.self_binding_count > 0 && primer.self_binding_count >= total_records {
+            log::warn!(
+                "Primer {} binds {} sites outside its search windows",
+                primer.word,
+                primer.self_binding_count
+            );
+        }
+    }
+    stages.push((
+        "criteria",
+        candidate_primers_fwd.len(),
+        candidate_primers_rev.len(),
+    ));
+
+    Ok(Selection {
+        forward: candidate_primers_fwd,
+        reverse: candidate_primers_rev,
+        stages,
+        traces: [trace_fwd, trace_rev],
+        filter_summaries,
+        rejections,
+    })
+}
+
+/**
+ * Parameters of `design`, the segmentation of the sequences and the primer selection
+ */
+pub struct DesignParams {
+    pub partitioning: PartitioningOption,
+    pub program_config: ProgramConfig,
+}
+
+/**
+ * Design primers in memory, without reading or writing files
+ *
+ * Segments the (aligned) sequences, selects the k-mers and filters them by the criteria,
+ * forward primers first. primer3 is still run for the criteria, the ntthal cross dimer
+ * filter, sorting and outputs are left to `run`.
+ */
+pub fn design(
+    records: &[SequenceRecord],
+    params: &DesignParams,
+) -> Result<Vec<KmerStat>, DesignError> {
+    let segment_manager = get_segment_manager(records, params.partitioning.clone())?;
+    let selection = select_primers(&segment_manager, records.len(), &params.program_config)?;
+    Ok(selection
+        .forward
+        .into_iter()
+        .chain(selection.reverse)
+        .collect())
+}
+
+/**
+ * Run the whole primer design pipeline with the parsed command line arguments
+ */
+/**
+ * Order the dimer edges the ΔG filter resolves, which decides the primer dropped from a
+ * dimer when neither primer has another one. The edges of a primer come from a HashSet, so
+ * their order changes between runs unless shuffled by the `seed` or sorted by their primers
+ * when `deterministic`.
+ */
+fn order_dimer_edges(edges: &mut [&Edge], seed: Option<u64>, deterministic: bool) {
+    match seed {
+        Some(seed) => edges.sort_by_cached_key(|edge| {
+            let mut hasher = std::hash::DefaultHasher::new();
+            (seed, edge.get_id()).hash(&mut hasher);
+            (std::hash::Hasher::finish(&hasher), edge.get_id().clone())
+        }),
+        None if deterministic => edges.sort_by(|a, b| a.get_id().cmp(b.get_id())),
+        None => {}
+    }
+}
+
+/**
+ * Species and taxonomy ID of the outputs, the most common of the input or the arguments
+ */
+fn get_taxonomy(records: &[SequenceRecord], args: &Args) -> output::Taxonomy {
+    output::Taxonomy {
+        species_name: most_common_value(
+            records.iter().map(|r| &r.species_name),
+            &args.species_name,
+        ),
+        tax_id: most_common_value(records.iter().map(|r| &r.tax_id), &args.tax_id),
+    }
+}
+
+/**
+ * Write the primers in the output format
+ */
+fn write_primers<W: Write>(
+    writer: W,
+    format: OutputFormat,
+    primers: &[Vec<KmerStat>],
+    taxonomy: &output::Taxonomy,
+    columns: &[Column],
+) -> io::Result<()> {
+    match format {
+        OutputFormat::Csv if columns == config::DEFAULT_COLUMNS => {
+            output::write_csv(writer, primers, taxonomy)
+        }
+        OutputFormat::Csv => output::write_table(writer, b',', primers, taxonomy, columns),
+        OutputFormat::Tsv => output::write_table(writer, b'\t', primers, taxonomy, columns),
+        OutputFormat::Json => output::write_json(writer, primers),
+        OutputFormat::Jsonl => output::write_jsonl(writer, primers),
+        OutputFormat::Primer3 => output::write_boulder_io(writer, primers),
+    }
+}
+
+pub fn run(args: Args) -> Result<(), DesignError> {
+    log::debug!("args: do_align={:?}", args.do_align);
+    configure_threads(args.threads);
+    let dry_run = args.dry_run.as_str() == "true";
+    // written first, so failed runs can be reproduced
+    if !dry_run && let Some(manifest_file) = &args.manifest {
+        let manifest = output::Manifest::new(&args)?;
+        output::write_manifest(create_file(manifest_file)?, &manifest)?;
+    }
+    let output_file = args.output.to_string();
+
+    let is_ntthal_path_default = args.ntthal == config::DEFAULT_NTTHAL_PATH;
+    let is_primer3_path_default = args.primer3 == config::DEFAULT_PRIMER3_PATH;
+    let ntthal_path = find_executable(args.ntthal.as_str(), !is_ntthal_path_default);
+    let primer3_path = find_executable(args.primer3.as_str(), !is_primer3_path_default);
+    // check if both ntthal and primer3 are available
+    let Some(ntthal_path) = ntthal_path else {
+        return Err(DesignError::MissingExecutable {
+            name: "ntthal",
+            path: args.ntthal,
+        });
+    };
+    let Some(primer3_path) = primer3_path else {
+        return Err(DesignError::MissingExecutable {
+            name: "primer3",
+            path: args.primer3,
+        });
+    };
+
+    // blastn is only required when checking specificity
+    let blastn_path = match &args.specificity_db {
+        Some(_) => {
+            let is_blastn_path_default = args.blastn == config::DEFAULT_BLASTN_PATH;
+            let Some(blastn_path) = find_executable(args.blastn.as_str(), !is_blastn_path_default)
+            else {
+                return Err(DesignError::MissingExecutable {
+                    name: "blastn",
+                    path: args.blastn,
+                });
+            };
+            blastn_path
+        }
+        None => args.blastn.clone(),
+    };
+
+    let mut program_config = get_program_config(&args, ntthal_path, primer3_path, blastn_path);
+    if let Some(table_file) = &args.complement_table {
+        let table = iupac::parse_complement_table(&std::fs::read_to_string(table_file)?)?;
+        log::info!("Complementing {} bases with {}", table.len(), table_file);
+        program_config.complement_table = Some(table);
+    }
+    // fail before the alignment rather than after it
+    let mut options = get_partitioning_option(&args, program_config.progress);
+    for kmer_size in args.kmer_sizes.iter().chain([&options.kmer_size]) {
+        check_window_sizes(&PartitioningOption {
+            kmer_size: *kmer_size,
+            ..options.clone()
+        })?;
+    }
+
+    // read before the alignment, an updated index segments the added sequences with them
+    if let Some(conservation_file) = &args.conservation {
+        let conservation = parse_conservation(&std::fs::read_to_string(conservation_file)?)?;
+        log::info!(
+            "Weighting k-mers by the conservation of {} positions in {}",
+            conservation.len(),
+            conservation_file
+        );
+        options.conservation = Some(conservation);
+    }
+    if let Some(mask_file) = &args.mask {
+        let mask = parse_mask(&std::fs::read_to_string(mask_file)?)?;
+        log::info!(
+            "Masking {} ranges of {} positions in {}",
+            mask.len(),
+            mask.iter().map(|(start, end)| end - start).sum::<usize>(),
+            mask_file
+        );
+        options.mask = Some(mask);
+    }
+    if let Some(weights_file) = &args.sequence_weights {
+        options.weights = Some(parse_sequence_weights(&std::fs::read_to_string(
+            weights_file,
+        )?)?);
+    }
+
+    let verbose = args.verbose.as_str() == "true";
+
+    // 1. Align sequences, or load them along with their segments from a saved index
+    let timer = progress::StageTimer::start("alignment", verbose);
+    let loaded_index = match (&args.update, &args.load_index) {
+        (Some(index_file), _) => Some(update_index(
+            index_file,
+            &args.add,
+            &options,
+            &program_config,
+            args.strict_length.as_str() == "true",
+            dry_run,
+        )?),
+        (None, Some(index_file)) => {
+            let loaded = index::load_index(index_file)?;
+            log::info!(
+                "Loaded {} sequences from {}",
+                loaded.records.len(),
+                index_file
+            );
+            Some(loaded)
+        }
+        (None, None) => None,
+    };
+    let aligned_records;
+    let records: &[SequenceRecord] = match &loaded_index {
+        Some(loaded) => &loaded.records,
+        None => {
+            log::info!("Aligning sequences...");
+            aligned_records = read_records(&args.input, &program_config)?;
+            check_uniform_length(&aligned_records, args.strict_length.as_str() == "true")?;
+            if program_config.do_align {
+                log::info!(".... DONE.");
+            } else {
+                log::info!(".... SKIPPED.");
+            }
+            &aligned_records
+        }
+    };
+    for (source, total) in records
+        .iter()
+        .filter_map(|r| r.source.as_ref())
+        .counts()
+        .into_iter()
+        .sorted()
+    {
+        log::info!("Read {} sequences from {}", total, source);
+    }
+
+    timer.finish();
+
+    if let Some(check_file) = &args.check {
+        let primers = parse_check_primers(&std::fs::read_to_string(check_file)?)?;
+        log::info!("Checking {} primers of {}", primers.len(), check_file);
+        let evaluated = evaluate_primers(records, &options, &primers, &program_config)?;
+        if dry_run {
+            log::info!("Dry run, skipped writing outputs");
+            return Ok(());
+        }
+        let taxonomy = get_taxonomy(records, &args);
+        let output_primers = output::convert_primers_alphabet(&evaluated, args.alphabet);
+        write_primers(
+            create_writer(&output_file)?,
+            args.format,
+            &output_primers,
+            &taxonomy,
+            &args.columns,
+        )?;
+        return Ok(());
+    }
+
+    // 2. Extracting n-grams from each sequence segments
+    let timer = progress::StageTimer::start("segmentation", verbose);
+    log::info!("Extracting n-grams from each sequence segments...");
+    if let Some(weights_file) = &args.sequence_weights
+        && let Some(weights) = &options.weights
+    {
+        let names: HashSet<&str> = records.iter().map(|r| r.name.as_str()).collect();
+        let unknown = weights
+            .keys()
+            .filter(|name| !names.contains(name.as_str()))
+            .count();
+        if unknown > 0 {
+            log::warn!(
+                "{} sequences of {} are not in the input, their weights are ignored",
+                unknown,
+                weights_file
+            );
+        }
+        log::info!(
+            "Weighting k-mers by the weights of {} sequences in {}",
+            weights.len() - unknown,
+            weights_file
+        );
+    }
+    let excluded = match &args.exclude {
+        Some(exclude_file) => parse_exclude_list(
+            &std::fs::read_to_string(exclude_file)?,
+            program_config.complement_table.as_ref(),
+        ),
+        None => HashSet::new(),
+    };
+    let mask = options.mask.clone();
+    let mut segment_manager = match &loaded_index {
+        Some(loaded) => {
+            loaded.check_options(&options)?;
+            if !args.kmer_sizes.is_empty() {
+                log::warn!("Ignoring --kmer-sizes, the segments are loaded from an index");
+            }
+            loaded.get_segment_manager()?
+        }
+        None => {
+            if !args.kmer_sizes.is_empty() {
+                log::info!("Sweeping k-mer sizes {:?}...", args.kmer_sizes);
+                let reports = sweep_kmer_sizes(
+                    records,
+                    &options,
+                    &args.kmer_sizes,
+                    &excluded,
+                    &program_config,
+                )?;
+                output::write_kmer_sizes(io::stderr(), &reports)?;
+                if let Some(kmer_size) = get_best_kmer_size(&reports) {
+                    log::info!("Designing with the best k-mer size {}", kmer_size);
+                    options.kmer_size = kmer_size;
+                    program_config.primer_config.kmer_size = kmer_size;
+                }
+            }
+            let manager = get_segment_manager(records, options.clone())?;
+            if !dry_run && let Some(index_file) = &args.save_index {
+                let saved = index::SegmentIndex::new(records, &manager, &options);
+                index::save_index(index_file, &saved)?;
+                log::info!("Saved the segment index to {}", index_file);
+            }
+            manager
+        }
+    };
+    if let Some(exclude_file) = &args.exclude {
+        let removed = exclude_kmers(&mut segment_manager.segments, &excluded);
+        log::info!("Excluded {} k-mers listed in {}", removed, exclude_file);
+    }
+    let total_partitions = segment_manager
+        .segments
+        .iter()
+        .map(|s| s.partition_no)
+        .max()
+        .unwrap_or_default();
+    log::info!(
+        "Done, total partitions: {}, total segments: {}",
+        total_partitions,
+        segment_manager.segments.len()
+    );
+
+    timer.finish();
+
+    // 3. Calculate frequencies of n-grams for each segment both forward/reverse and filter
+    let timer = progress::StageTimer::start("selection", verbose);
+    let Selection {
+        forward: candidate_primers_fwd,
+        reverse: candidate_primers_rev,
+        stages,
+        traces: [trace_fwd, trace_rev],
+        filter_summaries,
+        mut rejections,
+    } = select_primers(&segment_manager, records.len(), &program_config)?;
+    if let Some(filter_summaries) = &filter_summaries {
+        output::write_filter_summary(io::stderr(), filter_summaries)?;
+        if !dry_run && let Some(report_file) = &args.report {
+            output::write_filter_summary(create_file(report_file)?, filter_summaries)?;
+        }
+    }
+    let mut summary = output::RunSummary {
+        records: records.len(),
+        segments: segment_manager.segments.len(),
+        stages,
+        pool_tm: None,
+    };
+    timer.finish();
+
+    let timer = progress::StageTimer::start("delta_g", verbose);
+    let primers: Vec<String> = candidate_primers_fwd
+        .iter()
+        .chain(&candidate_primers_rev)
+        .map(|s| s.word.clone())
+        .collect();
+    let ntthal_opts = NtthalOptions {
+        mv: args.mv_conc,
+        dv: args.dv_conc,
+        dntp: args.dntp_conc,
+        conc: args.dna_conc,
+        t: args.annealing_temp,
+        dg: args.delta_g_threshold,
+    };
+    let graph = run_ntthal(primers.clone(), ntthal_opts, program_config.clone())?;
+    let mut candidate_unusable_edges: Vec<&Edge> = Vec::new();
+    let mut primers_total_low_dg: HashMap<String, i32> = HashMap::new();
+    // find nodes with dG < -9.0kmol-1
+    for primer in primers.clone() {
+        let edges = graph.get_edges_for_node(&primer);
+        for edge in edges {
+            let dg = edge.get_dg();
+            if dg < args.delta_g_threshold {
+                candidate_unusable_edges.push(edge);
+                let (a, b) = graph.get_edge_nodes(edge);
+                log::debug!("Edge: {} -> {} dg={}", a.id, b.id, dg);
+                *primers_total_low_dg.entry(a.id.clone()).or_insert(0) += 1;
+                *primers_total_low_dg.entry(b.id.clone()).or_insert(0) += 1;
+            }
+        }
+    }
+    order_dimer_edges(
+        &mut candidate_unusable_edges,
+        program_config.seed,
+        program_config.deterministic,
+    );
+    let mut deleted_primers: HashSet<String> = HashSet::new();
+    for edge in candidate_unusable_edges {
+        let (a, b) = graph.get_edge_nodes(edge);
+        let total_n_a = primers_total_low_dg.get(&a.id).unwrap();
+        let total_n_b = primers_total_low_dg.get(&b.id).unwrap();
+        if total_n_a > &1 {
+            deleted_primers.insert(a.id.clone());
+        }
+        if total_n_b > &1 {
+            deleted_primers.insert(b.id.clone());
+        }
+        if total_n_a == &1 && !deleted_primers.contains(b.id.as_str()) {
+            deleted_primers.insert(a.id.clone());
+        }
+        if total_n_b == &1 && !deleted_primers.contains(a.id.as_str()) {
+            deleted_primers.insert(b.id.clone());
+        }
+    }
+    log::debug!("Will delete primers: {:?}", deleted_primers);
+    if !program_config.keep_all {
+        rejections.extend(
+            candidate_primers_fwd
+                .iter()
+                .chain(&candidate_primers_rev)
+                .filter(|p| deleted_primers.contains(p.word.as_str()))
+                .map(|p| output::Rejection::new(p, vec!["dimer"])),
+        );
+    }
+    if !dry_run && let Some(explain_file) = &args.explain {
+        output::write_rejections(create_file(explain_file)?, &rejections)?;
+        log::info!(
+            "Wrote the {} rejected primers to {}",
+            rejections.len(),
+            explain_file
+        );
+    }
+    let good_delta_g_fwd_primers: Vec<KmerStat> = match program_config.keep_all {
+        false => candidate_primers_fwd
+            .iter()
+            .filter(|p| !deleted_primers.contains(p.word.as_str()))
+            .cloned()
+            .collect(),
+        true => candidate_primers_fwd,
+    };
+    let good_delta_g_rev_primers: Vec<KmerStat> = match program_config.keep_all {
+        false => candidate_primers_rev
+            .iter()
+            .filter(|p| !deleted_primers.contains(p.word.as_str()))
+            .cloned()
+            .collect(),
+        true => candidate_primers_rev,
+    };
+    if program_config.keep_all {
+        log::info!(".... SKIPPED.");
+    } else {
+        log::info!(".... DONE.");
+        log::info!(
+            "Filtering out unmatched, primers left is fwd={}, rev={}",
+            good_delta_g_fwd_primers.len(),
+            good_delta_g_rev_primers.len()
+        );
+    }
+    summary.stages.push((
+        "delta_g",
+        good_delta_g_fwd_primers.len(),
+        good_delta_g_rev_primers.len(),
+    ));
+    // balanced after the filters, so the cap holds for the primers written
+    let (good_delta_g_fwd_primers, good_delta_g_rev_primers) = balance_directions(
+        good_delta_g_fwd_primers,
+        good_delta_g_rev_primers,
+        program_config.max_direction_ratio,
+    );
+    log::info!(
+        "Primers after balancing directions, fwd: {}, rev: {}",
+        good_delta_g_fwd_primers.len(),
+        good_delta_g_rev_primers.len()
+    );
+    summary.stages.push((
+        "direction_balance",
+        good_delta_g_fwd_primers.len(),
+        good_delta_g_rev_primers.len(),
+    ));
+    let mut candidate_primers = vec![good_delta_g_fwd_primers, good_delta_g_rev_primers];
+    for primers in candidate_primers.iter_mut() {
+        sort_primers(primers, args.sort_by, &segment_manager.segments);
+    }
+    if args.collapse_degeneracy > 1 {
+        let total: usize = candidate_primers.iter().map(|p| p.len()).sum();
+        for primers in candidate_primers.iter_mut() {
+            *primers = collapse_primers(
+                std::mem::take(primers),
+                &segment_manager.segments,
+                args.collapse_degeneracy,
+            );
+        }
+        log::info!(
+            "Collapsed {} primers into degenerate consensus primers",
+            total - candidate_primers.iter().map(|p| p.len()).sum::<usize>()
+        );
+        summary.stages.push((
+            "collapse",
+            candidate_primers[0].len(),
+            candidate_primers[1].len(),
+        ));
+    }
+    if args.min_primer_spacing > 0 {
+        let total: usize = candidate_primers.iter().map(|p| p.len()).sum();
+        for primers in candidate_primers.iter_mut() {
+            *primers = space_primers(
+                std::mem::take(primers),
+                &segment_manager.segments,
+                args.min_primer_spacing,
+            );
+        }
+        log::info!(
+            "Rejected {} primers within {} bases of a primer of the same direction",
+            total - candidate_primers.iter().map(|p| p.len()).sum::<usize>(),
+            args.min_primer_spacing
+        );
+        summary.stages.push((
+            "spacing",
+            candidate_primers[0].len(),
+            candidate_primers[1].len(),
+        ));
+    }
+    if let Some(top_n) = args.top_n {
+        let total: usize = candidate_primers.iter().map(|p| p.len()).sum();
+        candidate_primers = take_top_primers(
+            candidate_primers,
+            top_n,
+            program_config.max_direction_ratio >= 1.0,
+            args.sort_by,
+            &segment_manager.segments,
+        );
+        log::info!("Kept the top {} of {} primers", top_n.min(total), total);
+        summary.stages.push((
+            "top_n",
+            candidate_primers[0].len(),
+            candidate_primers[1].len(),
+        ));
+    }
+    if args.name_by_position.as_str() == "true" {
+        name_by_position(&mut candidate_primers, &segment_manager.segments);
+    }
+    summary.pool_tm = check_pool_tm(&candidate_primers, args.max_pool_tm_std);
+    if program_config.soft_mask || mask.is_some() {
+        let flagged = flag_near_masked(
+            &mut candidate_primers,
+            &segment_manager.segments,
+            program_config.soft_mask,
+            mask.as_deref(),
+            args.mask_proximity,
+        );
+        if flagged > 0 {
+            log::warn!(
+                "{} primers bind within {} bases of a masked region",
+                flagged,
+                args.mask_proximity
+            );
+        }
+    }
+    timer.finish();
+    if dry_run {
+        log::info!("Dry run, skipped writing outputs");
+        output::write_summary(io::stderr(), &summary)?;
+        return Ok(());
+    }
+
+    // 5. Output the primers
+    let timer = progress::StageTimer::start("output", verbose);
+    log::info!("Outputting primers...");
+    let taxonomy = get_taxonomy(records, &args);
+    // the alphabet is only converted for reporting, pairs and coverage match on DNA words
+    let output_primers = output::convert_primers_alphabet(&candidate_primers, args.alphabet);
+    write_primers(
+        create_writer(&output_file)?,
+        args.format,
+        &output_primers,
+        &taxonomy,
+        &args.columns,
+    )?;
+    if let Some(fasta_file) = &args.fasta {
+        let writer = create_file(fasta_file)?;
+        output::write_fasta(writer, &output_primers)?;
+    }
+    if let Some(pairs_file) = &args.pairs {
+        let primers: Vec<KmerStat> = candidate_primers.iter().flatten().cloned().collect();
+        let pairs = pair_primers(
+            &primers,
+            &segment_manager.segments,
+            args.min_amplicon_length,
+            args.max_amplicon_length,
+            args.max_delta_tm,
+        );
+        log::info!("Total primer pairs: {}", pairs.len());
+        let writer = create_file(pairs_file)?;
+        output::write_pairs(writer, &pairs, args.alphabet)?;
+    }
+    if let Some(trace_file) = &args.trace {
+        let writer = create_file(trace_file)?;
+        output::write_trace(writer, trace_fwd.iter().chain(&trace_rev))?;
+    }
+    if let Some(matrix_file) = &args.dimer_matrix {
+        let (names, primers): (Vec<String>, Vec<KmerStat>) = candidate_primers
+            .iter()
+            .flat_map(|p| p.iter().enumerate())
+            .map(|(idx, primer)| (output::get_primer_name(primer, idx), primer.clone()))
+            .unzip();
+        let writer = create_file(matrix_file)?;
+        output::write_dimer_matrix(writer, &names, &dimer_matrix(&primers))?;
+    }
+    if let Some(gff_file) = &args.gff {
+        let tiling = get_tiling(&candidate_primers, &segment_manager.segments);
+        let writer = create_file(gff_file)?;
+        output::write_gff(writer, &tiling)?;
+    }
+    if let Some(tiling_file) = &args.tiling {
+        let tiling = get_tiling(&candidate_primers, &segment_manager.segments);
+        let writer = create_file(tiling_file)?;
+        output::write_tiling(writer, &tiling)?;
+    }
+    if let Some(coverage_file) = &args.coverage {
+        let coverage = get_segment_coverage(&segment_manager.segments, &candidate_primers);
+        log::info!(
+            "Segments covered: {}/{} ({:.2}%), uncovered: {:?}",
+            coverage.covered.len(),
+            coverage.total(),
+            coverage.percent(),
+            coverage.uncovered
+        );
+        let writer = create_file(coverage_file)?;
+        output::write_coverage(writer, &coverage)?;
+    }
+    log::info!("Done outputting primers");
+    timer.finish();
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::get_test_program_config;
+    use crate::testing::get_test_kmer_stat;
+
+    #[test]
+    fn test_reverse_complement() {
+        let sequence = "ATCGAA";
+        assert_eq!(reverse_complement(sequence, None), "TTCGAT");
+    }
+
+    #[test]
+    fn test_parse_header_taxonomy() {
+        let (species_name, tax_id) = parse_header_taxonomy(
+            "Severe acute respiratory syndrome coronavirus 2 isolate Wuhan-Hu-1, complete genome taxid=2697049",
+        );
+        assert_eq!(
+            species_name.unwrap(),
+            "Severe acute respiratory syndrome coronavirus 2"
+        );
+        assert_eq!(tax_id.unwrap(), "2697049");
+
+        let (species_name, tax_id) =
+            parse_header_taxonomy("[organism=Dengue virus 2] [tax_id=11060] segment 1");
+        assert_eq!(species_name.unwrap(), "Dengue virus 2");
+        assert_eq!(tax_id.unwrap(), "11060");
+
+        let (species_name, tax_id) = parse_header_taxonomy("");
+        assert!(species_name.is_none());
+        assert!(tax_id.is_none());
+    }
+
+    #[test]
+    fn test_to_records_taxonomy() {
+        let fasta = b">seq1 Zika virus strain MR766 taxid=64320\nACGU\n>seq2\nACGT\n".to_vec();
+        let records = to_records(fasta, false).unwrap();
+        assert_eq!(records[0].species_name.as_deref(), Some("Zika virus"));
+        assert_eq!(records[0].tax_id.as_deref(), Some("64320"));
+        assert_eq!(records[0].sequence, "ACGT");
+        assert!(records[1].species_name.is_none());
+
+        let species_name = most_common_value(records.iter().map(|r| &r.species_name), "unknown");
+        assert_eq!(species_name, "Zika virus");
+        let tax_id = most_common_value(std::iter::empty(), "unknown");
+        assert_eq!(tax_id, "unknown");
+    }
+
+    #[test]
+    fn test_to_records_from_path() {
+        let path = std::env::temp_dir().join(format!("od-msspe-path-{}.fasta", std::process::id()));
+        std::fs::write(
+            &path,
+            ">seq1 Zika virus taxid=64320\nACGU\nACGT\n>seq2\nTTGC\n",
+        )
+        .unwrap();
+        let records = to_records_from_path(&path, false).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].sequence, "ACGTACGT");
+        assert_eq!(records[0].tax_id.as_deref(), Some("64320"));
+        assert_eq!(records[1].name, "seq2");
+    }
+
+    #[test]
+    fn test_read_fastq_records() {
+        let fasta = ">seq1 Zika virus taxid=64320\nACGU\n>seq2\nTTGC\n";
+        let fastq = "@seq1 Zika virus taxid=64320\nACGU\n+\nIIII\n@seq2\nTTGC\n+\n#I#I\n";
+        let dir = std::env::temp_dir().join(format!("od-msspe-fastq-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let fasta_path = dir.join("seqs.fasta");
+        let fastq_path = dir.join("seqs.fq");
+        // detected by content without the extension
+        let sniffed_path = dir.join("seqs.txt");
+        std::fs::write(&fasta_path, fasta).unwrap();
+        std::fs::write(&fastq_path, fastq).unwrap();
+        std::fs::write(&sniffed_path, format!("\n{}", fastq)).unwrap();
+
+        let expected = to_records_from_path(&fasta_path, false).unwrap();
+        for path in [&fastq_path, &sniffed_path] {
+            assert!(is_fastq_path(path).unwrap());
+            let records = to_records_from_path(path, false).unwrap();
+            assert_eq!(records.len(), expected.len());
+            for (a, b) in records.iter().zip(&expected) {
+                assert_eq!(a.name, b.name);
+                assert_eq!(a.sequence, b.sequence);
+                assert_eq!(a.tax_id, b.tax_id);
+            }
+        }
+        assert!(!is_fastq_path(&fasta_path).unwrap());
+
+        let records = read_sequence_records(fastq.as_bytes(), false).unwrap();
+        assert_eq!(records[0].sequence, "ACGT");
+
+        let mut converted = Vec::new();
+        write_fastq_as_fasta(&fastq_path, &mut converted).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(
+            String::from_utf8(converted).unwrap(),
+            ">seq1 Zika virus taxid=64320\nACGU\n>seq2\nTTGC\n"
+        );
+
+        let result = read_fastq_records("@seq1\nACGT\n".as_bytes(), false);
+        assert!(matches!(result, Err(DesignError::FastqParse(_))));
+    }
+
+    #[test]
+    fn test_design_errors() {
+        let result = to_records(b"ACGT\n".to_vec(), false);
+        assert!(matches!(result, Err(DesignError::FastaParse(_))));
+
+        let result = to_records(b">seq1\n\xff\xfe\n".to_vec(), false);
+        assert!(matches!(result, Err(DesignError::FastaParse(_))));
+
+        let path =
+            std::env::temp_dir().join(format!("od-msspe-empty-{}.fasta", std::process::id()));
+        std::fs::write(&path, "").unwrap();
+        let config = get_test_program_config();
+        let result = read_records(&[path.display().to_string()], &config);
+        std::fs::remove_file(&path).unwrap();
+        assert!(matches!(result, Err(DesignError::EmptyInput)));
+
+        let result = read_records(&["does/not/exist.fasta".to_string()], &config);
+        assert!(matches!(result, Err(DesignError::Io(_))));
+
+        let records = vec![SequenceRecord::new("seq1".to_string(), "ACGT".to_string())];
+        let opt = PartitioningOption {
+            segment_size: 10,
+            overlap_size: 2,
+            window_size: 5,
+            kmer_size: 3,
+            ..Default::default()
+        };
+        let result = get_segment_manager(&records, opt);
+        assert!(matches!(
+            result,
+            Err(DesignError::InvalidWindowSizes {
+                overlap_size: 2,
+                window_size: 5
+            })
+        ));
+    }
+
+    #[test]
+    fn test_kmer_longer_than_window() {
+        let records = vec![SequenceRecord::new("seq1".to_string(), "ACGT".to_string())];
+        let opt = PartitioningOption {
+            segment_size: 250,
+            overlap_size: 125,
+            kmer_size: 60,
+            ..Default::default()
+        };
+        let result = get_segment_manager(&records, opt);
+        assert!(matches!(
+            result,
+            Err(DesignError::KmerLongerThanWindow {
+                kmer_size: 60,
+                window_size: 50
+            })
+        ));
+        assert_eq!(
+            result.err().unwrap().to_string(),
+            "k-mer size (60) must not exceed the search windows size (50), no k-mer would fit in a window"
+        );
+    }
+
+    #[test]
+    fn test_run_with_timeout() {
+        let started = std::time::Instant::now();
+        let mut command = std::process::Command::new("sleep");
+        command.arg("5");
+        let result = run_with_timeout(&mut command, Some(std::time::Duration::from_millis(200)));
+        assert!(started.elapsed() < std::time::Duration::from_secs(5));
+        assert!(matches!(
+            result,
+            Err(DesignError::AlignmentTimeout(t)) if t == std::time::Duration::from_millis(200)
+        ));
+        assert_eq!(
+            result.err().unwrap().to_string(),
+            "MAFFT did not finish within 200ms, raise --align-timeout or set it to 0"
+        );
+
+        let mut command = std::process::Command::new("echo");
+        command.arg(">seq1");
+        let output =
+            run_with_timeout(&mut command, Some(std::time::Duration::from_secs(5))).unwrap();
+        assert!(output.status.success());
+        assert_eq!(output.stdout, b">seq1\n");
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_run_with_timeout_kills_children() {
+        // like the mafft wrapper script, the shell waits on the process doing the work
+        let pid_file =
+            std::env::temp_dir().join(format!("od-msspe-timeout-{}.pid", std::process::id()));
+        let mut command = std::process::Command::new("sh");
+        command
+            .arg("-c")
+            .arg(format!("sleep 30 & echo $! > {}; wait", pid_file.display()));
+        let result = run_with_timeout(&mut command, Some(std::time::Duration::from_millis(300)));
+        assert!(matches!(result, Err(DesignError::AlignmentTimeout(_))));
+        let pid = std::fs::read_to_string(&pid_file).unwrap();
+        std::fs::remove_file(&pid_file).unwrap();
+
+        // the orphaned sleep is reaped by init once killed
+        let stat = format!("/proc/{}/stat", pid.trim());
+        let alive = || {
+            std::fs::read_to_string(&stat).is_ok_and(|s| s.split_whitespace().nth(2) != Some("Z"))
+        };
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(2);
+        while alive() && std::time::Instant::now() < deadline {
+            std::thread::sleep(std::time::Duration::from_millis(20));
+        }
+        assert!(!alive());
+    }
+
+    #[test]
+    fn test_get_mafft_threads() {
+        assert_eq!(get_mafft_threads(0), "-1");
+        assert_eq!(get_mafft_threads(4), "4");
+    }
+
+    #[test]
+    fn test_is_stdio() {
+        assert!(is_stdio("-"));
+        assert!(is_stdio(""));
+        assert!(!is_stdio("output/primers.csv"));
+    }
+
+    #[test]
+    fn test_create_writer_nested_dir() {
+        let dir = std::env::temp_dir().join(format!("od-msspe-out-{}", std::process::id()));
+        let path = dir.join("nested/output/primers.csv");
+        let mut writer = create_writer(&path.display().to_string()).unwrap();
+        writer.write_all(b"direction,name\n").unwrap();
+        drop(writer);
+
+        let content = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(content, "direction,name\n");
+    }
+
+    #[test]
+    fn test_read_records_multiple_files() {
+        let dir = std::env::temp_dir().join(format!("od-msspe-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let file_a = dir.join("a.fasta").display().to_string();
+        let file_b = dir.join("b.fasta").display().to_string();
+        std::fs::write(&file_a, ">seq1\nACGT\n>seq2\nACGA").unwrap();
+        std::fs::write(&file_b, ">seq3\nTTGCA\n").unwrap();
+
+        let records = read_records(
+            &[file_a.clone(), file_b.clone()],
+            &get_test_program_config(),
+        )
+        .unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let names: Vec<&str> = records.iter().map(|r| r.name.as_str()).collect();
+        assert_eq!(names, vec!["seq1", "seq2", "seq3"]);
+        assert_eq!(records[1].sequence, "ACGA");
+        assert_eq!(records[0].source.as_ref(), Some(&file_a));
+        assert_eq!(records[1].source.as_ref(), Some(&file_a));
+        assert_eq!(records[2].source.as_ref(), Some(&file_b));
+    }
+
+    #[test]
+    fn test_read_records_empty_sequences() {
+        let path = std::env::temp_dir().join(format!("od-msspe-gaps-{}.fasta", std::process::id()));
+        std::fs::write(&path, ">seq1\nACGT\n>seq2\n----\n>seq3\n\n>seq4\nN-NN\n").unwrap();
+        let records = read_records(&[path.display().to_string()], &get_test_program_config());
+        let names: Vec<String> = records.unwrap().into_iter().map(|r| r.name).collect();
+        assert_eq!(names, vec!["seq1"]);
+
+        std::fs::write(&path, ">seq1\n----\n").unwrap();
+        let result = read_records(&[path.display().to_string()], &get_test_program_config());
+        std::fs::remove_file(&path).unwrap();
+        assert!(matches!(result, Err(DesignError::EmptyInput)));
+    }
+
+    #[test]
+    fn test_read_records_duplicate_names() {
+        let path = std::env::temp_dir().join(format!("od-msspe-dups-{}.fasta", std::process::id()));
+        std::fs::write(&path, ">seq1\nACGT\n>seq1_2\nACGA\n>seq1\nACGC\n").unwrap();
+        let records = read_records(&[path.display().to_string()], &get_test_program_config());
+        let names: Vec<String> = records.unwrap().into_iter().map(|r| r.name).collect();
+        // seq1_2 is taken by the input, the duplicate gets the next suffix
+        assert_eq!(names, vec!["seq1", "seq1_2", "seq1_3"]);
+
+        let mut config = get_test_program_config();
+        config.duplicate_names = DuplicateNames::Error;
+        let result = read_records(&[path.display().to_string()], &config);
+        std::fs::remove_file(&path).unwrap();
+        assert!(matches!(
+            result,
+            Err(DesignError::DuplicateNames { count: 1, name }) if name == "seq1"
+        ));
+    }
+
+    #[test]
+    fn test_get_search_windows() {
+        let sequence = "AACCTTGGAACCTTG-".to_string();
+        let (first, second) = get_sequence_on_search_windows(&sequence, 5).unwrap();
+        assert_eq!(first, "AACCT");
+        assert_eq!(second, "CTTG-");
+    }
+
+    #[test]
+    fn test_get_search_windows_shorter_than_window() {
+        assert!(get_sequence_on_search_windows("ACG", 5).is_none());
+
+        let records = vec![SequenceRecord::new("seq1".to_string(), "ACG".to_string())];
+        let opt = PartitioningOption {
+            segment_size: 3,
+            overlap_size: 5,
+            window_size: 5,
+            kmer_size: 3,
+            ..Default::default()
+        };
+        let manager = get_segment_manager(&records, opt).unwrap();
+        assert!(manager.segments.is_empty());
+    }
+
+    #[test]
+    fn test_find_kmers() {
+        let sequence = "AACCTTGGAACCTTG-";
+        let kmers = find_kmers(sequence, 5);
+        assert_eq!(kmers.len(), 8);
+        assert!(kmers.contains(&"AACCT".to_string()));
+        assert!(kmers.contains(&"ACCTT".to_string()));
+        assert!(kmers.contains(&"CCTTG".to_string()));
+        assert!(kmers.contains(&"CTTGG".to_string()));
+        assert!(kmers.contains(&"TTGGA".to_string()));
+        assert!(kmers.contains(&"TGGAA".to_string()));
+        assert!(kmers.contains(&"GGAAC".to_string()));
+        assert!(kmers.contains(&"GAACC".to_string()));
+    }
+
+    #[test]
+    fn test_get_segments() {
+        // search windows = (AACCT)(TGGAA)
+        // AAC, freq=2
+        // ACC, freq=3
+        // CCT, freq=3
+        // reverse complement = AAGGT -> TTCCA
+        // TTC, freq=3
+        // TCC, freq=3
+        // CCA, freq=3
+        let records = vec![
+            SequenceRecord::new("seq1".to_string(), "AACCTTGGAACCTTGG".to_string()),
+            SequenceRecord::new("seq2".to_string(), "AACCTTGGAACCTTG-".to_string()),
+            SequenceRecord::new("seq3".to_string(), "-ACCTTGGAACCTT-G".to_string()),
+        ];
+        for rec in records.iter() {
+            println!("seq={} ---", rec.name);
+            for p in partitioning_sequence(&rec.sequence, 10, 5, 5) {
+                println!("-- partition={}", p);
+                let (i, j) = get_sequence_on_search_windows(&p, 5).unwrap();
+                println!("---- search_windows(start)={}", i);
+                println!("---- search_windows(end)={}", j);
+                let rev = reverse_complement(&j, None);
+                println!("---- search_windows(end/rev)={}", rev);
+            }
+        }
+        let opt = PartitioningOption {
+            segment_size: 10,
+            overlap_size: 5,
+            window_size: 5,
+            kmer_size: 3,
+            ..Default::default()
+        };
+        let manager = get_segment_manager(&records, opt).unwrap();
+        assert_eq!(manager.segments.len(), 9);
+        let first_segment = manager.segments.first().unwrap();
+        for kmer in first_segment.kmers.iter() {
+            for k in kmer.iter() {
+                println!("kmer={}", k.word);
+            }
+        }
+        assert_eq!(manager.segments.first().unwrap().kmers[0].len(), 3);
+        assert_eq!(manager.segments.get(1).unwrap().kmers[1].len(), 3);
+    }
+
+    #[test]
+    fn test_kmers_in_both_directions() {
+        // ACGT is its own reverse complement, found in the start and end window
+        let records = vec![
+            SequenceRecord::new("seq1".to_string(), "ACGTTTACGT".to_string()),
+            SequenceRecord::new("seq2".to_string(), "ACGTGGACGT".to_string()),
+        ];
+        let opt = PartitioningOption {
+            segment_size: 10,
+            overlap_size: 10,
+            window_size: 4,
+            kmer_size: 4,
+            ..Default::default()
+        };
+        let manager = get_segment_manager(&records, opt).unwrap();
+        let segment = &manager.segments[0];
+        let fwd = &segment.kmers[0];
+        let rev = &segment.kmers[1];
+        assert_eq!(fwd.len(), 1);
+        assert_eq!(rev.len(), 1);
+        assert_eq!(
+            (fwd[0].word.as_str(), fwd[0].direction),
+            ("ACGT", Direction::Forward)
+        );
+        assert_eq!(
+            (rev[0].word.as_str(), rev[0].direction),
+            ("ACGT", Direction::Reverse)
+        );
+
+        // each direction counts the word in every segment, the frequency isn't split
+        for direction in Direction::ALL {
+            let kmer_freq = find_most_freq_kmer(
+                &manager.segments,
+                direction,
+                &HashSet::new(),
+                0,
+                Strategy::MaxFrequency,
+            )
+            .unwrap();
+            assert_eq!(kmer_freq.kmer.word, "ACGT");
+            assert_eq!(kmer_freq.kmer.direction, direction);
+            assert_eq!(kmer_freq.frequency, 2);
+        }
+    }
+
+    #[test]
+    fn test_partitioning_sequence() {
+        let sequence = "AACCTTGGAACCTTGG";
+        let partitions = partitioning_sequence(sequence, 10, 5, 5);
+        assert_eq!(partitions.len(), 3);
+        assert_eq!(partitions[0], "AACCTTGGAA");
+        assert_eq!(partitions[1], "TGGAACCTTG");
+        assert_eq!(partitions[2], "CCTTGG");
+    }
+
+    #[test]
+    fn test_window_from_amplicon() {
+        assert_eq!(window_from_amplicon(400, 50), 450);
+
+        use clap::Parser;
+        let args = Args::parse_from(["od-msspe", "--input", "in.fasta", "--amplicon-size", "400"]);
+        let opt = get_partitioning_option(&args, false);
+        assert_eq!((opt.segment_size, opt.overlap_size), (450, 225));
+        assert!(opt.overlap_size >= opt.window_size);
+        // primers at the centres of the search windows are the target apart
+        assert_eq!(opt.segment_size - opt.window_size, 400);
+
+        let args = Args::parse_from(["od-msspe", "--input", "in.fasta"]);
+        let opt = get_partitioning_option(&args, false);
+        assert_eq!((opt.segment_size, opt.overlap_size), (500, 250));
+
+        // an explicit window or overlap size would be silently replaced
+        for size in ["--window-size", "--overlap-size"] {
+            let args = Args::try_parse_from([
+                "od-msspe",
+                "--input",
+                "in.fasta",
+                "--amplicon-size",
+                "400",
+                size,
+                "300",
+            ]);
+            assert!(args.is_err());
+        }
+    }
+
+    #[test]
+    fn test_partitioning_sequence_tail() {
+        // last full partition ends at the end of the sequence, no tail is needed
+        let partitions = partitioning_sequence("AACCTTGGAACCTTG", 10, 5, 5);
+        assert_eq!(partitions.len(), 2);
+
+        // remainder shorter than the minimum size is dropped
+        let partitions = partitioning_sequence("AACCTTGGAACCTTGGA", 10, 5, 8);
+        assert_eq!(partitions.len(), 2);
+
+        // sequence shorter than a partition is kept as a single partition
+        let partitions = partitioning_sequence("AACCTTG", 10, 5, 5);
+        assert_eq!(partitions, vec!["AACCTTG".to_string()]);
+    }
+
+    #[test]
+    fn test_make_kmer_segments_mapping() {
+        let seq_1 = &SequenceRecord::new("seq1".to_string(), "ACTGAGGTATTA".to_string());
+        let seq_2 = &SequenceRecord::new("seq2".to_string(), "ACTGAGGTGGAA".to_string());
+        let manager: SegmentManager = SegmentManager {
+            segments: vec![
+                Segment {
+                    sequence: seq_1,
+                    partition_no: 0,
+                    index: 0,
+                    start: 0,
+                    end: 12,
+                    kmers: [
+                        vec![
+                            KmerRecord {
+                                word: "ACT".to_string(),
+                                direction: Direction::Forward,
+                                count: 1,
+                                strands: [true, false],
+                                canonical: None,
+                                weight: 1.0,
+                                conservation: 1.0,
+                            },
+                            KmerRecord {
+                                word: "CTG".to_string(),
+                                direction: Direction::Forward,
+                                count: 1,
+                                strands: [true, false],
+                                canonical: None,
+                                weight: 1.0,
+                                conservation: 1.0,
+                            },
+                            KmerRecord {
+                                word: "TGA".to_string(),
+                                direction: Direction::Forward,
+                                count: 1,
+                                strands: [true, false],
+                                canonical: None,
+                                weight: 1.0,
+                                conservation: 1.0,
+                            },
+                        ],
+                        vec![
+                            KmerRecord {
+                                word: "TAA".to_string(),
+                                direction: Direction::Reverse,
+                                count: 1,
+                                strands: [true, false],
+                                canonical: None,
+                                weight: 1.0,
+                                conservation: 1.0,
+                            },
+                            KmerRecord {
+                                word: "AAT".to_string(),
+                                direction: Direction::Reverse,
+                                count: 1,
+                                strands: [true, false],
+                                canonical: None,
+                                weight: 1.0,
+                                conservation: 1.0,
+                            },
+                            KmerRecord {
+                                word: "ATA".to_string(),
+                                direction: Direction::Reverse,
+                                count: 1,
+                                strands: [true, false],
+                                canonical: None,
+                                weight: 1.0,
+                                conservation: 1.0,
+                            },
+                        ],
+                    ],
+                },
+                Segment {
+                    sequence: seq_2,
+                    partition_no: 1,
+                    index: 1,
+                    start: 0,
+                    end: 12,
+                    kmers: [
+                        vec![
+                            KmerRecord {
+                                word: "ACT".to_string(),
+                                direction: Direction::Forward,
+                                count: 1,
+                                strands: [true, false],
+                                canonical: None,
+                                weight: 1.0,
+                                conservation: 1.0,
+                            },
+                            KmerRecord {
+                                word: "CTG".to_string(),
+                                direction: Direction::Forward,
+                                count: 1,
+                                strands: [true, false],
+                                canonical: None,
+                                weight: 1.0,
+                                conservation: 1.0,
+                            },
+                            KmerRecord {
+                                word: "TGA".to_string(),
+                                direction: Direction::Forward,
+                                count: 1,
+                                strands: [true, false],
+                                canonical: None,
+                                weight: 1.0,
+                                conservation: 1.0,
+                            },
+                        ],
+                        vec![
+                            KmerRecord {
+                                word: "TTC".to_string(),
+                                direction: Direction::Reverse,
+                                count: 1,
+                                strands: [true, false],
+                                canonical: None,
+                                weight: 1.0,
+                                conservation: 1.0,
+                            },
+                            KmerRecord {
+                                word: "TCC".to_string(),
+                                direction: Direction::Reverse,
+                                count: 1,
+                                strands: [true, false],
+                                canonical: None,
+                                weight: 1.0,
+                                conservation: 1.0,
+                            },
+                            KmerRecord {
+                                word: "CCA".to_string(),
+                                direction: Direction::Reverse,
+                                count: 1,
+                                strands: [true, false],
+                                canonical: None,
+                                weight: 1.0,
+                                conservation: 1.0,
+                            },
+                        ],
+                    ],
+                },
+            ],
+        };
+
+        let kmer_segments_mapping = make_kmer_segments_windows_mapping(&manager.segments, 0);
+        assert_eq!(kmer_segments_mapping.len(), 9);
+        assert_eq!(
+            kmer_segments_mapping
+                .get(&KmerRecord {
+                    word: "ACT".to_string(),
+                    direction: Direction::Forward,
+                    count: 1,
+                    strands: [true, false],
+                    canonical: None,
+                    weight: 1.0,
+                    conservation: 1.0,
+                })
+                .unwrap()
+                .len(),
+            2
+        );
+        assert_eq!(
+            kmer_segments_mapping
+                .get(&KmerRecord {
+                    word: "CTG".to_string(),
+                    direction: Direction::Forward,
+                    count: 1,
+                    strands: [true, false],
+                    canonical: None,
+                    weight: 1.0,
+                    conservation: 1.0,
+                })
+                .unwrap()
+                .len(),
+            2
+        );
+        assert_eq!(
+            kmer_segments_mapping
+                .get(&KmerRecord {
+                    word: "TGA".to_string(),
+                    direction: Direction::Forward,
+                    count: 1,
+                    strands: [true, false],
+                    canonical: None,
+                    weight: 1.0,
+                    conservation: 1.0,
+                })
+                .unwrap()
+                .len(),
+            2
+        );
+        assert_eq!(
+            kmer_segments_mapping
+                .get(&KmerRecord {
+                    word: "TAA".to_string(),
+                    direction: Direction::Reverse,
+                    count: 1,
+                    strands: [true, false],
+                    canonical: None,
+                    weight: 1.0,
+                    conservation: 1.0,
+                })
+                .unwrap()
+                .len(),
+            1
+        );
+        assert_eq!(
+            kmer_segments_mapping
+                .get(&KmerRecord {
+                    word: "AAT".to_string(),
+                    direction: Direction::Reverse,
+                    count: 1,
+                    strands: [true, false],
+                    canonical: None,
+                    weight: 1.0,
+                    conservation: 1.0,
+                })
+                .unwrap()
+                .len(),
+            1
+        );
+        assert_eq!(
+            kmer_segments_mapping
+                .get(&KmerRecord {
+                    word: "ATA".to_string(),
+                    direction: Direction::Reverse,
+                    count: 1,
+                    strands: [true, false],
+                    canonical: None,
+                    weight: 1.0,
+                    conservation: 1.0,
+                })
+                .unwrap()
+                .len(),
+            1
+        );
+        assert_eq!(
+            kmer_segments_mapping
+                .get(&KmerRecord {
+                    word: "TTC".to_string(),
+                    direction: Direction::Reverse,
+                    count: 1,
+                    strands: [true, false],
+                    canonical: None,
+                    weight: 1.0,
+                    conservation: 1.0,
+                })
+                .unwrap()
+                .len(),
+            1
+        );
+        assert_eq!(
+            kmer_segments_mapping
+                .get(&KmerRecord {
+                    word: "TCC".to_string(),
+                    direction: Direction::Reverse,
+                    count: 1,
+                    strands: [true, false],
+                    canonical: None,
+                    weight: 1.0,
+                    conservation: 1.0,
+                })
+                .unwrap()
+                .len(),
+            1
+        );
+        assert_eq!(
+            kmer_segments_mapping
+                .get(&KmerRecord {
+                    word: "CCA".to_string(),
+                    direction: Direction::Reverse,
+                    count: 1,
+                    strands: [true, false],
+                    canonical: None,
+                    weight: 1.0,
+                    conservation: 1.0,
+                })
+                .unwrap()
+                .len(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_find_most_freq_kmer() {
+        let seq_1 = SequenceRecord::new("seq1".to_string(), "ACTGAGGTATTA".to_string());
+        let seq_2 = SequenceRecord::new("seq2".to_string(), "ACAGGGGTGGAA".to_string());
+        let manager: SegmentManager = SegmentManager {
+            segments: vec![
+                Segment {
+                    sequence: &seq_1,
+                    partition_no: 0,
+                    index: 0,
+                    start: 0,
+                    end: 12,
+                    kmers: [
+                        vec![
+                            KmerRecord {
+                                word: "ACT".to_string(),
+                                direction: Direction::Forward,
+                                count: 1,
+                                strands: [true, false],
+                                canonical: None,
+                                weight: 1.0,
+                                conservation: 1.0,
+                            },
+                            KmerRecord {
+                                word: "CTG".to_string(),
+                                direction: Direction::Forward,
+                                count: 1,
+                                strands: [true, false],
+                                canonical: None,
+                                weight: 1.0,
+                                conservation: 1.0,
+                            },
+                            KmerRecord {
+                                word: "TGA".to_string(),
+                                direction: Direction::Forward,
+                                count: 1,
+                                strands: [true, false],
+                                canonical: None,
+                                weight: 1.0,
+                                conservation: 1.0,
+                            },
+                        ],
+                        vec![
+                            KmerRecord {
+                                word: "TAA".to_string(),
+                                direction: Direction::Reverse,
+                                count: 1,
+                                strands: [true, false],
+                                canonical: None,
+                                weight: 1.0,
+                                conservation: 1.0,
+                            },
+                            KmerRecord {
+                                word: "AAT".to_string(),
+                                direction: Direction::Reverse,
+                                count: 1,
+                                strands: [true, false],
+                                canonical: None,
+                                weight: 1.0,
+                                conservation: 1.0,
+                            },
+                            KmerRecord {
+                                word: "ATA".to_string(),
+                                direction: Direction::Reverse,
+                                count: 1,
+                                strands: [true, false],
+                                canonical: None,
+                                weight: 1.0,
+                                conservation: 1.0,
+                            },
+                        ],
+                    ],
+                },
+                Segment {
+                    sequence: &seq_2,
+                    partition_no: 1,
+                    index: 1,
+                    start: 0,
+                    end: 12,
+                    kmers: [
+                        vec![
+                            KmerRecord {
+                                word: "ACT".to_string(),
+                                direction: Direction::Forward,
+                                count: 1,
+                                strands: [true, false],
+                                canonical: None,
+                                weight: 1.0,
+                                conservation: 1.0,
+                            },
+                            KmerRecord {
+                                word: "CAG".to_string(),
+                                direction: Direction::Forward,
+                                count: 1,
+                                strands: [true, false],
+                                canonical: None,
+                                weight: 1.0,
+                                conservation: 1.0,
+                            },
+                            KmerRecord {
+                                word: "TGG".to_string(),
+                                direction: Direction::Forward,
+                                count: 1,
+                                strands: [true, false],
+                                canonical: None,
+                                weight: 1.0,
+                                conservation: 1.0,
+                            },
+                        ],
+                        vec![
+                            KmerRecord {
+                                word: "TTC".to_string(),
+                                direction: Direction::Reverse,
+                                count: 1,
+                                strands: [true, false],
+                                canonical: None,
+                                weight: 1.0,
+                                conservation: 1.0,
+                            },
+                            KmerRecord {
+                                word: "TCC".to_string(),
+                                direction: Direction::Reverse,
+                                count: 1,
+                                strands: [true, false],
+                                canonical: None,
+                                weight: 1.0,
+                                conservation: 1.0,
+                            },
+                            KmerRecord {
+                                word: "CCA".to_string(),
+                                direction: Direction::Reverse,
+                                count: 1,
+                                strands: [true, false],
+                                canonical: None,
+                                weight: 1.0,
+                                conservation: 1.0,
+                            },
+                        ],
+                    ],
+                },
+            ],
+        };
+
+        let result = find_most_freq_kmer(
+            &manager.segments,
+            Direction::Forward,
+            &HashSet::new(),
+            0,
+            Strategy::MaxFrequency,
+        );
+        assert!(result.is_some());
+        let kmer_freq = result.unwrap();
+        assert_eq!(kmer_freq.kmer.word, "ACT");
+        assert_eq!(kmer_freq.frequency, 2);
+    }
+
+    #[test]
+    fn test_has_low_complexity_motif() {
+        // homopolymer, more than 5 identical bases anywhere in the kmer
+        assert!(has_low_complexity_motif("GCAAAAAAGCT", 1, 5));
+        assert!(!has_low_complexity_motif("GCAAAAAGCTT", 1, 5));
+
+        // dinucleotide
+        assert!(has_low_complexity_motif("ATATATATATGG", 2, 4));
+        assert!(has_low_complexity_motif("GATATATATATG", 2, 4));
+        assert!(!has_low_complexity_motif("ATATATATGGCC", 2, 4));
+
+        // trinucleotide
+        assert!(has_low_complexity_motif("CAGCAGCAGCAGT", 3, 3));
+        assert!(has_low_complexity_motif("TCAGCAGCAGCAG", 3, 3));
+        assert!(!has_low_complexity_motif("CAGCAGCAGTTGA", 3, 3));
+        assert!(!has_low_complexity_motif("CAGCAGCAGCAGT", 2, 4));
+
+        // disabled
+        assert!(!has_low_complexity_motif("AAAAAAAAAAAAA", 1, 0));
+
+        let primer_config = get_test_program_config().primer_config;
+        assert!(has_repeats("ACGTTTTTTACGT", &primer_config));
+        assert!(has_repeats("GGCAGCAGCAGCA", &primer_config));
+        assert!(!has_repeats("ACGTAGGCTTACG", &primer_config));
+        // trinucleotide repeats are unchecked by default
+        let primer_config = PrimerConfig {
+            max_tri_repeats: constants::MAX_TRI_REPEATS,
+            ..primer_config
+        };
+        assert!(!has_repeats("GGCAGCAGCAGCA", &primer_config));
+    }
+
+    #[test]
+    fn test_has_low_complexity_motif_boundary() {
+        // exactly four vs five AT units
+        assert!(!has_low_complexity_motif("ATATATAT", 2, 4));
+        assert!(has_low_complexity_motif("ATATATATAT", 2, 4));
+        assert!(!has_low_complexity_motif("GATATATATG", 2, 4));
+        assert!(has_low_complexity_motif("GATATATATATG", 2, 4));
+        // a trailing partial unit is not a repeat
+        assert!(!has_low_complexity_motif("ATATATATA", 2, 4));
+
+        assert!(!has_low_complexity_motif("AAAAA", 1, 5));
+        assert!(has_low_complexity_motif("AAAAAA", 1, 5));
+        assert!(!has_low_complexity_motif("CAGCAGCAG", 3, 3));
+        assert!(has_low_complexity_motif("CAGCAGCAGCAG", 3, 3));
+    }
+
+    #[test]
+    fn test_has_3prime_run() {
+        // a terminal run of 3 is flagged, the same run inside the primer passes
+        assert!(has_3prime_run("ACGTACGTACAAA", 2));
+        assert!(!has_3prime_run("ACGAAACGTACGC", 2));
+        assert!(!has_repeats(
+            "ACGTACGTACAAA",
+            &get_test_program_config().primer_config
+        ));
+        assert!(!has_3prime_run("ACGTACGTACGAA", 2));
+        assert!(!has_3prime_run("ACGTACGTACAAA", 0));
+        assert!(!has_3prime_run("", 2));
+    }
+
+    #[test]
+    fn test_has_gc_clamp() {
+        assert!(!has_gc_clamp("ACGTACGTACGAA", 2, 1));
+        assert!(has_gc_clamp("ACGTACGTACTGC", 2, 1));
+        assert!(has_gc_clamp("ACGTACGTACTGC", 2, 2));
+        assert!(!has_gc_clamp("ACGTACGTACTGA", 2, 2));
+        assert!(has_gc_clamp("ACGTACGTACGAA", 3, 1));
+        assert!(has_gc_clamp("GC", 5, 1));
+    }
+
+    #[test]
+    fn test_sequence_complexity() {
+        let random = sequence_complexity("AGCCCGTGTAAAC");
+        let repeats = sequence_complexity("ATGATGATGATGA");
+        assert!(random > 0.9);
+        assert!(repeats < 0.5);
+        assert!(sequence_complexity("AAAAAAAAAAAAA") < repeats);
+        assert_eq!(sequence_complexity(""), 0.0);
+    }
+
+    #[test]
+    fn test_tm_threshold_single_primer() {
+        let tm_values = vec![42.5];
+        let (mean, std) = get_tm_stat(&tm_values);
+        let margin = get_tm_margin(tm_values.len(), std, 2.0, 5.0);
+        assert_eq!(margin, 5.0);
+        assert!(tm_in_threshold(42.5, mean, margin));
+        assert!(!tm_in_threshold(48.0, mean, margin));
+
+        let tm_values = vec![40.0, 42.0, 44.0];
+        let (mean, std) = get_tm_stat(&tm_values);
+        let margin = get_tm_margin(tm_values.len(), std, 2.0, 5.0);
+        assert_eq!(margin, 4.0);
+        assert!(tm_in_threshold(44.0, mean, margin));
+    }
+
+    #[test]
+    fn test_is_tm_ok() {
+        // bounds of the test config are 30-60, the statistical margin alone passes all of these
+        let mut config = get_test_program_config();
+        assert!(!is_tm_ok(25.0, 25.0, 5.0, &config));
+        assert!(is_tm_ok(45.0, 45.0, 5.0, &config));
+        assert!(!is_tm_ok(65.0, 65.0, 5.0, &config));
+
+        // within bounds, but too far from the mean
+        assert!(!is_tm_ok(45.0, 55.0, 5.0, &config));
+        config.disable_tm_stddev = true;
+        assert!(is_tm_ok(45.0, 55.0, 5.0, &config));
+        assert!(!is_tm_ok(25.0, 55.0, 5.0, &config));
+    }
+
+    #[test]
+    fn test_tm_above_annealing() {
+        // annealing at 40°C with the 5°C margin of the test config, inside the 30-60 bounds
+        let mut config = get_test_program_config();
+        config.primer_config.annealing_temp = 40.0;
+        assert!(!tm_above_annealing(40.0, 40.0, 5.0));
+        assert!(!is_tm_ok(40.0, 40.0, 5.0, &config));
+        assert!(!is_tm_ok(44.0, 44.0, 5.0, &config));
+        assert!(is_tm_ok(45.0, 45.0, 5.0, &config));
+    }
+
+    fn get_test_segment_manager<'a>(
+        record: &'a SequenceRecord,
+        segment_kmers: &[&[&str]],
+    ) -> SegmentManager<'a> {
+        SegmentManager {
+            segments: segment_kmers
+                .iter()
+                .enumerate()
+                .map(|(idx, words)| Segment {
+                    sequence: record,
+                    partition_no: idx as u16,
+                    index: idx,
+                    start: 0,
+                    end: record.sequence.len(),
+                    kmers: [
+                        words
+                            .iter()
+                            .map(|w| KmerRecord {
+                                word: w.to_string(),
+                                direction: Direction::Forward,
+                                count: 1,
+                                strands: [true, false],
+                                canonical: None,
+                                weight: 1.0,
+                                conservation: 1.0,
+                            })
+                            .collect(),
+                        Vec::new(),
+                    ],
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_find_candidates_kmers_max_mismatch_segments() {
+        let record = SequenceRecord::new("seq1".to_string(), "ACTGAGGTATTA".to_string());
+        let manager = get_test_segment_manager(
+            &record,
+            &[&["ACT", "TTT"], &["ACT"], &["GAG"], &["GAG", "TTT"]],
+        );
+        let mut config = get_test_program_config();
+        config.max_iterations = 10;
+
+        // stop early, 2 segments are allowed to stay uncovered
+        config.max_mismatch_segments = 2;
+        let candidates =
+            find_candidates_kmers(&manager, Direction::Forward, config.clone()).unwrap();
+        assert_eq!(candidates.len(), 1);
+        assert_eq!(candidates[0].kmer.word, "ACT");
+
+        // cover every segment
+        config.max_mismatch_segments = 0;
+        let candidates =
+            find_candidates_kmers(&manager, Direction::Forward, config.clone()).unwrap();
+        let words: Vec<&str> = candidates.iter().map(|k| k.kmer.word.as_str()).collect();
+        assert_eq!(words, vec!["ACT", "GAG"]);
+    }
+
+    #[test]
+    fn test_find_candidates_kmers_target_coverage() {
+        let record = SequenceRecord::new("seq1".to_string(), "ACTGAGGTATTA".to_string());
+        let manager = get_test_segment_manager(
+            &record,
+            &[
+                &["ACT"],
+                &["ACT"],
+                &["ACT"],
+                &["GAG"],
+                &["GAG"],
+                &["TTA"],
+                &["TTA"],
+            ],
+        );
+        let mut config = get_test_program_config();
+        config.max_iterations = 10;
+
+        let candidates =
+            find_candidates_kmers(&manager, Direction::Forward, config.clone()).unwrap();
+        assert_eq!(candidates.len(), 3);
+
+        // ACT and GAG cover 5 of the 7 segments
+        config.target_coverage = 0.7;
+        let (candidates, reports) =
+            find_candidates_kmers_traced(&manager, Direction::Forward, config.clone());
+        let words: Vec<String> = candidates
+            .unwrap()
+            .iter()
+            .map(|k| k.kmer.word.clone())
+            .collect();
+        assert_eq!(words, vec!["ACT", "GAG"]);
+        assert_eq!(reports.last().unwrap().remaining, 2);
+        assert!(!is_selection_capped(
+            &reports,
+            Direction::Forward,
+            7,
+            &config
+        ));
+
+        assert!(is_target_coverage_reached(0, 7, 1.0));
+        assert!(!is_target_coverage_reached(1, 7, 1.0));
+        assert!(!is_target_coverage_reached(0, 0, 0.5));
+    }
+
+    #[test]
+    fn test_get_segment_coverage() {
+        let record = SequenceRecord::new("seq1".to_string(), "ACTGAGGTATTA".to_string());
+        let mut manager =
+            get_test_segment_manager(&record, &[&["ACT", "TTT"], &["ACT"], &["GAG"], &["CCC"]]);
+        for (segment, word) in manager
+            .segments
+            .iter_mut()
+            .zip(["TAA", "TAA", "TAA", "GGG"])
+        {
+            segment.kmers[1].push(KmerRecord {
+                word: word.to_string(),
+                direction: Direction::Reverse,
+                count: 1,
+                strands: [true, false],
+                canonical: None,
+                weight: 1.0,
+                conservation: 1.0,
+            });
+        }
+        // segment 2 lacks a forward primer, segment 3 lacks both
+        let candidate_primers = vec![
+            vec![get_test_kmer_stat("ACT", Direction::Forward)],
+            vec![get_test_kmer_stat("TAA", Direction::Reverse)],
+        ];
+
+        let coverage = get_segment_coverage(&manager.segments, &candidate_primers);
+        assert_eq!(coverage.covered, vec![0, 1]);
+        assert_eq!(coverage.uncovered, vec![2, 3]);
+        assert_eq!(coverage.percent(), 50.0);
+    }
+
+    #[test]
+    fn test_find_most_freq_kmer_tie_break() {
+        let seq_1 = SequenceRecord::new("seq1".to_string(), "ACTGAGGTATTA".to_string());
+        let kmers = |words: &[&str]| {
+            words
+                .iter()
+                .map(|w| KmerRecord {
+                    word: w.to_string(),
+                    direction: Direction::Forward,
+                    count: 1,
+                    strands: [true, false],
+                    canonical: None,
+                    weight: 1.0,
+                    conservation: 1.0,
+                })
+                .collect::<Vec<KmerRecord>>()
+        };
+        let manager: SegmentManager = SegmentManager {
+            segments: vec![
+                Segment {
+                    sequence: &seq_1,
+                    partition_no: 0,
+                    index: 0,
+                    start: 0,
+                    end: 12,
+                    kmers: [kmers(&["TGA", "GAG", "CTG", "ACT"]), Vec::new()],
+                },
+                Segment {
+                    sequence: &seq_1,
+                    partition_no: 1,
+                    index: 1,
+                    start: 0,
+                    end: 12,
+                    kmers: [kmers(&["ACT", "GAG", "CTG", "TGA"]), Vec::new()],
+                },
+            ],
+        };
+
+        for _ in 0..20 {
+            let kmer_freq = find_most_freq_kmer(
+                &manager.segments,
+                Direction::Forward,
+                &HashSet::new(),
+                0,
+                Strategy::MaxFrequency,
+            )
+            .unwrap();
+            assert_eq!(kmer_freq.kmer.word, "ACT");
+            assert_eq!(kmer_freq.frequency, 2);
+        }
+
+        let ignored = HashSet::from([0]);
+        for _ in 0..20 {
+            let kmer_freq = find_most_freq_kmer(
+                &manager.segments,
+                Direction::Forward,
+                &ignored,
+                0,
+                Strategy::MaxFrequency,
+            )
+            .unwrap();
+            assert_eq!(kmer_freq.kmer.word, "ACT");
+            assert_eq!(kmer_freq.frequency, 1);
+        }
+    }
+
+    #[test]
+    fn test_find_most_freq_kmer_max_mismatch() {
+        let record = SequenceRecord::new("seq1".to_string(), "ACTGAGGTATTA".to_string());
+        let manager = get_test_segment_manager(&record, &[&["ACGTA"], &["ACGTT"], &["GGGGG"]]);
+
+        // exact matching, every k-mer is only found once
+        let kmer_freq = find_most_freq_kmer(
+            &manager.segments,
+            Direction::Forward,
+            &HashSet::new(),
+            0,
+            Strategy::MaxFrequency,
+        )
+        .unwrap();
+        assert_eq!(kmer_freq.frequency, 1);
+
+        // the 1-mismatch variant ACGTT is counted as covered by ACGTA
+        let kmer_freq = find_most_freq_kmer(
+            &manager.segments,
+            Direction::Forward,
+            &HashSet::new(),
+            1,
+            Strategy::MaxFrequency,
+        )
+        .unwrap();
+        assert_eq!(kmer_freq.kmer.word, "ACGTA");
+        assert_eq!(kmer_freq.frequency, 2);
+
+        let mapping = make_kmer_segments_windows_mapping(&manager.segments, 1);
+        assert_eq!(mapping.get(kmer_freq.kmer).unwrap(), &vec![0, 1]);
+
+        let mut config = get_test_program_config();
+        config.max_iterations = 10;
+        config.max_mismatch = 1;
+        let candidates = find_candidates_kmers(&manager, Direction::Forward, config).unwrap();
+        let words: Vec<&str> = candidates.iter().map(|k| k.kmer.word.as_str()).collect();
+        assert_eq!(words, vec!["ACGTA"]);
+    }
+
+    #[test]
+    fn test_pair_primers() {
+        let short = SequenceRecord::new("short".to_string(), "ACGTAGGGGGGGGGGCCTTT".to_string());
+        let long = SequenceRecord::new("long".to_string(), format!("ACGTA{}CCTTT", "G".repeat(30)));
+        fn segment(record: &SequenceRecord, index: usize) -> Segment<'_> {
+            Segment {
+                sequence: record,
+                partition_no: 0,
+                index,
+                start: 0,
+                end: record.sequence.len(),
+                kmers: [
+                    vec![KmerRecord {
+                        word: "ACGTA".to_string(),
+                        direction: Direction::Forward,
+                        count: 1,
+                        strands: [true, false],
+                        canonical: None,
+                        weight: 1.0,
+                        conservation: 1.0,
+                    }],
+                    vec![KmerRecord {
+                        word: "AAAGG".to_string(),
+                        direction: Direction::Reverse,
+                        count: 1,
+                        strands: [true, false],
+                        canonical: None,
+                        weight: 1.0,
+                        conservation: 1.0,
+                    }],
+                ],
+            }
+        }
+        let segments = vec![segment(&short, 0), segment(&long, 1)];
+        let stat = |word: &str, direction: Direction| KmerStat {
+            frequency: 2,
+            ..get_test_kmer_stat(word, direction)
+        };
+        let stats = vec![
+            stat("ACGTA", Direction::Forward),
+            stat("AAAGG", Direction::Reverse),
+        ];
+
+        let pairs = pair_primers(&stats, &segments, 10, 100, 5.0);
+        let lengths: Vec<usize> = pairs.iter().map(|p| p.amplicon_length).collect();
+        assert_eq!(lengths, vec![20, 40]);
+
+        // the 40 bp amplicon of the long sequence is out of range
+        let pairs = pair_primers(&stats, &segments, 10, 30, 5.0);
+        assert_eq!(pairs.len(), 1);
+        assert_eq!(pairs[0].segment.sequence.name, "short");
+        assert_eq!(pairs[0].forward.word, "ACGTA");
+        assert_eq!(pairs[0].reverse.word, "AAAGG");
+
+        let pairs = pair_primers(&stats, &segments, 30, 100, 5.0);
+        assert_eq!(pairs.len(), 1);
+        assert_eq!(pairs[0].segment.sequence.name, "long");
+    }
+
+    #[test]
+    fn test_pair_primers_max_delta_tm() {
+        let record = SequenceRecord::new("seq1".to_string(), "ACGTAGGGGGGGGGGCCTTT".to_string());
+        let segments = vec![Segment {
+            sequence: &record,
+            partition_no: 0,
+            index: 0,
+            start: 0,
+            end: record.sequence.len(),
+            kmers: [
+                vec![
+                    KmerRecord {
+                        word: "ACGTA".to_string(),
+                        direction: Direction::Forward,
+                        count: 1,
+                        strands: [true, false],
+                        canonical: None,
+                        weight: 1.0,
+                        conservation: 1.0,
+                    },
+                    KmerRecord {
+                        word: "CGTAG".to_string(),
+                        direction: Direction::Forward,
+                        count: 1,
+                        strands: [true, false],
+                        canonical: None,
+                        weight: 1.0,
+                        conservation: 1.0,
+                    },
+                ],
+                vec![KmerRecord {
+                    word: "AAAGG".to_string(),
+                    direction: Direction::Reverse,
+                    count: 1,
+                    strands: [true, false],
+                    canonical: None,
+                    weight: 1.0,
+                    conservation: 1.0,
+                }],
+            ],
+        }];
+        let stat = |word: &str, direction: Direction, tm: f32| KmerStat {
+            tm,
+            tm_max: tm,
+            ..get_test_kmer_stat(word, direction)
+        };
+        let stats = vec![
+            stat("ACGTA", Direction::Forward, 52.0),
+            stat("CGTAG", Direction::Forward, 60.0),
+            stat("AAAGG", Direction::Reverse, 50.0),
+        ];
+
+        // CGTAG is 10 degrees away from the reverse primer
+        let pairs = pair_primers(&stats, &segments, 10, 100, 5.0);
+        assert_eq!(pairs.len(), 1);
+        assert_eq!(pairs[0].forward.word, "ACGTA");
+        assert_eq!(pairs[0].forward_tm, 52.0);
+        assert_eq!(pairs[0].reverse_tm, 50.0);
+        assert_eq!(pairs[0].delta_tm, 2.0);
+
+        let pairs = pair_primers(&stats, &segments, 10, 100, 10.0);
+        assert_eq!(pairs.len(), 2);
+    }
+
+    #[test]
+    fn test_get_tm_values_cache() {
+        let records: Vec<KmerRecord> = ["ACGTAGGCTTACG", "TTGCAGGCATCCA", "ACGTAGGCTTACG"]
+            .iter()
+            .map(|w| KmerRecord {
+                word: w.to_string(),
+                direction: Direction::Forward,
+                count: 1,
+                strands: [true, false],
+                canonical: None,
+                weight: 1.0,
+                conservation: 1.0,
+            })
+            .collect();
+        let kmer_records: Vec<KmerFrequency> = records
+            .iter()
+            .map(|kmer| KmerFrequency { kmer, frequency: 1 })
+            .collect();
+        let info = PrimerInfo::new();
+        let expansions: Vec<Vec<String>> = kmer_records
+            .iter()
+            .map(|k| iupac::expand(&k.kmer.word))
+            .collect();
+        let primer_infos = vec![vec![&info]; kmer_records.len()];
+
+        for tm_method in [
+            TmMethod::Nn,
+            TmMethod::Basic,
+            TmMethod::Wallace,
+            TmMethod::Auto,
+        ] {
+            let mut config = get_test_program_config();
+            config.tm_method = tm_method;
+            let cached = get_tm_values(&expansions, &primer_infos, &config);
+            let uncached: Vec<(f32, f32)> = records
+                .iter()
+                .map(|k| get_primer_tm(&k.word, &info, &config))
+                .map(|tm| (tm, tm))
+                .collect();
+            assert_eq!(cached, uncached);
+            assert_eq!(cached[0], cached[2]);
+        }
+    }
+
+    #[test]
+    fn test_soft_mask() {
+        let fasta = b">seq1\nACGTTGacguTGCAACGT\n".to_vec();
+
+        // uppercased by default, every k-mer is kept
+        let records = to_records(fasta.clone(), false).unwrap();
+        assert_eq!(records[0].sequence, "ACGTTGACGTTGCAACGT");
+        assert_eq!(find_kmers(&records[0].sequence, 4).len(), 11);
+
+        // k-mers overlapping the masked stretch are skipped
+        let records = to_records(fasta, true).unwrap();
+        assert_eq!(records[0].sequence, "ACGTTGacgtTGCAACGT");
+        let kmers = find_kmers(&records[0].sequence, 4);
+        assert_eq!(
+            kmers,
+            vec!["ACGT", "CGTT", "GTTG", "TGCA", "GCAA", "CAAC", "AACG"]
+        );
+    }
+
+    #[test]
+    fn test_count_occurrences() {
+        let kmers = find_kmers_counted("ACGACGACG", 3);
+        let expected = vec![
+            ("ACG".to_string(), 3),
+            ("CGA".to_string(), 2),
+            ("GAC".to_string(), 2),
+        ];
+        assert_eq!(kmers, expected);
+        assert_eq!(find_kmers("ACGACGACG", 3), vec!["ACG", "CGA", "GAC"]);
+
+        // ACG repeats 3 times in the window of seq1, TTT is shared by seq2 and seq3
+        let records = vec![
+            SequenceRecord::new("seq1".to_string(), "ACGACGACG".to_string()),
+            SequenceRecord::new("seq2".to_string(), "TTTGCCTGA".to_string()),
+            SequenceRecord::new("seq3".to_string(), "TTTCAGGCA".to_string()),
+        ];
+        let winner = |count_occurrences: bool| {
+            let opt = PartitioningOption {
+                segment_size: 9,
+                overlap_size: 9,
+                window_size: 9,
+                kmer_size: 3,
+                count_occurrences,
+                ..Default::default()
+            };
+            let manager = get_segment_manager(&records, opt).unwrap();
+            let kmer_freq = find_most_freq_kmer(
+                &manager.segments,
+                Direction::Forward,
+                &HashSet::new(),
+                0,
+                Strategy::MaxFrequency,
+            )
+            .unwrap();
+            (kmer_freq.kmer.word.clone(), kmer_freq.frequency)
+        };
+        assert_eq!(winner(false), ("TTT".to_string(), 2));
+        assert_eq!(winner(true), ("ACG".to_string(), 3));
+    }
+
+    #[test]
+    fn test_filter_frequency() {
+        assert_eq!(get_min_frequency(3.0, 10), 3);
+        assert_eq!(get_min_frequency(0.25, 10), 3);
+        assert_eq!(get_min_frequency(0.0, 10), 0);
+
+        let rare = KmerRecord {
+            word: "ACGTACGTACGTA".to_string(),
+            direction: Direction::Forward,
+            count: 1,
+            strands: [true, false],
+            canonical: None,
+            weight: 1.0,
+            conservation: 1.0,
+        };
+        let conserved = KmerRecord {
+            word: "TTGCATGCATGCA".to_string(),
+            direction: Direction::Forward,
+            count: 1,
+            strands: [true, false],
+            canonical: None,
+            weight: 1.0,
+            conservation: 1.0,
+        };
+        let kmers = vec![
+            KmerFrequency {
+                kmer: &rare,
+                frequency: 2,
+            },
+            KmerFrequency {
+                kmer: &conserved,
+                frequency: 9,
+            },
+        ];
+        let kmers = filter_frequency(kmers, get_min_frequency(0.5, 10));
+        assert_eq!(kmers.len(), 1);
+        assert_eq!(kmers[0].kmer.word, "TTGCATGCATGCA");
+    }
+
+    #[test]
+    fn test_dedupe_reverse_complements() {
+        let fwd = KmerRecord {
+            word: "AACCGGTTACGTA".to_string(),
+            direction: Direction::Forward,
+            count: 1,
+            strands: [true, false],
+            canonical: None,
+            weight: 1.0,
+            conservation: 1.0,
+        };
+        let other = KmerRecord {
+            word: "TTGCATGCATGCA".to_string(),
+            direction: Direction::Forward,
+            count: 1,
+            strands: [true, false],
+            canonical: None,
+            weight: 1.0,
+            conservation: 1.0,
+        };
+        let rev = KmerRecord {
+            word: reverse_complement("AACCGGTTACGTA", None),
+            direction: Direction::Reverse,
+            count: 1,
+            strands: [true, false],
+            canonical: None,
+            weight: 1.0,
+            conservation: 1.0,
+        };
+        let kmers = |fwd_frequency: usize, rev_frequency: usize| {
+            dedupe_reverse_complements(
+                vec![
+                    KmerFrequency {
+                        kmer: &fwd,
+                        frequency: fwd_frequency,
+                    },
+                    KmerFrequency {
+                        kmer: &other,
+                        frequency: 2,
+                    },
+                ],
+                vec![KmerFrequency {
+                    kmer: &rev,
+                    frequency: rev_frequency,
+                }],
+            )
+        };
+
+        // forward primer has the higher frequency, the reverse one is collapsed into it
+        let (kmers_fwd, kmers_rev) = kmers(5, 3);
+        assert_eq!(kmers_fwd.len(), 2);
+        assert!(kmers_rev.is_empty());
+        assert_eq!(kmers_fwd[0].kmer.word, "AACCGGTTACGTA");
+        assert_eq!(kmers_fwd[0].frequency, 8);
+
+        let (kmers_fwd, kmers_rev) = kmers(3, 5);
+        assert_eq!(kmers_fwd.len(), 1);
+        assert_eq!(kmers_fwd[0].kmer.word, "TTGCATGCATGCA");
+        assert_eq!(kmers_rev.len(), 1);
+        assert_eq!(kmers_rev[0].frequency, 8);
+    }
+
+    #[test]
+    fn test_filter_three_prime_dg() {
+        let stat = |word: &str| get_test_kmer_stat(word, Direction::Forward);
+        // GCGCG at the 3' end is -8.82 kcal/mol, below the -8.0 threshold of the test config
+        let stats = vec![stat("ATCAGTATGCGCG"), stat("ATCAGTATGCAAG")];
+        let primers = filter_kmers(stats, get_test_program_config());
+        assert_eq!(primers.len(), 1);
+        assert_eq!(primers[0].word, "ATCAGTATGCAAG");
+    }
+
+    #[test]
+    fn test_filter_self_fold_dg() {
+        let stat = |self_fold_dg: f32| KmerStat {
+            self_fold_dg,
+            ..get_test_kmer_stat("ATCAGTATGCAAG", Direction::Forward)
+        };
+        let config = get_test_program_config();
+        let primers = filter_kmers(vec![stat(-7.5), stat(-2.0)], config.clone());
+        assert_eq!(primers.len(), 1);
+        assert_eq!(primers[0].self_fold_dg, -2.0);
+
+        // no fold is rejected by default
+        let config = ProgramConfig {
+            primer_config: PrimerConfig {
+                min_self_fold_dg: constants::MIN_SELF_FOLD_DG,
+                ..config.primer_config
+            },
+            ..config
+        };
+        assert_eq!(filter_kmers(vec![stat(-30.0)], config).len(), 1);
+    }
+
+    #[test]
+    fn test_explain_rejections() {
+        let config = get_test_program_config();
+        let config = ProgramConfig {
+            primer_config: PrimerConfig {
+                min_gc: 35.0,
+                max_gc: 60.0,
+                ..config.primer_config
+            },
+            ..config
+        };
+        let stat = |word: &str, specificity_ok: bool| KmerStat {
+            gc_clamp: has_gc_clamp(
+                word,
+                config.primer_config.gc_clamp_length,
+                config.primer_config.min_gc_clamp,
+            ),
+            specificity_ok,
+            ..get_test_kmer_stat(word, Direction::Forward)
+        };
+        // 31% GC and no G or C in the last three bases
+        let gc_failing = stat("ATCAGTATGCATA", true);
+        assert_eq!(
+            get_failed_filters(&gc_failing, &config),
+            ["gc_low", "gc_clamp"]
+        );
+        // every failed criterion is reported, not only the first
+        let off_target = stat("ATCAGTATGCATA", false);
+        assert_eq!(
+            get_failed_filters(&off_target, &config),
+            ["gc_low", "gc_clamp", "specificity"]
+        );
+        // 77% GC
+        let gc_rich = stat("GCCAGCGTGCCAG", true);
+        assert_eq!(get_failed_filters(&gc_rich, &config), ["gc_high"]);
+        let passing = stat("ATCAGTATGCAAG", true);
+        assert!(get_failed_filters(&passing, &config).is_empty());
+
+        let rejections = vec![
+            output::Rejection::new(&gc_failing, get_failed_filters(&gc_failing, &config)),
+            output::Rejection::new(&off_target, get_failed_filters(&off_target, &config)),
+        ];
+        let mut buffer = Vec::new();
+        output::write_rejections(&mut buffer, &rejections).unwrap();
+        let output = String::from_utf8(buffer).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(
+            lines,
+            [
+                "direction\tword\tfrequency\ttm\tgc\treasons",
+                "F\tATCAGTATGCATA\t1\t60.00\t0.31\tgc_low,gc_clamp",
+                "F\tATCAGTATGCATA\t1\t60.00\t0.31\tgc_low,gc_clamp,specificity",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_sort_primers() {
+        let stat = |word: &str, frequency: usize, tm: f32| KmerStat {
+            frequency,
+            tm,
+            tm_max: tm,
+            ..get_test_kmer_stat(word, Direction::Forward)
+        };
+        let mut primers = vec![
+            stat("ACT", 2, 50.0),
+            stat("GGC", 5, 40.0),
+            stat("AAT", 2, 45.0),
+            stat("CAT", 9, 42.0),
+        ];
+        sort_primers(&mut primers, SortBy::Frequency, &[]);
+        let words: Vec<&str> = primers.iter().map(|p| p.word.as_str()).collect();
+        assert_eq!(words, vec!["CAT", "GGC", "AAT", "ACT"]);
+
+        sort_primers(&mut primers, SortBy::Tm, &[]);
+        let words: Vec<&str> = primers.iter().map(|p| p.word.as_str()).collect();
+        assert_eq!(words, vec!["ACT", "AAT", "CAT", "GGC"]);
+
+        // CAT binds the second segment only, the rest are unknown and go last
+        let record = SequenceRecord::new("seq1".to_string(), "A".to_string());
+        let mut manager = get_test_segment_manager(&record, &[&["ACT"], &["CAT"]]);
+        manager.segments[0].start = 100;
+        manager.segments[1].start = 50;
+        sort_primers(&mut primers, SortBy::Position, &manager.segments);
+        let words: Vec<&str> = primers.iter().map(|p| p.word.as_str()).collect();
+        assert_eq!(words, vec!["CAT", "ACT", "AAT", "GGC"]);
+    }
+
+    #[test]
+    fn test_check_uniform_length() {
+        let records = vec![
+            SequenceRecord::new("seq1".to_string(), "ACGTACGT".to_string()),
+            SequenceRecord::new("seq2".to_string(), "ACGT".to_string()),
+        ];
+        assert!(check_uniform_length(&records, false).is_ok());
+        assert!(matches!(
+            check_uniform_length(&records, true),
+            Err(DesignError::UnequalLengths { min: 4, max: 8 })
+        ));
+
+        let records = vec![
+            SequenceRecord::new("seq1".to_string(), "ACGT".to_string()),
+            SequenceRecord::new("seq2".to_string(), "AC-T".to_string()),
+        ];
+        assert!(check_uniform_length(&records, true).is_ok());
+        assert!(check_uniform_length(&[], true).is_ok());
+    }
+
+    #[test]
+    fn test_exclude_kmers() {
+        let excluded = parse_exclude_list("aaa\n\n  GCu \n", None);
+        let expected: HashSet<String> = ["AAA", "TTT", "GCT", "AGC"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        assert_eq!(excluded, expected);
+        // an inosine primer is excluded with the complement of the table
+        let table = HashMap::from([('I', 'C')]);
+        assert!(parse_exclude_list("AIG\n", Some(&table)).contains("CCT"));
+
+        // TTT is shared by all sequences and would win, it is excluded by its reverse complement
+        let records = vec![
+            SequenceRecord::new("seq1".to_string(), "TTTACGCAT".to_string()),
+            SequenceRecord::new("seq2".to_string(), "TTTGCCCAT".to_string()),
+            SequenceRecord::new("seq3".to_string(), "TTTCAGGCA".to_string()),
+        ];
+        let opt = PartitioningOption {
+            segment_size: 9,
+            overlap_size: 9,
+            window_size: 9,
+            kmer_size: 3,
+            ..Default::default()
+        };
+        let mut manager = get_segment_manager(&records, opt).unwrap();
+        // TTT from the forward windows and AAA from the reverse windows of every sequence
+        assert_eq!(exclude_kmers(&mut manager.segments, &excluded), 6);
+
+        let mut config = get_test_program_config();
+        config.max_iterations = 10;
+        let candidates = find_candidates_kmers(&manager, Direction::Forward, config).unwrap();
+        assert_eq!(candidates[0].kmer.word, "CAT");
+        assert!(candidates.iter().all(|k| !excluded.contains(&k.kmer.word)));
+    }
+
+    #[test]
+    fn test_find_candidates_kmers_traced() {
+        let record = SequenceRecord::new("seq1".to_string(), "A".to_string());
+        let manager = get_test_segment_manager(
+            &record,
+            &[
+                &["AAA", "CCC"],
+                &["AAA", "GGG"],
+                &["AAA"],
+                &["CCC", "GGG"],
+                &["GGG"],
+            ],
+        );
+        let mut config = get_test_program_config();
+        config.max_iterations = 10;
+        let (candidates, trace) =
+            find_candidates_kmers_traced(&manager, Direction::Forward, config);
+        let candidates = candidates.unwrap();
+        assert_eq!(trace.len(), candidates.len());
+
+        // AAA covers 3 segments, then GGG the remaining 2
+        let words: Vec<&str> = trace.iter().map(|r| r.word.as_str()).collect();
+        assert_eq!(words, vec!["AAA", "GGG"]);
+        assert_eq!(trace[0].iteration, 1);
+        assert_eq!(trace[0].frequency, 3);
+        assert_eq!(trace[0].newly_skipped, 3);
+        assert_eq!(trace[0].remaining, 2);
+        assert_eq!(trace[1].newly_skipped, 2);
+        assert_eq!(trace[1].remaining, 0);
+    }
+
+    #[test]
+    fn test_iter_candidates_kmers() {
+        let record = SequenceRecord::new("seq1".to_string(), "A".to_string());
+        let manager = get_test_segment_manager(
+            &record,
+            &[
+                &["AAA"],
+                &["AAA"],
+                &["AAA"],
+                &["CCC"],
+                &["CCC"],
+                &["GGG"],
+                &["GGG"],
+                &["TTT"],
+                &["TTT"],
+            ],
+        );
+        let mut config = get_test_program_config();
+        config.max_iterations = 10;
+        let mut candidates = iter_candidates_kmers(&manager, Direction::Forward, config.clone());
+        let words: Vec<String> = candidates
+            .by_ref()
+            .take(2)
+            .map(|k| k.kmer.word.clone())
+            .collect();
+        // the selection stops with the consumer, CCC ties GGG and TTT and wins by its word
+        assert_eq!(words, ["AAA", "CCC"]);
+        assert_eq!(candidates.reports().len(), 2);
+        assert_eq!(candidates.reports()[1].remaining, 4);
+
+        let all = find_candidates_kmers(&manager, Direction::Forward, config).unwrap();
+        assert_eq!(all.len(), 4);
+        assert_eq!(all[0].kmer.word, words[0]);
+        assert_eq!(all[1].kmer.word, words[1]);
+    }
+
+    #[test]
+    fn test_is_selection_capped() {
+        let record = SequenceRecord::new("seq1".to_string(), "A".to_string());
+        let manager = get_test_segment_manager(
+            &record,
+            &[&["AAA"], &["AAA"], &["CCC"], &["CCC"], &["GGG"], &["GGG"]],
+        );
+        let mut config = get_test_program_config();
+        config.max_iterations = 2;
+        let (_, trace) = find_candidates_kmers_traced(&manager, Direction::Forward, config.clone());
+        // the cap ends the selection with GGG left
+        assert_eq!(trace.len(), 2);
+        assert_eq!(trace[1].remaining, 2);
+        assert!(is_selection_capped(&trace, Direction::Forward, 6, &config));
+
+        config.max_iterations = 10;
+        let (_, trace) = find_candidates_kmers_traced(&manager, Direction::Forward, config.clone());
+        assert_eq!(trace.len(), 3);
+        assert!(!is_selection_capped(&trace, Direction::Forward, 6, &config));
+
+        // the last iteration covering everything is not capped
+        config.max_iterations = 3;
+        assert!(!is_selection_capped(&trace, Direction::Forward, 6, &config));
+    }
+
+    #[test]
+    fn test_sweep_kmer_sizes() {
+        // every sequence shares ACGT, only seq1 and seq2 share ACGTA
+        let records = vec![
+            SequenceRecord::new("seq1".to_string(), "ACGTAC".to_string()),
+            SequenceRecord::new("seq2".to_string(), "ACGTAG".to_string()),
+            SequenceRecord::new("seq3".to_string(), "ACGTTC".to_string()),
+        ];
+        let options = PartitioningOption {
+            segment_size: 6,
+            overlap_size: 6,
+            window_size: 6,
+            kmer_size: 0,
+            ..Default::default()
+        };
+        let mut config = get_test_program_config();
+        config.max_iterations = 10;
+        let reports =
+            sweep_kmer_sizes(&records, &options, &[4, 5], &HashSet::new(), &config).unwrap();
+        assert_eq!(reports.len(), 2);
+        assert_eq!(reports[0].kmer_size, 4);
+        assert_eq!(reports[0].coverage.covered.len(), 3);
+        assert_eq!(reports[1].kmer_size, 5);
+        assert_eq!(reports[1].coverage.covered.len(), 2);
+        assert_eq!(get_best_kmer_size(&reports), Some(4));
+        assert_eq!(get_best_kmer_size(&[]), None);
+    }
+
+    #[test]
+    fn test_remaining_segments_reverse_complement() {
+        assert_eq!(get_remaining_segments(3, 2), 1);
+        assert_eq!(get_remaining_segments(3, 3), 0);
+        assert_eq!(get_remaining_segments(3, 5), 0);
+
+        // ACGT is its own reverse complement, so it is found in the forward and reverse
+        // windows of every segment, the segments must still be counted once
+        let records = vec![
+            SequenceRecord::new("seq1".to_string(), "ACGTAC".to_string()),
+            SequenceRecord::new("seq2".to_string(), "ACGTAG".to_string()),
+        ];
+        let opt = PartitioningOption {
+            segment_size: 6,
+            overlap_size: 6,
+            window_size: 6,
+            kmer_size: 4,
+            ..Default::default()
+        };
+        let manager = get_segment_manager(&records, opt).unwrap();
+        let mut config = get_test_program_config();
+        config.max_iterations = 10;
+        for direction in Direction::ALL {
+            let (candidates, trace) =
+                find_candidates_kmers_traced(&manager, direction, config.clone());
+            assert_eq!(candidates.unwrap()[0].kmer.word, "ACGT");
+            assert_eq!(trace.len(), 1);
+            assert_eq!(trace[0].newly_skipped, 2);
+            assert_eq!(trace[0].remaining, 0);
+        }
+    }
+
+    #[test]
+    fn test_segment_kmer_counts() {
+        let records = vec![SequenceRecord::new(
+            "seq1".to_string(),
+            "ACGACGACG".to_string(),
+        )];
+        let counts = |count_occurrences: bool| {
+            let opt = PartitioningOption {
+                segment_size: 9,
+                overlap_size: 9,
+                window_size: 9,
+                kmer_size: 3,
+                count_occurrences,
+                ..Default::default()
+            };
+            let manager = get_segment_manager(&records, opt).unwrap();
+            manager.segments[0].kmers[0]
+                .iter()
+                .map(|k| (k.word.clone(), k.count))
+                .collect::<Vec<(String, usize)>>()
+        };
+        // deduplicated by default, every k-mer counts once per window
+        assert_eq!(
+            counts(false),
+            vec![
+                ("ACG".to_string(), 1),
+                ("CGA".to_string(), 1),
+                ("GAC".to_string(), 1),
+            ]
+        );
+        assert_eq!(
+            counts(true),
+            vec![
+                ("ACG".to_string(), 3),
+                ("CGA".to_string(), 2),
+                ("GAC".to_string(), 2),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_get_filter_summary() {
+        let stat = |word: &str, tm_ok: bool, runs: bool, gc_clamp: bool| KmerStat {
+            std: 1.5,
+            tm: 45.0,
+            tm_max: 45.0,
+            tm_ok,
+            runs,
+            gc_clamp,
+            ..get_test_kmer_stat(word, Direction::Forward)
+        };
+        let stats = vec![
+            stat("ATCAGTATGCAAG", true, false, true),
+            stat("ATCAGTATGCGCG", true, false, true),
+            stat("ATCAGTATGCAAG", false, true, true),
+            stat("ATCAGTATGCAAG", true, true, false),
+            stat("ATCAGTATGCAAG", true, false, false),
+        ];
+        let config = get_test_program_config();
+        let summary = get_filter_summary(Direction::Forward, &stats, &config);
+        let removed: HashMap<&str, usize> = summary.removed.iter().cloned().collect();
+        assert_eq!(removed["three_prime_dg"], 1);
+        // counted by the first failing filter only
+        assert_eq!(removed["tm"], 1);
+        assert_eq!(removed["runs"], 1);
+        assert_eq!(removed["gc_clamp"], 1);
+        assert_eq!(summary.passed, 1);
+        assert_eq!(summary.tm_mean, 45.0);
+        assert_eq!(summary.tm_margin, 3.0);
+
+        let total_removed: usize = summary.removed.iter().map(|(_, n)| n).sum();
+        let primers = filter_kmers(stats, config);
+        assert_eq!(total_removed, summary.candidates - primers.len());
+        assert_eq!(summary.passed, primers.len());
+    }
+
+    #[test]
+    fn test_degenerate_kmers() {
+        let records = vec![
+            SequenceRecord::new("seq1".to_string(), "ACATGCC".to_string()),
+            SequenceRecord::new("seq2".to_string(), "ACGTGCC".to_string()),
+        ];
+        let words = |max_degeneracy: usize, direction: Direction| {
+            let opt = PartitioningOption {
+                segment_size: 7,
+                overlap_size: 7,
+                window_size: 7,
+                kmer_size: 5,
+                max_degeneracy,
+                ..Default::default()
+            };
+            let manager = get_segment_manager(&records, opt).unwrap();
+            manager
+                .segments
+                .iter()
+                .map(|segment| {
+                    segment.kmers[direction as usize]
+                        .iter()
+                        .map(|k| k.word.clone())
+                        .collect::<Vec<String>>()
+                })
+                .collect::<Vec<_>>()
+        };
+        // the A/G position collapses to R, found in both segments
+        for segment in words(2, Direction::Forward) {
+            assert!(segment.contains(&"ACRTG".to_string()));
+        }
+        for segment in words(2, Direction::Reverse) {
+            assert!(segment.contains(&"CAYGT".to_string()));
+        }
+        assert!(
+            words(1, Direction::Forward)
+                .iter()
+                .flatten()
+                .all(|word| iupac::get_degeneracy(word) == 1)
+        );
+    }
+
+    #[test]
+    fn test_order_dimer_edges() {
+        let mut graph = graphdb::GraphDB::new();
+        for (a, b) in [("CCCC", "GGGG"), ("AAAA", "TTTT"), ("ACGT", "TGCA")] {
+            graph.add_node(a.to_string());
+            graph.add_node(b.to_string());
+            graph.add_edge(&a.to_string(), &b.to_string(), HashMap::new());
+        }
+        let ids =
+            |edges: &[&Edge]| -> Vec<String> { edges.iter().map(|e| e.get_id().clone()).collect() };
+        let mut edges: Vec<&Edge> = graph.edges.iter().collect();
+        order_dimer_edges(&mut edges, None, true);
+        assert_eq!(ids(&edges), ["AAAA:TTTT", "ACGT:TGCA", "CCCC:GGGG"]);
+
+        // a seed always gives the same order, whatever the order found
+        order_dimer_edges(&mut edges, Some(7), false);
+        let seeded = ids(&edges);
+        edges.reverse();
+        order_dimer_edges(&mut edges, Some(7), true);
+        assert_eq!(ids(&edges), seeded);
+        assert_eq!(seeded.iter().sorted().collect::<Vec<_>>().len(), 3);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_run_deterministic() {
+        use clap::Parser;
+        use std::os::unix::fs::PermissionsExt;
+        let dir =
+            std::env::temp_dir().join(format!("od-msspe-deterministic-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let stub = |name: &str, script: &str| {
+            let path = dir.join(name);
+            std::fs::write(&path, script).unwrap();
+            std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+            path.display().to_string()
+        };
+        // stand-ins for primer3 and ntthal, primers starting with A dimerize with ones starting
+        // with T
+        let primer3 = stub("primer3_core", "#!/bin/sh\ncat > /dev/null\n");
+        let ntthal = stub(
+            "ntthal",
+            "#!/bin/sh\nwhile read -r line || [ -n \"$line\" ]; do\n\
+             case \"$line\" in A*,T*) dg=-10000.00 ;; *) dg=0.00 ;; esac\n\
+             echo \"x x x x x x x x x x x x x $dg\"\nprintf '\\n\\n\\n'\ndone\n",
+        );
+        let records = testing::SyntheticGenomes {
+            records: 10,
+            length: 2000,
+            conserved: vec![0..300, 1000..1300],
+            mutation_rate: 0.03,
+            seed: 5,
+        }
+        .generate();
+        let input = dir.join("input.fasta");
+        let fasta: String = records
+            .iter()
+            .map(|r| format!(">{}\n{}\n", r.name, r.sequence))
+            .collect();
+        std::fs::write(&input, fasta).unwrap();
+
+        let run_to = |output: &str| {
+            let output = dir.join(output);
+            let mut args = Args::parse_from([
+                "od-msspe",
+                "--input",
+                &input.display().to_string(),
+                "--ntthal",
+                &ntthal,
+                "--primer3",
+                &primer3,
+                "--output",
+                &output.display().to_string(),
+            ]);
+            // flags of the same group can't be combined on the command line
+            args.do_align = "false".to_string();
+            args.check_cross_dimers = "true".to_string();
+            args.deterministic = "true".to_string();
+            run(args).unwrap();
+            std::fs::read(output).unwrap()
+        };
+        let first = run_to("first.csv");
+        let second = run_to("second.csv");
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!(String::from_utf8_lossy(&first).lines().count() > 1);
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn test_check_pool_tm() {
+        let stat = |word: &str, direction: Direction, tm: f32| KmerStat {
+            mean: 55.0,
+            tm,
+            tm_max: tm,
+            ..get_test_kmer_stat(word, direction)
+        };
+        assert!(check_pool_tm(&[Vec::new(), Vec::new()], 2.5).is_none());
+
+        let narrow = vec![
+            vec![stat("ACGT", Direction::Forward, 55.0)],
+            vec![stat("TGCA", Direction::Reverse, 56.0)],
+        ];
+        let pool_tm = check_pool_tm(&narrow, 2.5).unwrap();
+        assert_eq!(pool_tm.mean, 55.5);
+        assert!(pool_tm.ok);
+
+        // 45 to 65°C won't anneal at one temperature
+        let wide = vec![
+            vec![
+                stat("ACGT", Direction::Forward, 45.0),
+                stat("CCGT", Direction::Forward, 55.0),
+            ],
+            vec![stat("TGCA", Direction::Reverse, 65.0)],
+        ];
+        let pool_tm = check_pool_tm(&wide, 2.5).unwrap();
+        assert_eq!(pool_tm.mean, 55.0);
+        assert!(pool_tm.std > 2.5);
+        assert!(!pool_tm.ok);
+        // a single primer has no spread
+        let single = check_pool_tm(&wide[1..], 2.5).unwrap();
+        assert_eq!((single.std, single.ok), (0.0, true));
+    }
+
+    #[test]
+    fn test_collapse_primers() {
+        let stat = |word: &str, frequency: usize, tm: f32| KmerStat {
+            frequency,
+            tm,
+            tm_max: tm,
+            sequences: vec![format!("seq_{}", word)],
+            ..get_test_kmer_stat(word, Direction::Forward)
+        };
+        let record = SequenceRecord::new("seq1".to_string(), "ACGTACGT".to_string());
+        let manager = get_test_segment_manager(
+            &record,
+            &[
+                &["ACGTACGT", "ACGAACGT"],
+                &["ACGTACGT", "ACGCACGT"],
+                &["ACGGACGT"],
+            ],
+        );
+        let primers = vec![
+            stat("ACGTACGT", 10, 60.0),
+            stat("ACGAACGT", 5, 58.0),
+            stat("ACGCACGT", 3, 61.0),
+            // one mismatch as well, but binding another partition
+            stat("ACGGACGT", 2, 60.0),
+        ];
+
+        let collapsed = collapse_primers(primers.clone(), &manager.segments, 3);
+        let words: Vec<&str> = collapsed.iter().map(|p| p.word.as_str()).collect();
+        assert_eq!(words, ["ACGHACGT", "ACGGACGT"]);
+        let consensus = &collapsed[0];
+        assert_eq!(consensus.constituents, ["ACGTACGT", "ACGAACGT", "ACGCACGT"]);
+        assert_eq!(consensus.degeneracy, 3);
+        // the two segments covered, not the sum of the primers
+        assert_eq!(consensus.frequency, 2);
+        assert_eq!((consensus.tm, consensus.tm_max), (58.0, 61.0));
+        assert_eq!(consensus.sequences.len(), 3);
+        assert!(collapsed[1].constituents.is_empty());
+
+        // the third primer would exceed the degeneracy
+        let collapsed = collapse_primers(primers, &manager.segments, 2);
+        let words: Vec<&str> = collapsed.iter().map(|p| p.word.as_str()).collect();
+        assert_eq!(words, ["ACGWACGT", "ACGCACGT", "ACGGACGT"]);
+
+        // ACGWACGK also stands for ACGAACGT and ACGTACGG, which were never scored
+        let manager = get_test_segment_manager(&record, &[&["ACGTACGT", "ACGAACGG"]]);
+        let primers = vec![stat("ACGTACGT", 1, 60.0), stat("ACGAACGG", 1, 60.0)];
+        let collapsed = collapse_primers(primers, &manager.segments, 4);
+        let words: Vec<&str> = collapsed.iter().map(|p| p.word.as_str()).collect();
+        assert_eq!(words, ["ACGTACGT", "ACGAACGG"]);
+    }
+
+    #[test]
+    fn test_space_primers() {
+        let stat = |word: &str| get_test_kmer_stat(word, Direction::Forward);
+        let record =
+            SequenceRecord::new("seq1".to_string(), "AACCGGTTACGTACGTCCAATTGG".to_string());
+        let manager = get_test_segment_manager(&record, &[&["AACCGGTT", "ACCGGTTA", "CCAATTGG"]]);
+        // the second primer binds one base after the first
+        let primers = vec![stat("AACCGGTT"), stat("ACCGGTTA"), stat("CCAATTGG")];
+        let words = |primers: &[KmerStat]| {
+            primers
+                .iter()
+                .map(|p| p.word.clone())
+                .collect::<Vec<String>>()
+        };
+
+        let spaced = space_primers(primers.clone(), &manager.segments, 5);
+        assert_eq!(words(&spaced), ["AACCGGTT", "CCAATTGG"]);
+        let spaced = space_primers(primers.clone(), &manager.segments, 20);
+        assert_eq!(words(&spaced), ["AACCGGTT"]);
+        let spaced = space_primers(primers.clone(), &manager.segments, 0);
+        assert_eq!(words(&spaced), words(&primers));
+    }
+
+    #[test]
+    fn test_flag_near_masked() {
+        let stat = |word: &str| get_test_kmer_stat(word, Direction::Forward);
+        // the first primer ends right before a soft-masked stretch, the second is 16 bases away
+        let record = SequenceRecord::new(
+            "seq1".to_string(),
+            "ACGTACGTaaaaCCCCCCCCCCCCCCCCTTGCAGGA".to_string(),
+        );
+        let manager = get_test_segment_manager(&record, &[&["ACGTACGT", "TTGCAGGA"]]);
+        let near_masked = |soft_mask: bool, mask: Option<&[(usize, usize)]>, proximity: usize| {
+            let mut primers = vec![vec![stat("ACGTACGT"), stat("TTGCAGGA")]];
+            let flagged =
+                flag_near_masked(&mut primers, &manager.segments, soft_mask, mask, proximity);
+            let flags: Vec<bool> = primers[0].iter().map(|p| p.near_masked).collect();
+            assert_eq!(flagged, flags.iter().filter(|f| **f).count());
+            flags
+        };
+
+        assert_eq!(near_masked(true, None, 10), [true, false]);
+        assert_eq!(near_masked(true, None, 1), [true, false]);
+        assert_eq!(near_masked(true, None, 0), [false, false]);
+        // lowercase bases only count as masked with --soft-mask
+        assert_eq!(near_masked(false, None, 10), [false, false]);
+        // a --mask range two bases before the second primer
+        assert_eq!(near_masked(false, Some(&[(26, 27)]), 10), [false, true]);
+        assert_eq!(near_masked(false, Some(&[(26, 27)]), 2), [false, true]);
+        assert_eq!(near_masked(false, Some(&[(26, 27)]), 1), [false, false]);
+    }
+
+    #[test]
+    fn test_take_top_primers() {
+        let stat = |word: &str, direction: Direction, frequency: usize| KmerStat {
+            frequency,
+            ..get_test_kmer_stat(word, direction)
+        };
+        let candidate_primers = vec![
+            vec![
+                stat("AAAA", Direction::Forward, 9),
+                stat("CCCC", Direction::Forward, 8),
+                stat("GGGG", Direction::Forward, 7),
+            ],
+            vec![
+                stat("TTTT", Direction::Reverse, 3),
+                stat("ACAC", Direction::Reverse, 2),
+            ],
+        ];
+        let words = |primers: &[Vec<KmerStat>]| {
+            primers
+                .iter()
+                .map(|p| p.iter().map(|s| s.word.clone()).collect::<Vec<_>>())
+                .collect::<Vec<_>>()
+        };
+
+        // the most frequent primers are all forward
+        let top = take_top_primers(candidate_primers.clone(), 3, false, SortBy::Frequency, &[]);
+        assert_eq!(words(&top), vec![vec!["AAAA", "CCCC", "GGGG"], vec![]]);
+        // balanced, the directions share the primers
+        let top = take_top_primers(candidate_primers.clone(), 3, true, SortBy::Frequency, &[]);
+        assert_eq!(words(&top), vec![vec!["AAAA", "CCCC"], vec!["TTTT"]]);
+        let top = take_top_primers(candidate_primers.clone(), 10, true, SortBy::Frequency, &[]);
+        assert_eq!(top.iter().flatten().count(), 5);
+        // forward primers run out, the rest are reverse
+        let top = take_top_primers(candidate_primers.clone(), 5, true, SortBy::Frequency, &[]);
+        assert_eq!(top[0].len() + top[1].len(), 5);
+
+        let top = take_top_primers(candidate_primers, 4, true, SortBy::Frequency, &[]);
+        let taxonomy = output::Taxonomy {
+            species_name: "unknown".to_string(),
+            tax_id: "unknown".to_string(),
+        };
+        let mut buffer = Vec::new();
+        output::write_csv(&mut buffer, &top, &taxonomy).unwrap();
+        // header and exactly 4 primers
+        assert_eq!(String::from_utf8(buffer).unwrap().lines().count(), 5);
+    }
+
+    #[test]
+    fn test_balance_directions() {
+        let record = SequenceRecord::new("seq1".to_string(), "ACTGAGGTATTA".to_string());
+        let mut manager = get_test_segment_manager(
+            &record,
+            &[&["ACT"], &["ACT"], &["GAG"], &["GAG"], &["CCA"], &["CCA"]],
+        );
+        // skewed toward forward, one reverse primer covers every segment
+        for segment in manager.segments.iter_mut() {
+            segment.kmers[1].push(KmerRecord {
+                word: "TAA".to_string(),
+                direction: Direction::Reverse,
+                count: 1,
+                strands: [true, false],
+                canonical: None,
+                weight: 1.0,
+                conservation: 1.0,
+            });
+        }
+        let mut config = get_test_program_config();
+        config.max_iterations = 10;
+        config.max_mismatch_segments = 0;
+        let select = |max_ratio: f32| {
+            let fwd = find_candidates_kmers(&manager, Direction::Forward, config.clone()).unwrap();
+            let rev = find_candidates_kmers(&manager, Direction::Reverse, config.clone()).unwrap();
+            let (fwd, rev) = balance_directions(fwd, rev, max_ratio);
+            let words = |kmers: &[KmerFrequency]| {
+                kmers
+                    .iter()
+                    .map(|k| k.kmer.word.clone())
+                    .collect::<Vec<String>>()
+            };
+            (words(&fwd), words(&rev))
+        };
+
+        let (fwd, rev) = select(0.0);
+        assert_eq!(fwd, ["ACT", "CCA", "GAG"]);
+        assert_eq!(rev, ["TAA"]);
+
+        // the first selected primers are kept
+        let (fwd, rev) = select(2.0);
+        assert_eq!(fwd, ["ACT", "CCA"]);
+        assert_eq!(rev, ["TAA"]);
+
+        let (fwd, _) = select(1.0);
+        assert_eq!(fwd, ["ACT"]);
+
+        // balanced after the criteria, a rejected primer doesn't count toward the cap
+        let stat = |word: &str, direction: Direction, tm_ok: bool| KmerStat {
+            tm_ok,
+            ..get_test_kmer_stat(word, direction)
+        };
+        let fwd = vec![
+            stat("ATCAGTATGCAAG", Direction::Forward, true),
+            stat("ATCAGTATGCAAC", Direction::Forward, true),
+        ];
+        let rev = vec![
+            stat("CTTGCATACTGAT", Direction::Reverse, true),
+            stat("GTTGCATACTGAT", Direction::Reverse, false),
+        ];
+        let (fwd, rev) = balance_directions(
+            filter_kmers(fwd, config.clone()),
+            filter_kmers(rev, config.clone()),
+            1.0,
+        );
+        assert_eq!((fwd.len(), rev.len()), (1, 1));
+        assert_eq!(fwd[0].word, "ATCAGTATGCAAG");
+    }
+
+    /**
+     * The k-mers of `find_kmers_resolved` as they were found over `chars().ngrams()`
+     */
+    fn find_kmers_ngrams(sequence: &str, kmer_size: usize, max_n: usize) -> Vec<(String, usize)> {
+        use ngrams::Ngram;
+        let mut kmers: Vec<(String, usize)> = Vec::new();
+        let mut positions: HashMap<String, usize> = HashMap::new();
+        for kmer in sequence.chars().ngrams(kmer_size).filter(|kmer| {
+            kmer.iter().all(|c| "ATCGUN".contains(*c))
+                && kmer.iter().filter(|c| **c == 'N').count() <= max_n
+        }) {
+            let kmer: String = kmer.iter().collect();
+            for kmer in iupac::expand(&kmer) {
+                match positions.get(&kmer) {
+                    Some(&idx) => kmers[idx].1 += 1,
+                    None => {
+                        positions.insert(kmer.clone(), kmers.len());
+                        kmers.push((kmer, 1));
+                    }
+                }
+            }
+        }
+        kmers
+    }
+
+    #[test]
+    fn test_find_kmers_same_as_ngrams() {
+        let sequences = [
+            "AACCTTGGAACCTTG-",
+            "ACGACGACG",
+            "ACNTGNNACGUUacgtACG--TTNA",
+            "ACGTÅCGTACG ACGT",
+            "NNNNACGTNACGT",
+            "AC",
+            "",
+        ];
+        for sequence in sequences {
+            // ngrams panics on sequences shorter than the k-mer and pairs bases for a 1-mer
+            for kmer_size in (2..6).filter(|k| *k <= sequence.chars().count()) {
+                for max_n in 0..3 {
+                    assert_eq!(
+                        find_kmers_resolved(sequence, kmer_size, max_n),
+                        find_kmers_ngrams(sequence, kmer_size, max_n),
+                        "{} k={} max_n={}",
+                        sequence,
+                        kmer_size,
+                        max_n
+                    );
+                }
+            }
+        }
+        assert!(find_kmers_resolved("AC", 3, 0).is_empty());
+        assert!(find_kmers_resolved("", 3, 0).is_empty());
+        assert_eq!(find_kmers("ACA", 1), vec!["A", "C"]);
+        let records = testing::SyntheticGenomes {
+            records: 3,
+            length: 500,
+            conserved: vec![],
+            mutation_rate: 0.05,
+            seed: 1,
+        }
+        .generate();
+        for record in records.iter() {
+            assert_eq!(
+                find_kmers_resolved(&record.sequence, 13, 1),
+                find_kmers_ngrams(&record.sequence, 13, 1)
+            );
+        }
+    }
+
+    #[test]
+    fn test_find_kmers_with_n() {
+        // dropped by default, the N resolves to every base when tolerated
+        assert_eq!(find_kmers_resolved("ACNT", 3, 0), vec![]);
+        let kmers: Vec<String> = find_kmers_resolved("ACNT", 3, 1)
+            .into_iter()
+            .map(|(kmer, _)| kmer)
+            .collect();
+        assert_eq!(
+            kmers,
+            ["ACA", "ACC", "ACG", "ACT", "CAT", "CCT", "CGT", "CTT"]
+        );
+        assert!(find_kmers_resolved("ANNT", 3, 1).is_empty());
+
+        assert_eq!(config::parse_max_n("3"), Ok(3));
+        assert!(config::parse_max_n("4").is_err());
+        assert!(config::parse_max_n("-1").is_err());
+    }
+
+    #[test]
+    fn test_segment_with_n() {
+        let records = vec![
+            SequenceRecord::new("seq1".to_string(), "TTACGTAGGCTTT".to_string()),
+            SequenceRecord::new("seq2".to_string(), "TTACGNAGGCTTT".to_string()),
+        ];
+        let winner = |max_n: usize| {
+            let opt = PartitioningOption {
+                segment_size: 13,
+                overlap_size: 13,
+                window_size: 13,
+                kmer_size: 9,
+                max_n,
+                ..Default::default()
+            };
+            let manager = get_segment_manager(&records, opt).unwrap();
+            let kmer_freq = find_most_freq_kmer(
+                &manager.segments,
+                Direction::Forward,
+                &HashSet::new(),
+                0,
+                Strategy::MaxFrequency,
+            )
+            .unwrap();
+            (kmer_freq.kmer.word.clone(), kmer_freq.frequency)
+        };
+        // the N hides every k-mer of the window from seq2, so only k-mers away from it count
+        assert_eq!(winner(0).1, 1);
+        // as a wildcard, the N matches the T of seq1
+        assert_eq!(winner(1), ("ACGTAGGCT".to_string(), 2));
+    }
+
+    #[test]
+    fn test_find_most_freq_kmer_strategy() {
+        let record = SequenceRecord::new("seq1".to_string(), "ACTGAGGTATTA".to_string());
+        let mut manager = get_test_segment_manager(&record, &[&["ACT"], &["GAG"], &["GAG"]]);
+        // counted occurrences, ACT is repeated in its window
+        manager.segments[0].kmers[0][0].count = 3;
+        let winner = |strategy: Strategy| {
+            let kmer_freq = find_most_freq_kmer(
+                &manager.segments,
+                Direction::Forward,
+                &HashSet::new(),
+                0,
+                strategy,
+            )
+            .unwrap();
+            (kmer_freq.kmer.word.clone(), kmer_freq.frequency)
+        };
+        assert_eq!(winner(Strategy::MaxFrequency), ("ACT".to_string(), 3));
+        assert_eq!(winner(Strategy::MaxCoverage), ("GAG".to_string(), 2));
+    }
+
+    #[test]
+    fn test_get_supporting_sequences() {
+        let records = vec![
+            SequenceRecord::new("seq1".to_string(), "ACGTTGCAAACCGGTT".to_string()),
+            SequenceRecord::new("seq2".to_string(), "TTTTTGCAAACCGGTT".to_string()),
+            SequenceRecord::new("seq3".to_string(), "ACGTTGCAAACCGGTT".to_string()),
+        ];
+        let opt = PartitioningOption {
+            segment_size: 8,
+            overlap_size: 4,
+            window_size: 4,
+            kmer_size: 4,
+            ..Default::default()
+        };
+        let manager = get_segment_manager(&records, opt).unwrap();
+        // ACGT only starts the first and third genome
+        assert_eq!(
+            get_supporting_sequences(&manager.segments, "ACGT", Direction::Forward, 0),
+            vec!["seq1", "seq3"]
+        );
+        assert_eq!(
+            get_supporting_sequences(&manager.segments, "ACGT", Direction::Reverse, 0),
+            Vec::<String>::new()
+        );
+        // one substitution away from TTTT
+        assert_eq!(
+            get_supporting_sequences(&manager.segments, "ATTT", Direction::Forward, 1),
+            vec!["seq2"]
+        );
+    }
+
+    #[test]
+    fn test_check_primers_coverage() {
+        let primers = parse_check_primers(">fwd_F\nACGT\n>rev-REV desc\nAA\nCC\n").unwrap();
+        let parsed: Vec<(&str, &str, Direction)> = primers
+            .iter()
+            .map(|p| (p.name.as_str(), p.word.as_str(), p.direction))
+            .collect();
+        assert_eq!(
+            parsed,
+            [
+                ("fwd_F", "ACGT", Direction::Forward),
+                ("rev-REV", "AACC", Direction::Reverse)
+            ]
+        );
+        let csv = "direction,name,primers,gc\nF,,ACGT,0.50\nR,rev,aacc,0.50\n";
+        let parsed: Vec<(String, String, Direction)> = parse_check_primers(csv)
+            .unwrap()
+            .into_iter()
+            .map(|p| (p.name, p.word, p.direction))
+            .collect();
+        assert_eq!(
+            parsed,
+            [
+                (
+                    "Primer_0_F".to_string(),
+                    "ACGT".to_string(),
+                    Direction::Forward
+                ),
+                ("rev".to_string(), "AACC".to_string(), Direction::Reverse)
+            ]
+        );
+        assert!(matches!(
+            parse_check_primers("name,sequence\nfwd,ACGT\n"),
+            Err(DesignError::InvalidCheckPrimers(_))
+        ));
+
+        let records = vec![
+            SequenceRecord::new("seq1".to_string(), "ACGTTGCAAACCGGTT".to_string()),
+            SequenceRecord::new("seq2".to_string(), "TTTTTGCAAACCGGTT".to_string()),
+            SequenceRecord::new("seq3".to_string(), "ACGTTGCAAACCGGTT".to_string()),
+        ];
+        let opt = PartitioningOption {
+            segment_size: 16,
+            overlap_size: 16,
+            window_size: 4,
+            // segmented with the length of the primers instead
+            kmer_size: 13,
+            ..Default::default()
+        };
+        let coverage = get_primer_coverage(&records, &opt, &primers, 0, None).unwrap();
+        let covered: Vec<(usize, Vec<&str>)> = coverage
+            .iter()
+            .map(|c| (c.segments, c.sequences.iter().map(|s| s.as_str()).collect()))
+            .collect();
+        // the reverse primer binds the conserved end of every sequence
+        assert_eq!(
+            covered,
+            [(2, vec!["seq1", "seq3"]), (3, vec!["seq1", "seq2", "seq3"])]
+        );
+        // within one mismatch TTTT of seq2 is still not ACGT
+        let coverage = get_primer_coverage(&records, &opt, &primers[..1], 1, None).unwrap();
+        assert_eq!(coverage[0].segments, 2);
+    }
+
+    #[test]
+    fn test_mask() {
+        let bed = "track name=hotspots\n# recombination\nref\t0\t3\nref\t13\t16\tutr\n";
+        let mask = parse_mask(bed).unwrap();
+        assert_eq!(mask, [(0, 3), (13, 16)]);
+        assert!(matches!(
+            parse_mask("ref\t5\t5\n"),
+            Err(DesignError::InvalidMask(_))
+        ));
+        assert!(matches!(
+            parse_mask("ref\t5\n"),
+            Err(DesignError::InvalidMask(_))
+        ));
+
+        let records = vec![
+            SequenceRecord::new("seq1".to_string(), "ACGTACGTTTGGCCAA".to_string()),
+            SequenceRecord::new("seq2".to_string(), "ACGTACGTTTGGCCAA".to_string()),
+        ];
+        let segment_kmers = |mask: Option<Vec<(usize, usize)>>| {
+            let opt = PartitioningOption {
+                segment_size: 16,
+                overlap_size: 16,
+                window_size: 8,
+                kmer_size: 4,
+                mask,
+                ..Default::default()
+            };
+            let manager = get_segment_manager(&records, opt).unwrap();
+            let words = |d: usize| -> Vec<String> {
+                manager.segments[0].kmers[d]
+                    .iter()
+                    .map(|k| k.word.clone())
+                    .collect()
+            };
+            let winner = find_most_freq_kmer(
+                &manager.segments,
+                Direction::Forward,
+                &HashSet::new(),
+                0,
+                Strategy::MaxFrequency,
+            )
+            .map(|k| k.kmer.word.clone());
+            (words(0), words(1), winner)
+        };
+        let (forward, reverse, winner) = segment_kmers(None);
+        assert_eq!(forward, ["ACGT", "CGTA", "GTAC", "TACG"]);
+        assert_eq!(reverse.len(), 5);
+        assert_eq!(winner.as_deref(), Some("ACGT"));
+
+        // ACGT at 4..8 is left, but no k-mer starts in or overlaps 0..3
+        let (forward, reverse, winner) = segment_kmers(Some(mask));
+        assert_eq!(forward, ["TACG", "ACGT"]);
+        // the end window TTGGCCAA at 8..16 keeps TTGGC, stored reverse complemented
+        assert_eq!(reverse, ["CCAA", "GCCA"]);
+        assert_eq!(winner.as_deref(), Some("ACGT"));
+        let (forward, _, winner) = segment_kmers(Some(vec![(0, 8)]));
+        assert!(forward.is_empty());
+        assert_eq!(winner, None);
+    }
+
+    #[test]
+    fn test_sequence_weights() {
+        let weights = parse_sequence_weights("# lineages\nname\tweight\nseq3\t1.5\n").unwrap();
+        assert_eq!(weights, HashMap::from([("seq3".to_string(), 1.5)]));
+        assert!(matches!(
+            parse_sequence_weights("seq1\t1\nseq2\t-1\n"),
+            Err(DesignError::InvalidSequenceWeights(_))
+        ));
+        assert!(matches!(
+            parse_sequence_weights("seq1\n"),
+            Err(DesignError::InvalidSequenceWeights(_))
+        ));
+
+        let records = vec![
+            SequenceRecord::new("seq1".to_string(), "ACGTAAAA".to_string()),
+            SequenceRecord::new("seq2".to_string(), "ACGTCCCC".to_string()),
+            SequenceRecord::new("seq3".to_string(), "TGCAGGGG".to_string()),
+        ];
+        let winner = |weight: f32, max_mismatch: usize| {
+            let opt = PartitioningOption {
+                segment_size: 8,
+                overlap_size: 8,
+                window_size: 4,
+                kmer_size: 4,
+                weights: Some(HashMap::from([("seq3".to_string(), weight)])),
+                ..Default::default()
+            };
+            let manager = get_segment_manager(&records, opt).unwrap();
+            let observed = match max_mismatch {
+                0 => HashSet::new(),
+                _ => get_observed_kmers(&manager.segments),
+            };
+            let counts = count_kmers(
+                &manager.segments,
+                Direction::Forward,
+                &HashSet::new(),
+                &observed,
+                max_mismatch,
+            );
+            let kmer_freq = get_best_kmer(&counts, Strategy::MaxFrequency).unwrap();
+            (kmer_freq.kmer.word.clone(), kmer_freq.frequency)
+        };
+        // ACGT of the two unweighted sequences outweighs TGCA of seq3, the frequency stays
+        // the number of sequences
+        assert_eq!(winner(1.5, 0), ("ACGT".to_string(), 2));
+        // until the weight of seq3 doubles
+        assert_eq!(winner(3.0, 0), ("TGCA".to_string(), 1));
+        // the weights hold with mismatches too
+        assert_eq!(winner(3.0, 1), ("TGCA".to_string(), 1));
+    }
+
+    #[test]
+    fn test_unit_sequence_weights() {
+        let records = testing::SyntheticGenomes {
+            records: 10,
+            length: 1500,
+            conserved: vec![0..300, 700..900],
+            mutation_rate: 0.05,
+            seed: 5,
+        }
+        .generate();
+        let weights: HashMap<String, f32> = records.iter().map(|r| (r.name.clone(), 1.0)).collect();
+        let select = |weights: Option<HashMap<String, f32>>, max_mismatch: usize| {
+            let opt = PartitioningOption {
+                segment_size: 300,
+                overlap_size: 150,
+                kmer_size: 9,
+                weights,
+                ..Default::default()
+            };
+            let manager = get_segment_manager(&records, opt).unwrap();
+            let mut config = get_test_program_config();
+            config.max_iterations = 20;
+            config.max_mismatch = max_mismatch;
+            Direction::ALL
+                .into_iter()
+                .flat_map(|direction| {
+                    find_candidates_kmers(&manager, direction, config.clone()).unwrap_or_default()
+                })
+                .map(|k| (k.kmer.word.clone(), k.frequency))
+                .collect::<Vec<(String, usize)>>()
+        };
+        // weights of 1.0 select and report what an unweighted run does
+        for max_mismatch in [0, 1] {
+            let unweighted = select(None, max_mismatch);
+            assert!(!unweighted.is_empty());
+            assert_eq!(select(Some(weights.clone()), max_mismatch), unweighted);
+        }
+    }
+
+    #[test]
+    fn test_conservation_weights() {
+        let track = "# conservation of the consensus\nposition\tscore\n\
+                     1\t0.2\n2\t0.2\n3\t0.2\n4\t0.2\n5\t0.9\n6\t0.9\n7\t0.9\n8\t0.9\n\
+                     9\t0.9\n10\t0.9\n11\t0.9\n12\t0.9\n";
+        let conservation = parse_conservation(track).unwrap();
+        assert_eq!(conservation.len(), 12);
+        assert_eq!(conservation[0], 0.2);
+        assert!(matches!(
+            parse_conservation("1\t0.5\n2\t1.5\n"),
+            Err(DesignError::InvalidConservation(_))
+        ));
+        assert!(matches!(
+            parse_conservation("1\t0.5\nx\t0.5\n"),
+            Err(DesignError::InvalidConservation(_))
+        ));
+
+        let records = vec![SequenceRecord::new(
+            "consensus".to_string(),
+            "AAGCTTGCCATG".to_string(),
+        )];
+        let opt = PartitioningOption {
+            segment_size: 12,
+            overlap_size: 12,
+            window_size: 8,
+            kmer_size: 4,
+            conservation: Some(conservation),
+            ..Default::default()
+        };
+        let manager = get_segment_manager(&records, opt).unwrap();
+        let scores: Vec<(&str, usize, f32)> = manager.segments[0].kmers[0]
+            .iter()
+            .map(|k| (k.word.as_str(), k.count, k.conservation))
+            .collect();
+        // k-mers overlapping the variable first 4 bases are weighted down, counts stay
+        // occurrences
+        assert_eq!(
+            scores,
+            [
+                ("AAGC", 1, 0.2),
+                ("AGCT", 1, 0.2),
+                ("GCTT", 1, 0.2),
+                ("CTTG", 1, 0.2),
+                ("TTGC", 1, 0.9)
+            ]
+        );
+        let kmer_freq = find_most_freq_kmer(
+            &manager.segments,
+            Direction::Forward,
+            &HashSet::new(),
+            0,
+            Strategy::MaxFrequency,
+        )
+        .unwrap();
+        assert_eq!(kmer_freq.kmer.word, "TTGC");
+        assert_eq!(kmer_freq.frequency, 1);
+    }
+
+    #[test]
+    fn test_conservation_min_frequency() {
+        // the variable first 4 bases of the alignment differ between the sequences
+        let track = "1\t0.2\n2\t0.2\n3\t0.2\n4\t0.2\n5\t0.9\n6\t0.9\n7\t0.9\n8\t0.9\n";
+        let records = vec![
+            SequenceRecord::new("seq1".to_string(), "AAGCTTGC".to_string()),
+            SequenceRecord::new("seq2".to_string(), "CCATTTGC".to_string()),
+        ];
+        let opt = PartitioningOption {
+            segment_size: 8,
+            overlap_size: 8,
+            window_size: 8,
+            kmer_size: 4,
+            conservation: Some(parse_conservation(track).unwrap()),
+            ..Default::default()
+        };
+        let manager = get_segment_manager(&records, opt).unwrap();
+        let mut config = get_test_program_config();
+        config.max_iterations = 10;
+        let candidates = find_candidates_kmers(&manager, Direction::Forward, config).unwrap();
+        // the conserved k-mer shared by both sequences is selected first with its
+        // occurrences as frequency
+        assert_eq!(candidates[0].kmer.word, "TTGC");
+        assert_eq!(candidates[0].frequency, 2);
+        // and is the only one found in both sequences
+        let kept = filter_frequency(candidates, get_min_frequency(2.0, records.len()));
+        let kept: Vec<(&str, usize)> = kept
+            .iter()
+            .map(|k| (k.kmer.word.as_str(), k.frequency))
+            .collect();
+        assert_eq!(kept, [("TTGC", 2)]);
+    }
+
+    #[test]
+    fn test_canonical_kmers() {
+        // the second genome is the reverse complement of the first
+        let records = vec![
+            SequenceRecord::new("seq1".to_string(), "ACCTGAAT".to_string()),
+            SequenceRecord::new("seq2".to_string(), "ATTCAGGT".to_string()),
+        ];
+        let winner = |canonical: bool| {
+            let opt = PartitioningOption {
+                segment_size: 8,
+                overlap_size: 8,
+                window_size: 8,
+                kmer_size: 4,
+                canonical,
+                ..Default::default()
+            };
+            let manager = get_segment_manager(&records, opt).unwrap();
+            let observed: Vec<Option<String>> = manager
+                .segments
+                .iter()
+                .map(|s| {
+                    s.kmers[0]
+                        .iter()
+                        .find(|k| k.key() == "ACCT")
+                        .map(|k| k.word.clone())
+                })
+                .collect();
+            let kmer_freq = find_most_freq_kmer(
+                &manager.segments,
+                Direction::Forward,
+                &HashSet::new(),
+                0,
+                Strategy::MaxFrequency,
+            )
+            .unwrap();
+            (kmer_freq.kmer.word.clone(), kmer_freq.frequency, observed)
+        };
+        // both orientations of the same site add up, each segment keeps the word it holds
+        assert_eq!(
+            winner(true),
+            (
+                "ACCT".to_string(),
+                2,
+                vec![Some("ACCT".to_string()), Some("AGGT".to_string())]
+            )
+        );
+        assert_eq!(winner(false).1, 1);
+
+        let kmer = |word: &str| KmerRecord {
+            word: word.to_string(),
+            direction: Direction::Forward,
+            count: 1,
+            strands: [true, false],
+            canonical: None,
+            weight: 1.0,
+            conservation: 1.0,
+        };
+        let canonical = canonicalize_kmers(vec![kmer("CCGT"), kmer("ACGG"), kmer("ACGT")], true);
+        let words: Vec<(&str, &str, usize, [bool; 2])> = canonical
+            .iter()
+            .map(|k| (k.word.as_str(), k.key(), k.count, k.strands))
+            .collect();
+        // the word first observed is kept, keyed by its canonical k-mer
+        assert_eq!(
+            words,
+            vec![
+                ("CCGT", "ACGG", 2, [true, true]),
+                ("ACGT", "ACGT", 1, [true, false])
+            ]
+        );
+        assert_eq!(
+            canonicalize_kmers(vec![kmer("CCGT"), kmer("ACGG")], false)[0].count,
+            1
+        );
+    }
+
+    #[test]
+    fn test_canonical_tiling() {
+        // the canonical k-mer ACCT of the winner is only found in the second genome
+        let records = vec![
+            SequenceRecord::new("seq1".to_string(), "AGGTCAAT".to_string()),
+            SequenceRecord::new("seq2".to_string(), "ATTGACCT".to_string()),
+        ];
+        let opt = PartitioningOption {
+            segment_size: 8,
+            overlap_size: 8,
+            window_size: 8,
+            kmer_size: 4,
+            canonical: true,
+            ..Default::default()
+        };
+        let manager = get_segment_manager(&records, opt).unwrap();
+        let kmer_freq = find_most_freq_kmer(
+            &manager.segments,
+            Direction::Forward,
+            &HashSet::new(),
+            0,
+            Strategy::MaxFrequency,
+        )
+        .unwrap();
+        assert_eq!(
+            (kmer_freq.kmer.word.as_str(), kmer_freq.frequency),
+            ("AGGT", 2)
+        );
+        let candidate_primers = vec![
+            vec![get_test_kmer_stat(&kmer_freq.kmer.word, Direction::Forward)],
+            vec![],
+        ];
+        let tiling = get_tiling(&candidate_primers, &manager.segments);
+        let rows: Vec<(usize, usize, usize)> = tiling
+            .iter()
+            .map(|i| (i.segment.index, i.start, i.end))
+            .collect();
+        assert_eq!(rows, [(0, 0, 4)]);
+    }
+
+    #[test]
+    fn test_get_self_binding_count() {
+        let records = vec![
+            SequenceRecord::new("seq1".to_string(), "ACCGTACCGTTTTTTT".to_string()),
+            SequenceRecord::new("seq2".to_string(), "ACCGTTTTTTTTTTTT".to_string()),
+        ];
+        let opt = PartitioningOption {
+            segment_size: 8,
+            overlap_size: 4,
+            window_size: 4,
+            kmer_size: 4,
+            ..Default::default()
+        };
+        let manager = get_segment_manager(&records, opt).unwrap();
+        let site_counts = get_site_counts(&manager.segments, 4);
+        let count = |word: &str, direction: Direction, max_mismatch: usize| {
+            get_self_binding_count(
+                &manager.segments,
+                &site_counts,
+                word,
+                direction,
+                max_mismatch,
+                None,
+            )
+        };
+        // ACCG starts both genomes and appears again mid-sequence in the first
+        assert_eq!(count("ACCG", Direction::Forward, 0), 1);
+        assert_eq!(count("ACCC", Direction::Forward, 1), 1);
+        // no end window holds ACCG, every site is spurious for the reverse primer
+        assert_eq!(count("CGGT", Direction::Reverse, 0), 3);
+        // a palindrome is the same site on both strands
+        assert_eq!(count("GTAC", Direction::Forward, 0), 1);
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn test_count_kmers_parallel() {
+        let records = testing::SyntheticGenomes {
+            records: 20,
+            length: 2000,
+            conserved: vec![0..200, 900..1100],
+            mutation_rate: 0.05,
+            seed: 3,
+        }
+        .generate();
+        let opt = PartitioningOption {
+            segment_size: 200,
+            overlap_size: 100,
+            kmer_size: 9,
+            count_occurrences: true,
+            ..Default::default()
+        };
+        let manager = get_segment_manager(&records, opt).unwrap();
+        let ignored: HashSet<u32> = (0..manager.segments.len() as u32).step_by(3).collect();
+        for max_mismatch in [0, 1] {
+            let observed = match max_mismatch {
+                0 => HashSet::new(),
+                _ => get_observed_kmers(&manager.segments),
+            };
+            for direction in Direction::ALL {
+                let args = (
+                    &manager.segments,
+                    direction,
+                    &ignored,
+                    &observed,
+                    max_mismatch,
+                );
+                let serial = count_kmers_serial(args.0, args.1, args.2, args.3, args.4);
+                let parallel = count_kmers(args.0, args.1, args.2, args.3, args.4);
+                assert!(serial == parallel);
+                for strategy in [Strategy::MaxFrequency, Strategy::MaxCoverage] {
+                    let winner = |counts: &KmerCounts| {
+                        get_best_kmer(counts, strategy).map(|k| (k.kmer.word.clone(), k.frequency))
+                    };
+                    assert_eq!(winner(&serial), winner(&parallel));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_segment_region() {
+        let records = testing::SyntheticGenomes {
+            records: 10,
+            length: 1200,
+            conserved: vec![0..600, 600..1200],
+            mutation_rate: 0.0,
+            seed: 11,
+        }
+        .generate();
+        let opt = PartitioningOption {
+            segment_size: 200,
+            overlap_size: 100,
+            region: Some((300, 700)),
+            ..Default::default()
+        };
+        let manager = get_segment_manager(&records, opt.clone()).unwrap();
+        assert!(!manager.segments.is_empty());
+        assert!(
+            manager
+                .segments
+                .iter()
+                .all(|s| s.start >= 300 && s.end <= 700)
+        );
+        assert_eq!(manager.segments[0].start, 300);
+
+        let mut config = get_test_program_config();
+        config.max_iterations = 100;
+        let region = &records[0].sequence[300..700];
+        for direction in Direction::ALL {
+            let candidates = find_candidates_kmers(&manager, direction, config.clone()).unwrap();
+            for kmer_freq in candidates {
+                let word = match direction {
+                    Direction::Forward => kmer_freq.kmer.word.clone(),
+                    Direction::Reverse => reverse_complement(&kmer_freq.kmer.word, None),
+                };
+                assert!(region.contains(&word), "{} outside the region", word);
+            }
+        }
+
+        // a region past the end of the sequences has no segments
+        let opt = PartitioningOption {
+            region: Some((2000, 3000)),
+            ..opt
+        };
+        assert!(
+            get_segment_manager(&records, opt)
+                .unwrap()
+                .segments
+                .is_empty()
+        );
+
+        assert_eq!(config::parse_region("300:700"), Ok((300, 700)));
+        assert!(config::parse_region("700:300").is_err());
+        assert!(config::parse_region("300").is_err());
+    }
+
+    #[test]
+    fn test_dimer_matrix() {
+        let stat = |word: &str| get_test_kmer_stat(word, Direction::Forward);
+        let primers = vec![stat("AAAAAAAAAA"), stat("GCGCGGAAAA"), stat("TTTTCCGCGC")];
+        let matrix = dimer_matrix(&primers);
+        for (i, row) in matrix.iter().enumerate() {
+            for (j, dg) in row.iter().enumerate() {
+                assert_eq!(*dg, matrix[j][i]);
+            }
+        }
+        // the second and third primers are reverse complements
+        assert!((matrix[1][2] - -14.96).abs() < 1e-4);
+        let strongest = matrix.iter().flatten().fold(0.0f32, |a, b| a.min(*b));
+        assert_eq!(strongest, matrix[1][2]);
+        assert_eq!(matrix[0][0], 0.0);
+
+        let names = vec!["A".to_string(), "B".to_string(), "C".to_string()];
+        let mut buffer = Vec::new();
+        output::write_dimer_matrix(&mut buffer, &names, &matrix).unwrap();
+        let output = String::from_utf8(buffer).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines[0], "primer\tA\tB\tC");
+        assert!(lines[2].starts_with("B\t"));
+        assert!(lines[2].ends_with("\t-14.96"));
+    }
+
+    #[test]
+    fn test_get_tiling() {
+        let records = vec![SequenceRecord::new(
+            "seq1".to_string(),
+            "ACGTTGCAAACCGGTT".to_string(),
+        )];
+        let opt = PartitioningOption {
+            segment_size: 8,
+            overlap_size: 4,
+            window_size: 4,
+            kmer_size: 4,
+            ..Default::default()
+        };
+        let manager = get_segment_manager(&records, opt).unwrap();
+        // the end window of segment 0 is the start window of segment 1
+        let candidate_primers = vec![
+            vec![
+                get_test_kmer_stat("ACGT", Direction::Forward),
+                get_test_kmer_stat("TGCA", Direction::Forward),
+            ],
+            vec![get_test_kmer_stat("TGCA", Direction::Reverse)],
+        ];
+        let tiling = get_tiling(&candidate_primers, &manager.segments);
+        let rows: Vec<(usize, Direction, usize, usize, &str)> = tiling
+            .iter()
+            .map(|i| {
+                (
+                    i.segment.index,
+                    i.direction,
+                    i.start,
+                    i.end,
+                    i.name.as_str(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            rows,
+            [
+                (0, Direction::Forward, 0, 4, "Primer_0_F"),
+                (0, Direction::Reverse, 4, 8, "Primer_0_R"),
+                (1, Direction::Forward, 4, 8, "Primer_1_F"),
+            ]
+        );
+
+        let mut buffer = Vec::new();
+        output::write_tiling(&mut buffer, &tiling).unwrap();
+        let output = String::from_utf8(buffer).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines[0], "segment\tsequence\tstart\tend\tstrand\tname");
+        assert_eq!(lines[2], "0\tseq1\t4\t8\t-\tPrimer_0_R");
+
+        assert_eq!(get_ungapped_position("AC--GT", 5), 3);
+    }
+
+    #[test]
+    fn test_name_by_position() {
+        let records = vec![SequenceRecord::new(
+            "seq1".to_string(),
+            "ACGTTGCAAACCGGTT".to_string(),
+        )];
+        let opt = PartitioningOption {
+            segment_size: 8,
+            overlap_size: 4,
+            window_size: 4,
+            kmer_size: 4,
+            ..Default::default()
+        };
+        let manager = get_segment_manager(&records, opt).unwrap();
+        // GGGG binds nowhere and keeps its index name
+        let mut candidate_primers = vec![
+            vec![
+                get_test_kmer_stat("ACGT", Direction::Forward),
+                get_test_kmer_stat("TGCA", Direction::Forward),
+                get_test_kmer_stat("GGGG", Direction::Forward),
+            ],
+            vec![get_test_kmer_stat("TGCA", Direction::Reverse)],
+        ];
+        name_by_position(&mut candidate_primers, &manager.segments);
+        let names: Vec<String> = candidate_primers
+            .iter()
+            .flat_map(|p| p.iter().enumerate())
+            .map(|(idx, primer)| output::get_primer_name(primer, idx))
+            .collect();
+        assert_eq!(names, ["seq1_0_F", "seq1_4_F", "Primer_2_F", "seq1_4_R"]);
+    }
+
+    #[test]
+    fn test_synthetic_design() {
+        // the search windows of every partition fall in a conserved region
+        let records = testing::SyntheticGenomes {
+            records: 30,
+            length: 3000,
+            conserved: (0..=12)
+                .map(|k| (k * 250usize).saturating_sub(50)..k * 250 + 50)
+                .collect(),
+            mutation_rate: 0.02,
+            seed: 42,
+        }
+        .generate();
+        let opt = PartitioningOption::default();
+        let manager = get_segment_manager(&records, opt).unwrap();
+        let mut config = get_test_program_config();
+        config.max_iterations = 1000;
+        config.max_mismatch_segments = 0;
+        config.primer_config.kmer_size = 13;
+
+        let candidates: Vec<KmerFrequency> = Direction::ALL
+            .into_iter()
+            .flat_map(|d| find_candidates_kmers(&manager, d, config.clone()).unwrap_or_default())
+            .collect();
+        assert!(candidates.iter().all(|k| k.frequency > 1));
+        let primers: HashSet<(&str, Direction)> = candidates
+            .iter()
+            .map(|k| (k.kmer.word.as_str(), k.kmer.direction))
+            .collect();
+        let coverage = get_covered_segments(&manager.segments, &primers);
+        assert!(coverage.percent() > 95.0, "coverage {}", coverage.percent());
+
+        let expansions: Vec<Vec<String>> = candidates
+            .iter()
+            .map(|k| iupac::expand(&k.kmer.word))
+            .collect();
+        let info = PrimerInfo::new();
+        let primer_infos = vec![vec![&info]; candidates.len()];
+        let stats = build_kmer_stats(
+            &candidates,
+            &expansions,
+            primer_infos,
+            &HashSet::new(),
+            &config,
+        );
+        let kept = filter_kmers(stats, config.clone());
+        assert!(!kept.is_empty());
+        let primer_config = &config.primer_config;
+        for stat in kept {
+            assert!(stat.tm_ok);
+            assert!(tm_in_bounds(
+                stat.tm,
+                primer_config.min_tm,
+                primer_config.max_tm
+            ));
+            assert_eq!(stat.word.len(), 13);
+        }
+    }
+
+    #[test]
+    fn test_design() {
+        let records = testing::SyntheticGenomes {
+            records: 20,
+            length: 1500,
+            conserved: (0..=6)
+                .map(|k| (k * 250usize).saturating_sub(50)..k * 250 + 50)
+                .collect(),
+            mutation_rate: 0.02,
+            seed: 7,
+        }
+        .generate();
+        let mut program_config = get_test_program_config();
+        program_config.max_iterations = 1000;
+        program_config.primer_config.kmer_size = 13;
+        // cat echoes the primer3 input, every primer is checked with empty primer3 results
+        program_config.primer3_path = "cat".to_string();
+        let params = DesignParams {
+            partitioning: PartitioningOption::default(),
+            program_config,
+        };
+
+        let primers = design(&records, &params).unwrap();
+        assert!(!primers.is_empty());
+        assert!(primers.iter().any(|p| p.direction == Direction::Forward));
+        assert!(primers.iter().any(|p| p.direction == Direction::Reverse));
+        for primer in &primers {
+            assert_eq!(get_failed_filter(primer, &params.program_config), None);
+            assert!(primer.frequency > 1);
+            assert!(!primer.sequences.is_empty());
+        }
+    }
+
+    #[test]
+    fn test_build_kmer_stats_degenerate() {
+        let kmer = KmerRecord {
+            word: "ACGTACGTACGSR".to_string(),
+            direction: Direction::Forward,
+            count: 1,
+            strands: [true, false],
+            canonical: None,
+            weight: 1.0,
+            conservation: 1.0,
+        };
+        let candidates = vec![KmerFrequency {
+            kmer: &kmer,
+            frequency: 2,
+        }];
+        let expansions = vec![iupac::expand(&kmer.word)];
+        let info = PrimerInfo::new();
+        let primer_infos = vec![vec![&info; expansions[0].len()]];
+        let config = get_test_program_config();
+        let stats = build_kmer_stats(
+            &candidates,
+            &expansions,
+            primer_infos,
+            &HashSet::new(),
+            &config,
+        );
+
+        let stat = &stats[0];
+        assert_eq!(stat.degeneracy, 4);
+        // S is C or G, always GC, R is A or G
+        assert_eq!(stat.gc_min, get_gc_percent("ACGTACGTACGCA"));
+        assert_eq!(stat.gc_max, get_gc_percent("ACGTACGTACGCG"));
+        assert_eq!(stat.gc_percent, (stat.gc_min + stat.gc_max) / 2.0);
+        assert!(stat.tm < stat.tm_max);
+    }
+}
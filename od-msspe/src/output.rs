@@ -0,0 +1,1013 @@
+use crate::config::{Alphabet, Args, Column, DEFAULT_COLUMNS};
+use crate::constants::STDIO_PATH;
+use crate::{Direction, IterationReport, KmerStat, PrimerPair, TilingInterval};
+use clap::ValueEnum;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::fs::File;
+use std::io::{self, Write};
+
+/// Name of the primer, by its index within its direction, e.g. `Primer_0_F`.
+pub fn primer_name(idx: usize, direction: Direction) -> String {
+    format!("Primer_{}_{}", idx, direction.label())
+}
+
+/// Name of the primer in the outputs, its `--name-by-position` name when it has one.
+pub fn get_primer_name(primer: &KmerStat, idx: usize) -> String {
+    match &primer.name {
+        Some(name) => name.clone(),
+        None => primer_name(idx, primer.direction),
+    }
+}
+
+/// Render a primer word in the requested alphabet, words are always DNA internally.
+pub fn convert_alphabet(word: &str, alphabet: Alphabet) -> String {
+    match alphabet {
+        Alphabet::Dna => word.to_string(),
+        Alphabet::Rna => word.replace('T', "U"),
+    }
+}
+
+/// Copy of the primers with their words in the requested alphabet, for reporting only.
+pub fn convert_primers_alphabet(
+    candidate_primers: &[Vec<KmerStat>],
+    alphabet: Alphabet,
+) -> Vec<Vec<KmerStat>> {
+    candidate_primers
+        .iter()
+        .map(|primers| {
+            primers
+                .iter()
+                .map(|primer| KmerStat {
+                    word: convert_alphabet(&primer.word, alphabet),
+                    ..primer.clone()
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// Species and taxonomy id the primer pool is designed for.
+pub struct Taxonomy {
+    pub species_name: String,
+    pub tax_id: String,
+}
+
+/// Segments covered by the final primer pool, by segment index.
+pub struct SegmentCoverage {
+    pub covered: Vec<usize>,
+    pub uncovered: Vec<usize>,
+}
+
+impl SegmentCoverage {
+    pub fn total(&self) -> usize {
+        self.covered.len() + self.uncovered.len()
+    }
+
+    pub fn percent(&self) -> f32 {
+        match self.total() {
+            0 => 0.0,
+            total => self.covered.len() as f32 * 100.0 / total as f32,
+        }
+    }
+}
+
+/// SHA-256 checksum of an input file, stdin is recorded without a checksum.
+#[derive(Serialize)]
+pub struct InputChecksum {
+    pub path: String,
+    pub sha256: Option<String>,
+}
+
+/// Everything needed to reproduce a design run.
+#[derive(Serialize)]
+pub struct Manifest<'a> {
+    pub version: &'static str,
+    pub parameters: &'a Args,
+    pub inputs: Vec<InputChecksum>,
+}
+
+impl<'a> Manifest<'a> {
+    pub fn new(args: &'a Args) -> io::Result<Self> {
+        let inputs = args
+            .input
+            .iter()
+            .map(|path| {
+                let sha256 = match path.as_str() {
+                    STDIO_PATH => None,
+                    _ => Some(get_sha256(path)?),
+                };
+                Ok(InputChecksum {
+                    path: path.clone(),
+                    sha256,
+                })
+            })
+            .collect::<io::Result<Vec<_>>>()?;
+        Ok(Manifest {
+            version: env!("CARGO_PKG_VERSION"),
+            parameters: args,
+            inputs,
+        })
+    }
+}
+
+/// Hex encoded SHA-256 of a file's content.
+pub fn get_sha256(path: &str) -> io::Result<String> {
+    let mut hasher = Sha256::new();
+    io::copy(&mut File::open(path)?, &mut hasher)?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Counts of the pipeline reported by `--dry-run`, stages are (name, forward, reverse) k-mers
+/// left after each step.
+pub struct RunSummary {
+    pub records: usize,
+    pub segments: usize,
+    pub stages: Vec<(&'static str, usize, usize)>,
+    pub pool_tm: Option<PoolTm>,
+}
+
+/// Tm spread of the final primers, `ok` unless the standard deviation exceeds
+/// `--max-pool-tm-std`.
+pub struct PoolTm {
+    pub mean: f32,
+    pub std: f32,
+    pub ok: bool,
+}
+
+/// Primers selected and segments covered with one k-mer size of a `--kmer-sizes` sweep.
+pub struct KmerSizeReport {
+    pub kmer_size: usize,
+    pub primers_fwd: usize,
+    pub primers_rev: usize,
+    pub coverage: SegmentCoverage,
+}
+
+/// Tm threshold, GC clamp and the primers removed by each filter for one direction.
+pub struct FilterSummary {
+    pub direction: Direction,
+    pub candidates: usize,
+    pub tm_mean: f32,
+    pub tm_margin: f32,
+    pub min_tm: f32,
+    pub max_tm: f32,
+    pub gc_clamp_length: usize,
+    pub min_gc_clamp: usize,
+    pub removed: Vec<(&'static str, usize)>,
+    pub passed: usize,
+}
+
+/// Write primers as CSV with the default columns, primers are named by their index within each
+/// direction.
+pub fn write_csv<W: Write>(
+    writer: W,
+    candidate_primers: &[Vec<KmerStat>],
+    taxonomy: &Taxonomy,
+) -> io::Result<()> {
+    write_table(writer, b',', candidate_primers, taxonomy, &DEFAULT_COLUMNS)
+}
+
+/// Write the `columns` of the primers as a table with the `delimiter`, the CSV with `,` and
+/// TSV with a tab. Primers are named by their index within each direction.
+pub fn write_table<W: Write>(
+    writer: W,
+    delimiter: u8,
+    candidate_primers: &[Vec<KmerStat>],
+    taxonomy: &Taxonomy,
+    columns: &[Column],
+) -> io::Result<()> {
+    let mut writer = csv::WriterBuilder::new()
+        .delimiter(delimiter)
+        .from_writer(writer);
+    writer.write_record(columns.iter().map(|column| get_column_name(*column)))?;
+    for candidates in candidate_primers {
+        for (idx, primer) in candidates.iter().enumerate() {
+            writer.write_record(
+                columns
+                    .iter()
+                    .map(|column| get_column_value(*column, primer, idx, taxonomy)),
+            )?;
+        }
+    }
+    writer.flush()
+}
+
+/// Header of the column, its `--columns` name.
+pub fn get_column_name(column: Column) -> String {
+    match column.to_possible_value() {
+        Some(value) => value.get_name().to_string(),
+        None => unreachable!("columns are never skipped"),
+    }
+}
+
+/// Value of the column for the primer, formatted as in the CSV.
+fn get_column_value(column: Column, primer: &KmerStat, idx: usize, taxonomy: &Taxonomy) -> String {
+    match column {
+        Column::Direction => primer.direction.label().to_string(),
+        Column::Name => get_primer_name(primer, idx),
+        Column::Primers => primer.word.clone(),
+        Column::Gc => format!("{:.2}", primer.gc_percent / 100.0),
+        Column::GcMin => format!("{:.2}", primer.gc_min / 100.0),
+        Column::GcMax => format!("{:.2}", primer.gc_max / 100.0),
+        Column::Avg => format!("{:.2}", primer.mean),
+        Column::Std => format!("{:.2}", primer.std),
+        Column::Tm => format!("{:.2}", primer.tm),
+        Column::TmMax => format!("{:.2}", primer.tm_max),
+        Column::TmOk => primer.tm_ok.to_string(),
+        Column::Frequency => primer.frequency.to_string(),
+        Column::Degeneracy => primer.degeneracy.to_string(),
+        Column::Sequences => primer.sequences.join(";"),
+        Column::SelfBindingCount => primer.self_binding_count.to_string(),
+        Column::Constituents => primer.constituents.join(";"),
+        Column::SelfAnyTh => format!("{:.2}", primer.self_any_th),
+        Column::SelfEndTh => format!("{:.2}", primer.self_end_th),
+        Column::HairpinTh => format!("{:.2}", primer.hairpin_th),
+        Column::ThreePrimeDg => format!("{:.2}", primer.three_prime_dg),
+        Column::SelfFoldDg => format!("{:.2}", primer.self_fold_dg),
+        Column::Runs => primer.runs.to_string(),
+        Column::ThreePrimeRun => primer.three_prime_run.to_string(),
+        Column::GcClamp => primer.gc_clamp.to_string(),
+        Column::ComplexityOk => primer.complexity_ok.to_string(),
+        Column::SpecificityOk => primer.specificity_ok.to_string(),
+        Column::NearMasked => primer.near_masked.to_string(),
+        Column::Species => taxonomy.species_name.clone(),
+        Column::TaxId => taxonomy.tax_id.clone(),
+    }
+}
+
+/// Write primers as FASTA records named like the CSV output.
+///
+/// Reverse primers are already stored as the reverse complement of the template, so every
+/// record is the oligo sequence to order.
+pub fn write_fasta<W: Write>(writer: W, candidate_primers: &[Vec<KmerStat>]) -> io::Result<()> {
+    let mut writer = io::BufWriter::new(writer);
+    for candidates in candidate_primers {
+        for (idx, primer) in candidates.iter().enumerate() {
+            writeln!(writer, ">{}", get_primer_name(primer, idx))?;
+            writeln!(writer, "{}", primer.word)?;
+        }
+    }
+    writer.flush()
+}
+
+/// Write primer pairs as CSV, one row per segment the pair amplifies.
+pub fn write_pairs<W: Write>(
+    writer: W,
+    pairs: &[PrimerPair],
+    alphabet: Alphabet,
+) -> io::Result<()> {
+    let mut writer = csv::Writer::from_writer(writer);
+    writer.write_record([
+        "sequence",
+        "partition",
+        "forward",
+        "reverse",
+        "amplicon_length",
+        "forward_tm",
+        "reverse_tm",
+        "delta_tm",
+    ])?;
+    for pair in pairs {
+        writer.write_record([
+            &*pair.segment.sequence.name,
+            &pair.segment.partition_no.to_string(),
+            &convert_alphabet(&pair.forward.word, alphabet),
+            &convert_alphabet(&pair.reverse.word, alphabet),
+            &pair.amplicon_length.to_string(),
+            &format!("{:.2}", pair.forward_tm),
+            &format!("{:.2}", pair.reverse_tm),
+            &format!("{:.2}", pair.delta_tm),
+        ])?;
+    }
+    writer.flush()
+}
+
+/// Write the primer intervals as TSV, one row per primer and segment it binds.
+pub fn write_tiling<W: Write>(writer: W, intervals: &[TilingInterval]) -> io::Result<()> {
+    let mut writer = io::BufWriter::new(writer);
+    writeln!(writer, "segment\tsequence\tstart\tend\tstrand\tname")?;
+    for interval in intervals {
+        writeln!(
+            writer,
+            "{}\t{}\t{}\t{}\t{}\t{}",
+            interval.segment.index,
+            interval.segment.sequence.name,
+            interval.start,
+            interval.end,
+            match interval.direction {
+                Direction::Forward => "+",
+                Direction::Reverse => "-",
+            },
+            interval.name
+        )?;
+    }
+    writer.flush()
+}
+
+/// Percent-encode the characters GFF3 reserves in the seqid and attribute columns.
+fn escape_gff(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if matches!(c, ';' | '=' | '&' | ',' | '%') || c.is_control() {
+            let mut bytes = [0; 4];
+            for byte in c.encode_utf8(&mut bytes).bytes() {
+                escaped.push_str(&format!("%{:02X}", byte));
+            }
+        } else {
+            escaped.push(c);
+        }
+    }
+    escaped
+}
+
+/// Write the primer intervals as GFF3 `primer_binding_site` features, with 1-based inclusive
+/// coordinates unlike the 0-based half-open `--tiling` intervals. A primer binding the same
+/// place of a sequence from overlapping segments is written once.
+pub fn write_gff<W: Write>(writer: W, intervals: &[TilingInterval]) -> io::Result<()> {
+    let mut writer = io::BufWriter::new(writer);
+    writeln!(writer, "##gff-version 3")?;
+    let mut written = std::collections::HashSet::new();
+    for interval in intervals {
+        let key = (
+            &interval.segment.sequence.name,
+            interval.start,
+            interval.direction,
+            &interval.name,
+        );
+        if !written.insert(key) {
+            continue;
+        }
+        writeln!(
+            writer,
+            "{}\tod-msspe\tprimer_binding_site\t{}\t{}\t.\t{}\t.\tName={};tm={:.2};gc={:.2}",
+            escape_gff(&interval.segment.sequence.name),
+            interval.start + 1,
+            interval.end,
+            match interval.direction {
+                Direction::Forward => "+",
+                Direction::Reverse => "-",
+            },
+            escape_gff(&interval.name),
+            interval.primer.tm,
+            interval.primer.gc_percent
+        )?;
+    }
+    writer.flush()
+}
+
+/// Write the dimer ΔG matrix as TSV, with a header row and a first column of primer names.
+pub fn write_dimer_matrix<W: Write>(
+    writer: W,
+    names: &[String],
+    matrix: &[Vec<f32>],
+) -> io::Result<()> {
+    let mut writer = io::BufWriter::new(writer);
+    writeln!(writer, "primer\t{}", names.join("\t"))?;
+    for (name, row) in names.iter().zip(matrix) {
+        let cells: Vec<String> = row.iter().map(|dg| format!("{:.2}", dg)).collect();
+        writeln!(writer, "{}\t{}", name, cells.join("\t"))?;
+    }
+    writer.flush()
+}
+
+/// Write the coverage summary as a single TSV row, uncovered indices are comma separated.
+pub fn write_coverage<W: Write>(writer: W, coverage: &SegmentCoverage) -> io::Result<()> {
+    let mut writer = io::BufWriter::new(writer);
+    let uncovered: Vec<String> = coverage.uncovered.iter().map(|i| i.to_string()).collect();
+    writeln!(writer, "total_segments\tcovered\tpercent\tuncovered")?;
+    writeln!(
+        writer,
+        "{}\t{}\t{:.2}\t{}",
+        coverage.total(),
+        coverage.covered.len(),
+        coverage.percent(),
+        uncovered.join(",")
+    )?;
+    writer.flush()
+}
+
+/// Write all primers of every direction as a single JSON array.
+pub fn write_json<W: Write>(writer: W, candidate_primers: &[Vec<KmerStat>]) -> io::Result<()> {
+    let primers: Vec<&KmerStat> = candidate_primers.iter().flatten().collect();
+    let mut writer = io::BufWriter::new(writer);
+    serde_json::to_writer_pretty(&mut writer, &primers)?;
+    writer.write_all(b"\n")?;
+    writer.flush()
+}
+
+/// Write primers as JSON Lines, one object per primer like the `write_json` array items.
+///
+/// Every line is flushed once written, so the output can be processed while it streams.
+pub fn write_jsonl<W: Write>(writer: W, candidate_primers: &[Vec<KmerStat>]) -> io::Result<()> {
+    let mut writer = io::BufWriter::new(writer);
+    for primer in candidate_primers.iter().flatten() {
+        serde_json::to_writer(&mut writer, primer)?;
+        writer.write_all(b"\n")?;
+        writer.flush()?;
+    }
+    Ok(())
+}
+
+/// Write primers as Primer3 boulder-IO records, one `=` terminated record per primer.
+///
+/// Forward primers are `SEQUENCE_PRIMER` with `PRIMER_LEFT_0_*` tags, reverse primers are
+/// already the oligo sequence and written as `SEQUENCE_PRIMER_REVCOMP` with `PRIMER_RIGHT_0_*`.
+pub fn write_boulder_io<W: Write>(
+    writer: W,
+    candidate_primers: &[Vec<KmerStat>],
+) -> io::Result<()> {
+    let mut writer = io::BufWriter::new(writer);
+    for candidates in candidate_primers {
+        for (idx, primer) in candidates.iter().enumerate() {
+            let (sequence_tag, side) = match primer.direction {
+                Direction::Forward => ("SEQUENCE_PRIMER", "LEFT"),
+                Direction::Reverse => ("SEQUENCE_PRIMER_REVCOMP", "RIGHT"),
+            };
+            writeln!(writer, "SEQUENCE_ID={}", get_primer_name(primer, idx))?;
+            writeln!(writer, "{}={}", sequence_tag, primer.word)?;
+            writeln!(writer, "PRIMER_{}_NUM_RETURNED=1", side)?;
+            writeln!(writer, "PRIMER_{}_0_SEQUENCE={}", side, primer.word)?;
+            writeln!(writer, "PRIMER_{}_0_TM={:.2}", side, primer.tm)?;
+            writeln!(
+                writer,
+                "PRIMER_{}_0_GC_PERCENT={:.2}",
+                side, primer.gc_percent
+            )?;
+            writeln!(
+                writer,
+                "PRIMER_{}_0_SELF_ANY_TH={:.2}",
+                side, primer.self_any_th
+            )?;
+            writeln!(
+                writer,
+                "PRIMER_{}_0_SELF_END_TH={:.2}",
+                side, primer.self_end_th
+            )?;
+            writeln!(
+                writer,
+                "PRIMER_{}_0_HAIRPIN_TH={:.2}",
+                side, primer.hairpin_th
+            )?;
+            writeln!(writer, "=")?;
+        }
+    }
+    writer.flush()
+}
+
+/// Write the iterations of the greedy selection as TSV, one row per selected k-mer.
+pub fn write_trace<'a, W: Write>(
+    writer: W,
+    reports: impl Iterator<Item = &'a IterationReport>,
+) -> io::Result<()> {
+    let mut writer = io::BufWriter::new(writer);
+    writeln!(
+        writer,
+        "iteration\tdirection\tword\tfrequency\tnewly_skipped\tremaining"
+    )?;
+    for report in reports {
+        writeln!(
+            writer,
+            "{}\t{}\t{}\t{}\t{}\t{}",
+            report.iteration,
+            report.direction.label(),
+            report.word,
+            report.frequency,
+            report.newly_skipped,
+            report.remaining
+        )?;
+    }
+    writer.flush()
+}
+
+/// Write the k-mer size sweep as TSV, one row per size.
+pub fn write_kmer_sizes<W: Write>(writer: W, reports: &[KmerSizeReport]) -> io::Result<()> {
+    let mut writer = io::BufWriter::new(writer);
+    writeln!(writer, "kmer_size\tforward\treverse\tcovered\tpercent")?;
+    for report in reports {
+        writeln!(
+            writer,
+            "{}\t{}\t{}\t{}\t{:.2}",
+            report.kmer_size,
+            report.primers_fwd,
+            report.primers_rev,
+            report.coverage.covered.len(),
+            report.coverage.percent()
+        )?;
+    }
+    writer.flush()
+}
+
+/// Candidate primer removed by the filters or the dimer check, with every criterion it fails.
+pub struct Rejection {
+    pub direction: Direction,
+    pub word: String,
+    pub frequency: usize,
+    pub tm: f32,
+    pub gc_percent: f32,
+    pub reasons: Vec<&'static str>,
+}
+
+impl Rejection {
+    pub fn new(primer: &KmerStat, reasons: Vec<&'static str>) -> Self {
+        Rejection {
+            direction: primer.direction,
+            word: primer.word.clone(),
+            frequency: primer.frequency,
+            tm: primer.tm,
+            gc_percent: primer.gc_percent,
+            reasons,
+        }
+    }
+}
+
+/// Write the rejected primers as TSV, one row per primer with its failed criteria separated
+/// by `,`.
+pub fn write_rejections<W: Write>(writer: W, rejections: &[Rejection]) -> io::Result<()> {
+    let mut writer = io::BufWriter::new(writer);
+    writeln!(writer, "direction\tword\tfrequency\ttm\tgc\treasons")?;
+    for rejection in rejections {
+        writeln!(
+            writer,
+            "{}\t{}\t{}\t{:.2}\t{:.2}\t{}",
+            rejection.direction.label(),
+            rejection.word,
+            rejection.frequency,
+            rejection.tm,
+            rejection.gc_percent / 100.0,
+            rejection.reasons.join(",")
+        )?;
+    }
+    writer.flush()
+}
+
+/// Write the filter summaries as TSV, one row per direction with a column per filter.
+pub fn write_filter_summary<W: Write>(writer: W, summaries: &[FilterSummary]) -> io::Result<()> {
+    let mut writer = io::BufWriter::new(writer);
+    let filters: Vec<&str> = summaries
+        .first()
+        .map(|s| s.removed.iter().map(|(f, _)| *f).collect())
+        .unwrap_or_default();
+    writeln!(
+        writer,
+        "direction\tcandidates\ttm_mean\ttm_margin\tmin_tm\tmax_tm\tgc_clamp\t{}\tpassed",
+        filters.join("\t")
+    )?;
+    for summary in summaries {
+        let removed: Vec<String> = summary.removed.iter().map(|(_, n)| n.to_string()).collect();
+        writeln!(
+            writer,
+            "{}\t{}\t{:.2}\t{:.2}\t{:.2}\t{:.2}\t{}/{}\t{}\t{}",
+            summary.direction.label(),
+            summary.candidates,
+            summary.tm_mean,
+            summary.tm_margin,
+            summary.min_tm,
+            summary.max_tm,
+            summary.min_gc_clamp,
+            summary.gc_clamp_length,
+            removed.join("\t"),
+            summary.passed
+        )?;
+    }
+    writer.flush()
+}
+
+/// Write the dry run summary as TSV, record and segment totals first, then one row per stage.
+pub fn write_summary<W: Write>(writer: W, summary: &RunSummary) -> io::Result<()> {
+    let mut writer = io::BufWriter::new(writer);
+    writeln!(writer, "records\t{}", summary.records)?;
+    writeln!(writer, "segments\t{}", summary.segments)?;
+    writeln!(writer, "stage\tforward\treverse")?;
+    for (stage, fwd, rev) in &summary.stages {
+        writeln!(writer, "{}\t{}\t{}", stage, fwd, rev)?;
+    }
+    if let Some(pool_tm) = &summary.pool_tm {
+        writeln!(writer, "pool_tm_mean\t{:.2}", pool_tm.mean)?;
+        writeln!(writer, "pool_tm_std\t{:.2}", pool_tm.std)?;
+        writeln!(writer, "pool_tm_ok\t{}", pool_tm.ok)?;
+    }
+    writer.flush()
+}
+
+/// Write the run manifest as pretty printed JSON.
+pub fn write_manifest<W: Write>(writer: W, manifest: &Manifest) -> io::Result<()> {
+    let mut writer = io::BufWriter::new(writer);
+    serde_json::to_writer_pretty(&mut writer, manifest)?;
+    writer.write_all(b"\n")?;
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::get_test_kmer_stat;
+    use clap::Parser;
+
+    #[test]
+    fn test_write_csv() {
+        let candidate_primers = vec![
+            vec![KmerStat {
+                frequency: 3,
+                sequences: vec!["seq1".to_string(), "seq2".to_string()],
+                ..get_test_kmer_stat("ACGTACGTACGTA", Direction::Forward)
+            }],
+            vec![get_test_kmer_stat("TTGCATGCATGCA", Direction::Reverse)],
+        ];
+        let taxonomy = Taxonomy {
+            species_name: "Zika virus".to_string(),
+            tax_id: "64320".to_string(),
+        };
+        let mut buffer = Vec::new();
+        write_csv(&mut buffer, &candidate_primers, &taxonomy).unwrap();
+
+        let output = String::from_utf8(buffer).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(
+            lines[0],
+            "direction,name,primers,gc,gc_min,gc_max,avg,std,tm,tm_max,tm_ok,frequency,degeneracy,sequences,self_binding_count,constituents,self_any_th,self_end_th,hairpin_th,three_prime_dg,self_fold_dg,runs,three_prime_run,gc_clamp,complexity_ok,specificity_ok,near_masked,species,tax_id"
+        );
+        assert_eq!(
+            lines[1],
+            "F,Primer_0_F,ACGTACGTACGTA,0.46,0.46,0.46,45.00,0.00,60.00,60.00,true,3,1,seq1;seq2,0,,0.00,0.00,0.00,-5.63,0.00,false,false,true,true,true,false,Zika virus,64320"
+        );
+        assert!(lines[2].starts_with("R,Primer_0_R,TTGCATGCATGCA,"));
+    }
+
+    #[test]
+    fn test_write_table_columns() {
+        let args = Args::parse_from([
+            "od-msspe",
+            "-i",
+            "input.fasta",
+            "--format",
+            "tsv",
+            "--columns",
+            "name,sequence,tm,gc_percent,direction",
+        ]);
+        assert_eq!(
+            args.columns,
+            [
+                Column::Name,
+                Column::Primers,
+                Column::Tm,
+                Column::Gc,
+                Column::Direction
+            ]
+        );
+        let candidate_primers = vec![
+            vec![get_test_kmer_stat("ACGTACGTACGTA", Direction::Forward)],
+            vec![get_test_kmer_stat("TTGCATGCATGCA", Direction::Reverse)],
+        ];
+        let taxonomy = Taxonomy {
+            species_name: "Zika, virus".to_string(),
+            tax_id: "64320".to_string(),
+        };
+        let mut buffer = Vec::new();
+        write_table(
+            &mut buffer,
+            b'\t',
+            &candidate_primers,
+            &taxonomy,
+            &args.columns,
+        )
+        .unwrap();
+        let output = String::from_utf8(buffer).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(
+            lines,
+            [
+                "name\tprimers\ttm\tgc\tdirection",
+                "Primer_0_F\tACGTACGTACGTA\t60.00\t0.46\tF",
+                "Primer_0_R\tTTGCATGCATGCA\t60.00\t0.46\tR",
+            ]
+        );
+
+        // commas need no quoting in TSV
+        let mut buffer = Vec::new();
+        write_table(
+            &mut buffer,
+            b'\t',
+            &candidate_primers,
+            &taxonomy,
+            &[Column::Species],
+        )
+        .unwrap();
+        assert!(
+            String::from_utf8(buffer)
+                .unwrap()
+                .ends_with("\nZika, virus\n")
+        );
+
+        let error = Args::try_parse_from([
+            "od-msspe",
+            "-i",
+            "input.fasta",
+            "--columns",
+            "name,melting_point",
+        ])
+        .unwrap_err()
+        .to_string();
+        assert!(error.contains("melting_point"));
+        assert!(error.contains("possible values"));
+        assert!(error.contains("three_prime_dg"));
+    }
+
+    #[test]
+    fn test_write_fasta() {
+        // reverse primers are picked from the end window and stored reverse complemented
+        let template = "TGCATGCATGCAA";
+        let candidate_primers = vec![
+            vec![get_test_kmer_stat("ACGTACGTACGTA", Direction::Forward)],
+            vec![get_test_kmer_stat(
+                &crate::reverse_complement(template, None),
+                Direction::Reverse,
+            )],
+        ];
+        let mut buffer = Vec::new();
+        write_fasta(&mut buffer, &candidate_primers).unwrap();
+
+        let output = String::from_utf8(buffer).unwrap();
+        assert_eq!(
+            output,
+            ">Primer_0_F\nACGTACGTACGTA\n>Primer_0_R\nTTGCATGCATGCA\n"
+        );
+    }
+
+    #[test]
+    fn test_write_gff() {
+        let record = crate::SequenceRecord::new("chr1;a=b,c%".to_string(), "ACGTTGCA".to_string());
+        let segment = crate::Segment {
+            sequence: &record,
+            partition_no: 0,
+            index: 0,
+            start: 0,
+            end: 8,
+            kmers: [Vec::new(), Vec::new()],
+        };
+        let forward = KmerStat {
+            tm: 58.456,
+            gc_percent: 62.5,
+            ..get_test_kmer_stat("ACGT", Direction::Forward)
+        };
+        let reverse = get_test_kmer_stat("TGCA", Direction::Reverse);
+        let interval = |primer, name: &str, direction, start| TilingInterval {
+            segment: &segment,
+            primer,
+            index: 0,
+            name: name.to_string(),
+            direction,
+            start,
+            end: start + 4,
+        };
+        // the same binding site reached from an overlapping segment is written once
+        let intervals = [
+            interval(&forward, "fwd\t1", Direction::Forward, 0),
+            interval(&reverse, "Primer_0_R", Direction::Reverse, 4),
+            interval(&reverse, "Primer_0_R", Direction::Reverse, 4),
+        ];
+        let mut buffer = Vec::new();
+        write_gff(&mut buffer, &intervals).unwrap();
+
+        let output = String::from_utf8(buffer).unwrap();
+        assert_eq!(
+            output,
+            "##gff-version 3\n\
+             chr1%3Ba%3Db%2Cc%25\tod-msspe\tprimer_binding_site\t1\t4\t.\t+\t.\t\
+             Name=fwd%091;tm=58.46;gc=62.50\n\
+             chr1%3Ba%3Db%2Cc%25\tod-msspe\tprimer_binding_site\t5\t8\t.\t-\t.\t\
+             Name=Primer_0_R;tm=60.00;gc=50.00\n"
+        );
+    }
+
+    #[test]
+    fn test_write_coverage() {
+        let coverage = SegmentCoverage {
+            covered: vec![0, 1, 3],
+            uncovered: vec![2, 4],
+        };
+        let mut buffer = Vec::new();
+        write_coverage(&mut buffer, &coverage).unwrap();
+
+        let output = String::from_utf8(buffer).unwrap();
+        assert_eq!(
+            output,
+            "total_segments\tcovered\tpercent\tuncovered\n5\t3\t60.00\t2,4\n"
+        );
+    }
+
+    #[test]
+    fn test_convert_primers_alphabet() {
+        use crate::config::Args;
+        use clap::Parser;
+
+        let args = Args::parse_from(["od-msspe", "--input", "in.fasta"]);
+        assert_eq!(args.alphabet, Alphabet::Dna);
+
+        let template = "TGCATGCATGCAA";
+        let candidate_primers = vec![
+            vec![get_test_kmer_stat("ACGTACGTACGTA", Direction::Forward)],
+            vec![get_test_kmer_stat(
+                &crate::reverse_complement(template, None),
+                Direction::Reverse,
+            )],
+        ];
+        let dna = convert_primers_alphabet(&candidate_primers, Alphabet::Dna);
+        assert_eq!(dna[0][0].word, "ACGTACGTACGTA");
+
+        let rna = convert_primers_alphabet(&candidate_primers, Alphabet::Rna);
+        assert_eq!(rna[0][0].word, "ACGUACGUACGUA");
+        assert_eq!(rna[1][0].word, "UUGCAUGCAUGCA");
+        // only the reported word changes
+        assert_eq!(candidate_primers[1][0].word, "TTGCATGCATGCA");
+        assert_eq!(rna[1][0].tm, candidate_primers[1][0].tm);
+        assert_eq!(rna[1][0].gc_percent, candidate_primers[1][0].gc_percent);
+    }
+
+    #[test]
+    fn test_write_json() {
+        let candidate_primers = vec![
+            vec![get_test_kmer_stat("ACGTACGTACGTA", Direction::Forward)],
+            vec![get_test_kmer_stat("TTGCATGCATGCA", Direction::Reverse)],
+        ];
+        let mut buffer = Vec::new();
+        write_json(&mut buffer, &candidate_primers).unwrap();
+
+        let value: serde_json::Value = serde_json::from_slice(&buffer).unwrap();
+        let primers = value.as_array().unwrap();
+        assert_eq!(primers.len(), 2);
+        assert_eq!(primers[0]["word"], "ACGTACGTACGTA");
+        assert_eq!(primers[0]["direction"], "forward");
+        assert_eq!(primers[0]["frequency"], 1);
+        assert_eq!(primers[1]["direction"], "reverse");
+    }
+
+    #[test]
+    fn test_write_jsonl() {
+        #[derive(serde::Deserialize)]
+        struct Primer {
+            word: String,
+            direction: Direction,
+            frequency: usize,
+            tm: f32,
+            sequences: Vec<String>,
+        }
+
+        let candidate_primers = vec![
+            vec![
+                get_test_kmer_stat("ACGTACGTACGTA", Direction::Forward),
+                get_test_kmer_stat("ACGTACGTACGTT", Direction::Forward),
+            ],
+            vec![KmerStat {
+                frequency: 3,
+                sequences: vec!["seq1".to_string(), "seq2".to_string()],
+                ..get_test_kmer_stat("TTGCATGCATGCA", Direction::Reverse)
+            }],
+        ];
+        let mut buffer = Vec::new();
+        write_jsonl(&mut buffer, &candidate_primers).unwrap();
+
+        let output = String::from_utf8(buffer).unwrap();
+        let primers: Vec<Primer> = output
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(primers.len(), 3);
+        assert_eq!(primers[0].word, "ACGTACGTACGTA");
+        assert_eq!(primers[2].direction, Direction::Reverse);
+        assert_eq!(primers[2].frequency, 3);
+        assert_eq!(primers[2].tm, 60.0);
+        assert_eq!(primers[2].sequences, vec!["seq1", "seq2"]);
+        // the direction is rendered like the JSON array output
+        assert!(
+            output
+                .lines()
+                .nth(2)
+                .unwrap()
+                .contains("\"direction\":\"reverse\"")
+        );
+    }
+
+    #[test]
+    fn test_write_boulder_io() {
+        let candidate_primers = vec![
+            vec![get_test_kmer_stat("ACGTACGTACGTA", Direction::Forward)],
+            vec![get_test_kmer_stat("TTGCATGCATGCA", Direction::Reverse)],
+        ];
+        let mut buffer = Vec::new();
+        write_boulder_io(&mut buffer, &candidate_primers).unwrap();
+
+        let output = String::from_utf8(buffer).unwrap();
+        let records: Vec<&str> = output.split_terminator("=\n").collect();
+        assert_eq!(records.len(), 2);
+        // every line of a record is a TAG=value pair
+        let tags: Vec<(&str, &str)> = records[0]
+            .lines()
+            .map(|line| line.split_once('=').unwrap())
+            .collect();
+        assert!(tags.iter().all(|(tag, value)| {
+            !value.is_empty()
+                && tag
+                    .chars()
+                    .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == '_')
+        }));
+        assert!(tags.contains(&("SEQUENCE_ID", "Primer_0_F")));
+        assert!(tags.contains(&("SEQUENCE_PRIMER", "ACGTACGTACGTA")));
+        assert!(tags.contains(&("PRIMER_LEFT_0_TM", "60.00")));
+        assert!(tags.contains(&("PRIMER_LEFT_0_GC_PERCENT", "46.15")));
+        assert!(records[1].contains("SEQUENCE_PRIMER_REVCOMP=TTGCATGCATGCA\n"));
+        assert!(records[1].contains("PRIMER_RIGHT_0_SEQUENCE=TTGCATGCATGCA\n"));
+    }
+
+    #[test]
+    fn test_write_manifest() {
+        let path =
+            std::env::temp_dir().join(format!("od-msspe-manifest-{}.fasta", std::process::id()));
+        std::fs::write(&path, ">seq1\nACGT\n").unwrap();
+        let input = path.display().to_string();
+        let args = Args::parse_from(["od-msspe", "--input", &input, "-", "--kmer-size", "17"]);
+        let manifest = Manifest::new(&args).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let mut buffer = Vec::new();
+        write_manifest(&mut buffer, &manifest).unwrap();
+
+        let output: serde_json::Value = serde_json::from_slice(&buffer).unwrap();
+        assert_eq!(output["version"], env!("CARGO_PKG_VERSION"));
+        assert_eq!(output["parameters"]["kmer_size"], 17);
+        assert_eq!(output["inputs"][0]["path"], input);
+        assert_eq!(
+            output["inputs"][0]["sha256"],
+            "9dd096c1e0a8981c4c6662f3f7f428c16c62e36c2f388db5a3fb1a1fbc507b53"
+        );
+        assert_eq!(output["inputs"][1]["sha256"], serde_json::Value::Null);
+    }
+
+    #[test]
+    fn test_write_summary() {
+        let summary = RunSummary {
+            records: 10,
+            segments: 40,
+            stages: vec![("candidate_kmers", 12, 11), ("criteria", 8, 7)],
+            pool_tm: Some(PoolTm {
+                mean: 55.0,
+                std: 1.5,
+                ok: true,
+            }),
+        };
+        let mut buffer = Vec::new();
+        write_summary(&mut buffer, &summary).unwrap();
+
+        let output = String::from_utf8(buffer).unwrap();
+        assert_eq!(
+            output,
+            "records\t10\nsegments\t40\nstage\tforward\treverse\ncandidate_kmers\t12\t11\ncriteria\t8\t7\npool_tm_mean\t55.00\npool_tm_std\t1.50\npool_tm_ok\ttrue\n"
+        );
+    }
+
+    #[test]
+    fn test_write_trace() {
+        let reports = [IterationReport {
+            iteration: 1,
+            direction: Direction::Reverse,
+            word: "TTGCATGCATGCA".to_string(),
+            frequency: 3,
+            newly_skipped: 3,
+            remaining: 2,
+        }];
+        let mut buffer = Vec::new();
+        write_trace(&mut buffer, reports.iter()).unwrap();
+
+        let output = String::from_utf8(buffer).unwrap();
+        assert_eq!(
+            output,
+            "iteration\tdirection\tword\tfrequency\tnewly_skipped\tremaining\n1\tR\tTTGCATGCATGCA\t3\t3\t2\n"
+        );
+    }
+
+    #[test]
+    fn test_write_filter_summary() {
+        let summaries = [FilterSummary {
+            direction: Direction::Forward,
+            candidates: 10,
+            tm_mean: 45.0,
+            tm_margin: 3.0,
+            min_tm: 30.0,
+            max_tm: 60.0,
+            gc_clamp_length: 3,
+            min_gc_clamp: 1,
+            removed: vec![("tm", 2), ("runs", 3)],
+            passed: 5,
+        }];
+        let mut buffer = Vec::new();
+        write_filter_summary(&mut buffer, &summaries).unwrap();
+
+        let output = String::from_utf8(buffer).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(
+            lines[0],
+            "direction\tcandidates\ttm_mean\ttm_margin\tmin_tm\tmax_tm\tgc_clamp\ttm\truns\tpassed"
+        );
+        assert_eq!(lines[1], "F\t10\t45.00\t3.00\t30.00\t60.00\t1/3\t2\t3\t5");
+    }
+}
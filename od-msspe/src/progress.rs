@@ -0,0 +1,241 @@
+use crate::config::LogFormat;
+use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+use log::kv::{Key, Value, VisitSource};
+use log::{Log, Metadata, Record};
+use serde_json::{Map, Value as JsonValue};
+use std::cell::RefCell;
+use std::io::{IsTerminal, Write};
+use std::rc::Rc;
+use std::sync::LazyLock;
+use std::time::{Duration, Instant};
+
+/// All visible progress bars are drawn through this, so log lines can be printed above them.
+static PROGRESS: LazyLock<MultiProgress> = LazyLock::new(MultiProgress::new);
+
+/// Logger that suspends the progress bars while a log line is written.
+struct ProgressLogger {
+    inner: env_logger::Logger,
+}
+
+impl Log for ProgressLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.inner.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        if self.inner.matches(record) {
+            PROGRESS.suspend(|| self.inner.log(record));
+        }
+    }
+
+    fn flush(&self) {
+        self.inner.flush();
+    }
+}
+
+/// Initialize env_logger so it keeps working alongside the progress bars.
+pub fn init_logger(format: LogFormat) {
+    let mut builder = env_logger::Builder::from_default_env();
+    if format == LogFormat::Json {
+        builder.format(|buf, record| {
+            let timestamp = buf.timestamp_millis().to_string();
+            writeln!(buf, "{}", get_json_log_line(record, &timestamp))
+        });
+    }
+    let inner = builder.build();
+    let max_level = inner.filter();
+    if log::set_boxed_logger(Box::new(ProgressLogger { inner })).is_ok() {
+        log::set_max_level(max_level);
+    }
+}
+
+/// Collects the key-values of a log record, e.g. the `stage` of the stage timings.
+struct JsonFields(Map<String, JsonValue>);
+
+impl<'kvs> VisitSource<'kvs> for JsonFields {
+    fn visit_pair(&mut self, key: Key<'kvs>, value: Value<'kvs>) -> Result<(), log::kv::Error> {
+        let value = match (
+            value.to_u64(),
+            value.to_i64(),
+            value.to_f64(),
+            value.to_bool(),
+        ) {
+            (Some(n), _, _, _) => JsonValue::from(n),
+            (_, Some(n), _, _) => JsonValue::from(n),
+            (_, _, Some(n), _) => JsonValue::from(n),
+            (_, _, _, Some(b)) => JsonValue::from(b),
+            _ => JsonValue::from(value.to_string()),
+        };
+        self.0.insert(key.to_string(), value);
+        Ok(())
+    }
+}
+
+/// Log record as one JSON object with its timestamp, level, target, message and key-values.
+fn get_json_log_line(record: &Record, timestamp: &str) -> String {
+    let mut fields = JsonFields(Map::new());
+    fields.0.insert("timestamp".to_string(), timestamp.into());
+    fields
+        .0
+        .insert("level".to_string(), record.level().as_str().into());
+    fields
+        .0
+        .insert("target".to_string(), record.target().into());
+    fields
+        .0
+        .insert("message".to_string(), record.args().to_string().into());
+    // a record with broken key-values is still logged with the fields collected so far
+    let _ = record.key_values().visit(&mut fields);
+    JsonValue::Object(fields.0).to_string()
+}
+
+/// Progress bar of `len` steps, hidden unless enabled and stderr is a terminal.
+pub fn get_progress_bar(len: usize, enabled: bool, template: &str) -> ProgressBar {
+    if !enabled || !std::io::stderr().is_terminal() {
+        return ProgressBar::hidden();
+    }
+    let style =
+        ProgressStyle::with_template(template).unwrap_or_else(|_| ProgressStyle::default_bar());
+    PROGRESS.add(ProgressBar::new(len as u64).with_style(style))
+}
+
+/// Elapsed time of a pipeline stage, reported on stderr with `--verbose` and logged at debug
+/// level otherwise. A stage left by an early return is reported as failed when dropped.
+pub struct StageTimer {
+    stage: &'static str,
+    start: Instant,
+    verbose: bool,
+    finished: bool,
+    // every reported message is also pushed here when set
+    reports: Option<Rc<RefCell<Vec<String>>>>,
+}
+
+impl StageTimer {
+    pub fn start(stage: &'static str, verbose: bool) -> Self {
+        StageTimer {
+            stage,
+            start: Instant::now(),
+            verbose,
+            finished: false,
+            reports: None,
+        }
+    }
+
+    /// Like `start`, also recording the reported messages in `reports`.
+    pub fn recording(
+        stage: &'static str,
+        verbose: bool,
+        reports: Rc<RefCell<Vec<String>>>,
+    ) -> Self {
+        StageTimer {
+            reports: Some(reports),
+            ..StageTimer::start(stage, verbose)
+        }
+    }
+
+    /// Report the stage as done.
+    pub fn finish(mut self) {
+        self.report("done");
+        self.finished = true;
+    }
+
+    fn report(&self, status: &str) {
+        let message = get_timing_message(self.stage, status, self.start.elapsed());
+        if let Some(reports) = &self.reports {
+            reports.borrow_mut().push(message.clone());
+        }
+        match self.verbose {
+            true => PROGRESS.suspend(|| eprintln!("{}", message)),
+            false => log::debug!(
+                stage = self.stage,
+                status = status,
+                elapsed_ms = self.start.elapsed().as_millis() as u64;
+                "{}",
+                message
+            ),
+        }
+    }
+}
+
+impl Drop for StageTimer {
+    fn drop(&mut self) {
+        if !self.finished {
+            self.report("failed");
+        }
+    }
+}
+
+fn get_timing_message(stage: &str, status: &str, elapsed: Duration) -> String {
+    format!("Stage {} {} in {} ms", stage, status, elapsed.as_millis())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get_progress_bar() {
+        assert!(get_progress_bar(10, false, "{pos}/{len}").is_hidden());
+    }
+
+    #[test]
+    fn test_get_timing_message() {
+        assert_eq!(
+            get_timing_message("selection", "done", Duration::from_millis(1520)),
+            "Stage selection done in 1520 ms"
+        );
+    }
+
+    #[test]
+    fn test_stage_timer() {
+        let reports = Rc::new(RefCell::new(Vec::new()));
+        let timer = StageTimer::recording("alignment", false, reports.clone());
+        assert!(!timer.finished);
+        timer.finish();
+        assert_eq!(reports.borrow().len(), 1);
+        assert!(reports.borrow()[0].starts_with("Stage alignment done in "));
+
+        // a stage left with an error is still reported when dropped
+        let failing = || -> Result<(), String> {
+            let _timer = StageTimer::recording("output", false, reports.clone());
+            Err("failed".to_string())
+        };
+        assert!(failing().is_err());
+        assert_eq!(reports.borrow().len(), 2);
+        assert!(reports.borrow()[1].starts_with("Stage output failed in "));
+    }
+
+    #[test]
+    fn test_get_json_log_line() {
+        let line = get_json_log_line(
+            &Record::builder()
+                .args(format_args!("Kept the top {} of {} primers", 4, 9))
+                .level(log::Level::Info)
+                .target("od_msspe")
+                .build(),
+            "2024-01-01T00:00:00.000Z",
+        );
+        let json: JsonValue = serde_json::from_str(&line).unwrap();
+        assert_eq!(json["timestamp"], "2024-01-01T00:00:00.000Z");
+        assert_eq!(json["level"], "INFO");
+        assert_eq!(json["target"], "od_msspe");
+        assert_eq!(json["message"], "Kept the top 4 of 9 primers");
+
+        let kvs: &[(&str, Value)] = &[
+            ("stage", Value::from("selection")),
+            ("elapsed_ms", Value::from(1520u64)),
+        ];
+        let line = get_json_log_line(
+            &Record::builder()
+                .args(format_args!("Stage selection done in 1520 ms"))
+                .level(log::Level::Debug)
+                .key_values(&kvs)
+                .build(),
+            "2024-01-01T00:00:00.000Z",
+        );
+        let json: JsonValue = serde_json::from_str(&line).unwrap();
+        assert_eq!(json["level"], "DEBUG");
+        assert_eq!(json["stage"], "selection");
+        assert_eq!(json["elapsed_ms"], 1520);
+    }
+}
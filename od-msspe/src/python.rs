@@ -0,0 +1,126 @@
+//! Python bindings, built with the `pyo3` feature, e.g. `maturin develop --features pyo3`.
+use crate::config::{Args, DNTP_CONC, DV_CONC, MV_CONC};
+use crate::error::DesignError;
+use crate::primer::PrimerInfo;
+use crate::tm::get_gc_percent;
+use crate::{
+    Direction, SequenceRecord, find_candidates_kmers, get_partitioning_option, get_primer_tm,
+    get_program_config, get_segment_manager, output,
+};
+use clap::Parser;
+use pyo3::exceptions::{PyRuntimeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::{PyBool, PyDict};
+
+impl From<DesignError> for PyErr {
+    fn from(e: DesignError) -> Self {
+        PyRuntimeError::new_err(e.to_string())
+    }
+}
+
+/// Command line arguments from a dict of parameters, `kmer_size=15` is `--kmer-size 15`.
+fn get_args(params: Option<&Bound<'_, PyDict>>) -> PyResult<Args> {
+    let mut argv = vec![
+        "od-msspe".to_string(),
+        "--input".to_string(),
+        "-".to_string(),
+    ];
+    for (key, value) in params.into_iter().flat_map(|p| p.iter()) {
+        argv.push(format!("--{}", key.extract::<String>()?.replace('_', "-")));
+        // flags are parsed from "true" and "false"
+        let is_bool = value.is_instance_of::<PyBool>();
+        let value = value.str()?.to_string();
+        argv.push(match is_bool {
+            true => value.to_lowercase(),
+            false => value,
+        });
+    }
+    Args::try_parse_from(argv).map_err(|e| PyValueError::new_err(e.to_string()))
+}
+
+// the wrappers generated by the pyo3 0.22 macros predate edition 2024 and sit next to the
+// functions, out of reach of an attribute on the functions themselves
+#[allow(unsafe_op_in_unsafe_fn, clippy::useless_conversion)]
+mod functions {
+    use super::*;
+
+    /// Distinct k-mers of the sequence in order of first appearance.
+    #[pyfunction]
+    #[pyo3(name = "find_kmers")]
+    pub(super) fn py_find_kmers(sequence: &str, kmer_size: usize) -> Vec<String> {
+        crate::find_kmers(sequence, kmer_size)
+    }
+
+    /// Reverse complement of the sequence, IUPAC codes are complemented too.
+    #[pyfunction]
+    #[pyo3(name = "reverse_complement")]
+    pub(super) fn py_reverse_complement(sequence: &str) -> String {
+        crate::reverse_complement(sequence, None)
+    }
+
+    /// Tm of the sequence with the GC content formula and salt correction, concentrations in mM.
+    #[pyfunction]
+    #[pyo3(name = "get_tm", signature = (sequence, mv_conc = MV_CONC, dv_conc = DV_CONC, dntp_conc = DNTP_CONC))]
+    pub(super) fn py_get_tm(sequence: &str, mv_conc: f32, dv_conc: f32, dntp_conc: f32) -> f32 {
+        crate::tm::get_tm(sequence, mv_conc as f64, dv_conc as f64, dntp_conc as f64)
+    }
+
+    /// Candidate primers of the greedy k-mer selection for the (aligned) sequences.
+    ///
+    /// `params` takes the command line options with underscores, e.g.
+    /// `{"kmer_size": 15, "window_size": 300}`. Returns a dict per primer with its name,
+    /// sequence, direction, frequency, Tm and GC percent. This is the selection only: none of
+    /// the criteria of the command line tool (Tm, repeats, GC, primer3 and ntthal checks) are
+    /// applied, and the Tm is 0 with `tm_method="primer3"`.
+    #[pyfunction]
+    #[pyo3(signature = (records, params = None))]
+    pub(super) fn find_candidate_primers<'py>(
+        py: Python<'py>,
+        records: Vec<String>,
+        params: Option<&Bound<'py, PyDict>>,
+    ) -> PyResult<Vec<Bound<'py, PyDict>>> {
+        let args = get_args(params)?;
+        let config = get_program_config(
+            &args,
+            args.ntthal.clone(),
+            args.primer3.clone(),
+            args.blastn.clone(),
+        );
+        let records: Vec<SequenceRecord> = records
+            .into_iter()
+            .enumerate()
+            .map(|(i, sequence)| {
+                SequenceRecord::new(format!("seq{}", i + 1), sequence.to_uppercase())
+            })
+            .collect();
+        let manager = get_segment_manager(&records, get_partitioning_option(&args, false))?;
+
+        let info = PrimerInfo::new();
+        let mut primers = Vec::new();
+        for direction in Direction::ALL {
+            let candidates =
+                find_candidates_kmers(&manager, direction, config.clone()).unwrap_or_default();
+            for (idx, kmer_freq) in candidates.iter().enumerate() {
+                let word = &kmer_freq.kmer.word;
+                let primer = PyDict::new_bound(py);
+                primer.set_item("name", output::primer_name(idx, direction))?;
+                primer.set_item("sequence", word)?;
+                primer.set_item("direction", direction.label())?;
+                primer.set_item("frequency", kmer_freq.frequency)?;
+                primer.set_item("tm", get_primer_tm(word, &info, &config))?;
+                primer.set_item("gc", get_gc_percent(word))?;
+                primers.push(primer);
+            }
+        }
+        Ok(primers)
+    }
+}
+
+#[pymodule]
+fn od_msspe(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_function(wrap_pyfunction!(functions::py_find_kmers, m)?)?;
+    m.add_function(wrap_pyfunction!(functions::py_reverse_complement, m)?)?;
+    m.add_function(wrap_pyfunction!(functions::py_get_tm, m)?)?;
+    m.add_function(wrap_pyfunction!(functions::find_candidate_primers, m)?)?;
+    Ok(())
+}
@@ -0,0 +1,133 @@
+use crate::error::DesignError;
+use std::collections::HashSet;
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+/// Parameters of the off-target screening of primers against a BLAST database with blastn
+pub struct SpecificityParams {
+    pub blastn_path: String,
+    pub db: String,
+    pub max_mismatch: usize,
+}
+
+/// Format primers as blastn FASTA queries, the primer is its own id
+/// Example:
+/// ```text
+/// >AGCCCGTGTAAAC
+/// AGCCCGTGTAAAC
+/// ```
+pub fn format_blastn_input(primers: &[String]) -> String {
+    let mut input = String::new();
+    for primer in primers {
+        input.push_str(&format!(">{}\n{}\n", primer, primer));
+    }
+    input
+}
+
+/// Find the primers with a significant off-target hit in blastn tabular output
+/// (`-outfmt "6 qseqid qlen nident qend"`). A hit is significant when it reaches the 3' end
+/// of the primer with at most `max_mismatch` unmatched bases, since such a primer can still
+/// extend on the off-target sequence.
+/// Example:
+/// ```text
+/// AGCCCGTGTAAAC    13    13    13
+/// ```
+pub fn parse_blastn_output(output: &str, max_mismatch: usize) -> HashSet<String> {
+    let mut off_target = HashSet::new();
+    for line in output.lines() {
+        let fields: Vec<&str> = line.split('\t').collect();
+        let [qseqid, qlen, nident, qend] = fields[..] else {
+            log::debug!("cannot parse blastn output line: {}", line);
+            continue;
+        };
+        let (Ok(qlen), Ok(nident), Ok(qend)) = (
+            qlen.parse::<usize>(),
+            nident.parse::<usize>(),
+            qend.parse::<usize>(),
+        ) else {
+            log::debug!("cannot parse blastn output line: {}", line);
+            continue;
+        };
+        if qend == qlen && qlen.saturating_sub(nident) <= max_mismatch {
+            off_target.insert(qseqid.to_string());
+        }
+    }
+    off_target
+}
+
+/// Call blastn (blastn-short task) with all primers as queries and return the primers with
+/// significant off-target hits.
+pub fn find_off_target_primers(
+    primers: &[String],
+    params: &SpecificityParams,
+) -> Result<HashSet<String>, DesignError> {
+    if primers.is_empty() {
+        return Ok(HashSet::new());
+    }
+    log::debug!("using blastn binary: {:?}", params.blastn_path);
+    let mut cmd = Command::new(&params.blastn_path)
+        .args([
+            "-task",
+            "blastn-short",
+            "-db",
+            &params.db,
+            "-outfmt",
+            "6 qseqid qlen nident qend",
+            "-evalue",
+            "1000",
+        ])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| DesignError::SpecificityCheckFailed(e.to_string()))?;
+
+    let input = format_blastn_input(primers);
+    if let Some(mut stdin) = cmd.stdin.take() {
+        std::thread::spawn(move || {
+            stdin
+                .write_all(input.as_bytes())
+                .expect("failed to write to stdin");
+        });
+    }
+
+    let output = cmd.wait_with_output()?;
+    if !output.status.success() {
+        return Err(DesignError::SpecificityCheckFailed(
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        ));
+    }
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    Ok(parse_blastn_output(&stdout, params.max_mismatch))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_blastn_input() {
+        let primers = vec!["AGCCCGTGTAAAC".to_string(), "GAAGCAGTATTTT".to_string()];
+        assert_eq!(
+            format_blastn_input(&primers),
+            ">AGCCCGTGTAAAC\nAGCCCGTGTAAAC\n>GAAGCAGTATTTT\nGAAGCAGTATTTT\n"
+        );
+    }
+
+    #[test]
+    fn test_parse_blastn_output() {
+        let output = "\
+            AGCCCGTGTAAAC\t13\t13\t13\n\
+            GAAGCAGTATTTT\t13\t10\t13\n\
+            AATATAGAGGCTG\t13\t12\t12\n\
+            CTGAAGCAGTATT\t13\t11\t13\n\
+            malformed line\n";
+        let off_target = parse_blastn_output(output, 2);
+        // too many mismatches, or the 3' end is not part of the hit
+        assert!(!off_target.contains("GAAGCAGTATTTT"));
+        assert!(!off_target.contains("AATATAGAGGCTG"));
+        assert!(off_target.contains("AGCCCGTGTAAAC"));
+        assert!(off_target.contains("CTGAAGCAGTATT"));
+        assert_eq!(off_target.len(), 2);
+    }
+}
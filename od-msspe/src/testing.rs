@@ -0,0 +1,117 @@
+//! Test fixtures, seeded synthetic genomes at a realistic scale and a passing primer.
+use crate::delta_g::three_prime_delta_g;
+use crate::tm::get_gc_percent;
+use crate::{Direction, KmerStat, SequenceRecord};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::ops::Range;
+
+const BASES: [char; 4] = ['A', 'C', 'G', 'T'];
+
+/// Parameters of the synthetic genomes, the same seed always generates the same genomes.
+pub struct SyntheticGenomes {
+    pub records: usize,
+    pub length: usize,
+    /// Regions identical in every genome
+    pub conserved: Vec<Range<usize>>,
+    /// Probability of a substitution at every site outside the conserved regions
+    pub mutation_rate: f64,
+    pub seed: u64,
+}
+
+impl SyntheticGenomes {
+    /// Genomes sharing a random ancestor, each with its own substitutions.
+    pub fn generate(&self) -> Vec<SequenceRecord> {
+        let mut rng = StdRng::seed_from_u64(self.seed);
+        let ancestor: Vec<char> = (0..self.length)
+            .map(|_| BASES[rng.gen_range(0..4)])
+            .collect();
+        (0..self.records)
+            .map(|i| {
+                let sequence: String = ancestor
+                    .iter()
+                    .enumerate()
+                    .map(|(pos, &base)| {
+                        let conserved = self.conserved.iter().any(|r| r.contains(&pos));
+                        match !conserved && rng.gen_bool(self.mutation_rate) {
+                            true => BASES[rng.gen_range(0..4)],
+                            false => base,
+                        }
+                    })
+                    .collect();
+                SequenceRecord::new(format!("synthetic{}", i + 1), sequence)
+            })
+            .collect()
+    }
+}
+
+/// Primer passing every criterion, tests override the fields they check.
+pub fn get_test_kmer_stat(word: &str, direction: Direction) -> KmerStat {
+    let gc_percent = get_gc_percent(word);
+    KmerStat {
+        word: word.to_string(),
+        direction,
+        name: None,
+        frequency: 1,
+        gc_percent,
+        gc_min: gc_percent,
+        gc_max: gc_percent,
+        mean: 45.0,
+        std: 0.0,
+        tm: 60.0,
+        tm_max: 60.0,
+        degeneracy: 1,
+        sequences: Vec::new(),
+        self_binding_count: 0,
+        constituents: Vec::new(),
+        tm_ok: true,
+        self_any_th: 0.0,
+        self_end_th: 0.0,
+        hairpin_th: 0.0,
+        three_prime_dg: three_prime_delta_g(word),
+        self_fold_dg: 0.0,
+        runs: false,
+        three_prime_run: false,
+        gc_clamp: true,
+        complexity_ok: true,
+        specificity_ok: true,
+        near_masked: false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generate() {
+        let genomes = SyntheticGenomes {
+            records: 5,
+            length: 200,
+            conserved: vec![0..30, 30..50],
+            mutation_rate: 0.5,
+            seed: 7,
+        };
+        let records = genomes.generate();
+        assert_eq!(records.len(), 5);
+        assert!(records.iter().all(|r| r.sequence.len() == 200));
+        assert!(
+            records
+                .iter()
+                .all(|r| r.sequence[..50] == records[0].sequence[..50])
+        );
+        assert!(
+            records
+                .iter()
+                .any(|r| r.sequence[50..] != records[0].sequence[50..])
+        );
+        // seeded
+        let again = genomes.generate();
+        assert!(
+            records
+                .iter()
+                .zip(&again)
+                .all(|(a, b)| a.sequence == b.sequence)
+        );
+    }
+}
@@ -0,0 +1,228 @@
+use crate::constants::WALLACE_MAX_LENGTH;
+use crate::iupac::get_gc_fraction;
+use crate::reverse_complement;
+
+/// Gas constant (cal/K·mol)
+const R: f64 = 1.987;
+
+/// SantaLucia (1998) unified nearest-neighbor parameters, ΔH (kcal/mol) and ΔS (cal/K·mol).
+fn nn_params(pair: &str) -> Option<(f64, f64)> {
+    match pair {
+        "AA" | "TT" => Some((-7.9, -22.2)),
+        "AT" => Some((-7.2, -20.4)),
+        "TA" => Some((-7.2, -21.3)),
+        "CA" | "TG" => Some((-8.5, -22.7)),
+        "GT" | "AC" => Some((-8.4, -22.4)),
+        "CT" | "AG" => Some((-7.8, -21.0)),
+        "GA" | "TC" => Some((-8.2, -22.2)),
+        "CG" => Some((-10.6, -27.2)),
+        "GC" => Some((-9.8, -24.4)),
+        "GG" | "CC" => Some((-8.0, -19.9)),
+        _ => None,
+    }
+}
+
+/// Initiation parameters for a terminal base pair, ΔH (kcal/mol) and ΔS (cal/K·mol).
+fn terminal_params(base: char) -> (f64, f64) {
+    match base {
+        'A' | 'T' => (2.3, 4.1),
+        _ => (0.1, -2.8),
+    }
+}
+
+/// Monovalent cation equivalent (mM) of the buffer, von Ahsen et al. (2001).
+pub fn monovalent_equivalent(mv: f64, dv: f64, dntp: f64) -> f64 {
+    if dv > dntp {
+        mv + 120.0 * (dv - dntp).sqrt()
+    } else {
+        mv
+    }
+}
+
+/**
+ * Calculate Tm using SantaLucia (1998) nearest-neighbor thermodynamics
+ *
+ * Salt correction is applied to ΔS as `0.368 * (N - 1) * ln([Na+])`, where divalent cations
+ * are converted to a monovalent equivalent. Concentrations are `mv`, `dv`, `dntp` in mM and
+ * `dna` in nM.
+ */
+pub fn get_tm_nn(kmer: &str, mv: f64, dv: f64, dntp: f64, dna: f64) -> f32 {
+    let bases: Vec<char> = kmer.chars().collect();
+    if bases.len() < 2 {
+        return 0.0;
+    }
+
+    let (mut dh, mut ds) = (0.0, 0.0);
+    for base in [bases[0], bases[bases.len() - 1]] {
+        let (h, s) = terminal_params(base);
+        dh += h;
+        ds += s;
+    }
+    for pair in bases.windows(2) {
+        let pair: String = pair.iter().collect();
+        if let Some((h, s)) = nn_params(&pair) {
+            dh += h;
+            ds += s;
+        }
+    }
+
+    let mon = monovalent_equivalent(mv, dv, dntp) / 1000.0;
+    ds += 0.368 * (bases.len() - 1) as f64 * mon.ln();
+
+    // self-complementary oligos anneal to themselves
+    let conc = if reverse_complement(kmer, None) == kmer {
+        ds += -1.4;
+        dna * 1e-9
+    } else {
+        dna / 4.0 * 1e-9
+    };
+
+    ((1000.0 * dh) / (ds + R * conc.ln()) - 273.15) as f32
+}
+
+/// Percentage of G and C bases in the k-mer.
+///
+/// IUPAC codes count by the fraction of their bases that are G or C (S fully, R or N by
+/// half), so a degenerate k-mer gets the mean GC of the sequences it stands for.
+///
+/// ```
+/// use od_msspe::tm::get_gc_percent;
+///
+/// assert_eq!(get_gc_percent("ACGTACGT"), 50.0);
+/// assert_eq!(get_gc_percent(""), 0.0);
+/// ```
+pub fn get_gc_percent(kmer: &str) -> f32 {
+    if kmer.is_empty() {
+        return 0.0;
+    }
+    100.0 * get_gc_count(kmer) as f32 / kmer.len() as f32
+}
+
+/// Number of G and C bases, IUPAC codes weighted as in `get_gc_percent`.
+fn get_gc_count(kmer: &str) -> f64 {
+    kmer.chars().map(|c| get_gc_fraction(c) as f64).sum()
+}
+
+/**
+ * Calculate Tm using the GC content formula `64.9 + 41 * (GC - 16.4) / N`
+ *
+ * The formula assumes 50mM Na+, a Schildkraut-Lippincott term `16.6 * log10([Na+] / 0.05)`
+ * shifts it to the monovalent equivalent of `mv`, `dv` and `dntp` (mM). IUPAC codes count
+ * toward GC as in `get_gc_percent`, giving the mean Tm of a degenerate k-mer's sequences.
+ *
+ * ```
+ * use od_msspe::tm::get_tm;
+ *
+ * // 50 mM Na+ without Mg2+ needs no salt correction
+ * let tm = get_tm("AGCCCGTGTAAACGATCAGT", 50.0, 0.0, 0.0);
+ * assert!((tm - 51.78).abs() < 0.01);
+ * ```
+ */
+pub fn get_tm(kmer: &str, mv: f64, dv: f64, dntp: f64) -> f32 {
+    if kmer.is_empty() {
+        return 0.0;
+    }
+    let gc = get_gc_count(kmer);
+    let tm = 64.9 + 41.0 * (gc - 16.4) / kmer.len() as f64;
+    let mon = monovalent_equivalent(mv, dv, dntp) / 1000.0;
+    (tm + 16.6 * (mon / 0.05).log10()) as f32
+}
+
+/// Wallace rule Tm `2 * (A + T) + 4 * (G + C)`, for short oligos without salt correction.
+/// IUPAC codes count toward GC as in `get_gc_percent`.
+pub fn get_tm_wallace(kmer: &str) -> f32 {
+    let gc = get_gc_count(kmer);
+    (2.0 * (kmer.len() as f64 - gc) + 4.0 * gc) as f32
+}
+
+/**
+ * Calculate Tm with the formula suited to the oligo length
+ *
+ * The GC content formula assumes long oligos and drops to implausible, even negative, values
+ * for short ones, so oligos shorter than `WALLACE_MAX_LENGTH` (14 nt) use the Wallace rule.
+ */
+pub fn get_tm_auto(kmer: &str, mv: f64, dv: f64, dntp: f64) -> f32 {
+    if kmer.len() < WALLACE_MAX_LENGTH {
+        return get_tm_wallace(kmer);
+    }
+    get_tm(kmer, mv, dv, dntp)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get_tm_nn() {
+        // Biopython Bio.SeqUtils.MeltingTemp.Tm_NN documented example (Na=50mM, 50nM oligo)
+        let tm = get_tm_nn("CGTTCCAAAGATGTGGGCATGAGCTTAC", 50.0, 0.0, 0.0, 50.0);
+        assert!((tm - 60.32).abs() < 0.05);
+
+        // primer3_core check_primers with its default conditions
+        let tm = get_tm_nn("AGCCCGTGTAAAC", 50.0, 1.5, 0.6, 50.0);
+        assert!((tm - 43.727).abs() < 0.05);
+    }
+
+    #[test]
+    fn test_get_gc_percent() {
+        assert_eq!(get_gc_percent("AGCCCGTGTAAAC"), 700.0 / 13.0);
+        assert_eq!(get_gc_percent("ATAT"), 0.0);
+        assert_eq!(get_gc_percent(""), 0.0);
+    }
+
+    #[test]
+    fn test_get_tm_salt() {
+        let reference = get_tm("AGCCCGTGTAAACAGCCCGTGTAAAC", 50.0, 0.0, 0.0);
+        assert!((reference - (64.9 + 41.0 * (14.0 - 16.4) / 26.0)).abs() < 0.001);
+
+        let low = get_tm("AGCCCGTGTAAACAGCCCGTGTAAAC", 20.0, 0.0, 0.0);
+        let high = get_tm("AGCCCGTGTAAACAGCCCGTGTAAAC", 100.0, 0.0, 0.0);
+        assert!(low < reference);
+        assert!(high > reference);
+
+        let with_mg = get_tm("AGCCCGTGTAAACAGCCCGTGTAAAC", 50.0, 3.0, 0.0);
+        assert!(with_mg > reference);
+    }
+
+    #[test]
+    fn test_get_tm_nn_salt() {
+        let low = get_tm_nn("AGCCCGTGTAAAC", 20.0, 0.0, 0.0, 250.0);
+        let high = get_tm_nn("AGCCCGTGTAAAC", 100.0, 0.0, 0.0, 250.0);
+        assert!(high > low);
+    }
+
+    #[test]
+    fn test_get_tm_auto() {
+        // the GC content formula is implausible for short oligos
+        let short = "ACGTACGT";
+        assert!(get_tm(short, 50.0, 0.0, 0.0) < 5.0);
+        assert_eq!(get_tm_wallace(short), 24.0);
+        assert_eq!(get_tm_auto(short, 50.0, 0.0, 0.0), 24.0);
+
+        let long = "CGTTCCAAAGATGTGGGCATGAGCTTAC";
+        let tm = get_tm_auto(long, 50.0, 0.0, 0.0);
+        assert_eq!(tm, get_tm(long, 50.0, 0.0, 0.0));
+        assert!(tm > 55.0 && tm < 70.0);
+    }
+
+    #[test]
+    fn test_ambiguous_bases() {
+        // S is G or C, R and N are GC by half, so the mean of the expansions
+        assert_eq!(get_gc_percent("ACGS"), 75.0);
+        assert_eq!(get_gc_percent("ACGR"), 62.5);
+        assert_eq!(get_gc_percent("ACGN"), 62.5);
+        assert_eq!(
+            get_gc_percent("ACGR"),
+            (get_gc_percent("ACGA") + get_gc_percent("ACGG")) / 2.0
+        );
+        assert_eq!(get_tm_wallace("ACGS"), get_tm_wallace("ACGC"));
+        assert_eq!(
+            get_tm_wallace("ACGN"),
+            (get_tm_wallace("ACGA") + get_tm_wallace("ACGC")) / 2.0
+        );
+        let tm = |kmer: &str| get_tm(kmer, 50.0, 0.0, 0.0);
+        assert_eq!(tm("ACGTACGTACGTACGTACGS"), tm("ACGTACGTACGTACGTACGC"));
+        let mean = (tm("ACGTACGTACGTACGTACGA") + tm("ACGTACGTACGTACGTACGG")) / 2.0;
+        assert!((tm("ACGTACGTACGTACGTACGR") - mean).abs() < 1e-4);
+    }
+}